@@ -0,0 +1,485 @@
+//! Alarms: scheduled playback at a local time of day.
+//!
+//! Definitions are kept in `alarms.json` in the app config directory. An alarm without weekdays
+//! goes off once, at the next occurrence of its time, and is then disabled. When an alarm goes
+//! off, the queue is optionally cleared, the stored or smart playlist is loaded and played, and
+//! the volume is raised from silence to the alarm's volume over its fade-in time.
+//!
+//! The background task checks the clock at least once a minute, so alarms still go off after
+//! the system clock changes or the machine wakes up, unless they are more than a few minutes
+//! late.
+
+use std::{collections::BTreeMap, fmt::Display, fs, path::PathBuf, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeZone};
+use mpd_client::{commands, Client};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    ipc::{self, ItemVariant},
+    playlist::{
+        smart::{self, SmartPlaylists},
+        PlaylistSource,
+    },
+};
+
+pub const ALARMS_FILE: &str = "alarms.json";
+/// Longest wait between checks of the clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Alarms missed by more than this, e.g. while the machine was asleep, are skipped.
+const MAX_LATENESS: Duration = Duration::from_secs(5 * 60);
+/// Time between volume steps while fading in.
+const FADE_STEP: Duration = Duration::from_secs(1);
+/// Mixers may report a volume a little off the one set; a fade only stops for larger changes.
+const FADE_TOLERANCE: u8 = 2;
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        match day {
+            chrono::Weekday::Mon => Weekday::Monday,
+            chrono::Weekday::Tue => Weekday::Tuesday,
+            chrono::Weekday::Wed => Weekday::Wednesday,
+            chrono::Weekday::Thu => Weekday::Thursday,
+            chrono::Weekday::Fri => Weekday::Friday,
+            chrono::Weekday::Sat => Weekday::Saturday,
+            chrono::Weekday::Sun => Weekday::Sunday,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub enum AlarmSource {
+    /// A stored playlist, by name.
+    Playlist(String),
+    /// A saved smart playlist, by name.
+    SmartPlaylist(String),
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Alarm {
+    pub name: String,
+    pub enabled: bool,
+    /// Local time of day.
+    pub hour: u32,
+    pub minute: u32,
+    /// Days to go off on; empty to go off once.
+    pub weekdays: Vec<Weekday>,
+    pub source: AlarmSource,
+    /// Clear the queue before loading the source.
+    pub replace_queue: bool,
+    /// Volume to play at, or the current volume.
+    pub volume: Option<u8>,
+    /// Seconds over which the volume is raised from 0.
+    pub fade_in: u32,
+}
+
+impl Alarm {
+    /// The first time strictly after `after` the alarm goes off, ignoring whether it's enabled.
+    ///
+    /// Times skipped by a DST change don't happen, so the alarm goes off on the next day.
+    fn next_after<Tz: TimeZone>(&self, after: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let time = NaiveTime::from_hms_opt(self.hour, self.minute, 0)?;
+        (0..=7).find_map(|days| {
+            let date = after.date_naive().checked_add_days(Days::new(days))?;
+            if !self.weekdays.is_empty() && !self.weekdays.contains(&date.weekday().into()) {
+                return None;
+            }
+            after
+                .timezone()
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+                .filter(|at| *at > after)
+        })
+    }
+}
+
+/// The alarm going off next, as reported to the UI.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct NextAlarm {
+    pub name: String,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+}
+
+pub struct Alarms {
+    /// `None` if there's no config directory, in which case alarms aren't persisted.
+    path: Option<PathBuf>,
+    alarms: Mutex<BTreeMap<String, Alarm>>,
+    wake: Notify,
+}
+
+impl Alarms {
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let path = dir.map(|dir| dir.join(ALARMS_FILE));
+        let alarms: Vec<Alarm> = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|raw| {
+                serde_json::from_slice(&raw)
+                    .map_err(|e| log::error!("Failed to load alarms: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            alarms: Mutex::new(alarms.into_iter().map(|a| (a.name.clone(), a)).collect()),
+            wake: Notify::new(),
+        }
+    }
+
+    fn persist(&self, alarms: &BTreeMap<String, Alarm>) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let list: Vec<&Alarm> = alarms.values().collect();
+        let raw = serde_json::to_vec_pretty(&list).map_err(|e| e.to_string())?;
+        fs::write(path, raw).map_err(|e| e.to_string())
+    }
+
+    pub fn list(&self) -> Vec<Alarm> {
+        self.alarms.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Add an alarm or replace the one with the same name.
+    pub fn save(&self, alarm: Alarm) -> Result<(), String> {
+        if alarm.hour > 23 || alarm.minute > 59 {
+            return Err(format!("Invalid time {}:{:02}", alarm.hour, alarm.minute));
+        }
+        if let Some(volume) = alarm.volume.filter(|&v| v > 100) {
+            return Err(format!("Invalid volume {}", volume));
+        }
+        let mut alarms = self.alarms.lock().unwrap();
+        alarms.insert(alarm.name.clone(), alarm);
+        self.persist(&alarms)?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Remove an alarm, returning whether it existed.
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        let mut alarms = self.alarms.lock().unwrap();
+        let existed = alarms.remove(name).is_some();
+        self.persist(&alarms)?;
+        self.wake.notify_one();
+        Ok(existed)
+    }
+
+    pub fn next(&self) -> Option<NextAlarm> {
+        let now = Local::now();
+        self.alarms
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|a| a.enabled)
+            .filter_map(|a| Some((a.next_after(now)?, &a.name)))
+            .min()
+            .map(|(at, name)| NextAlarm {
+                name: name.clone(),
+                at: at.timestamp().max(0) as u64,
+            })
+    }
+
+    /// Enabled alarms due in `(after, now]`, disabling the one-off ones.
+    fn take_due<Tz: TimeZone>(&self, after: DateTime<Tz>, now: DateTime<Tz>) -> Vec<Alarm>
+    where
+        Tz::Offset: Display,
+    {
+        let mut alarms = self.alarms.lock().unwrap();
+        let mut due = Vec::new();
+        let mut disabled = false;
+        for alarm in alarms.values_mut().filter(|a| a.enabled) {
+            let Some(at) = alarm.next_after(after.clone()).filter(|at| *at <= now) else {
+                continue;
+            };
+            if alarm.weekdays.is_empty() {
+                alarm.enabled = false;
+                disabled = true;
+            }
+            if (now.clone() - at.clone())
+                .to_std()
+                .is_ok_and(|late| late > MAX_LATENESS)
+            {
+                log::warn!("Skipped alarm '{}', missed at {}", alarm.name, at);
+                continue;
+            }
+            due.push(alarm.clone());
+        }
+        if disabled {
+            if let Err(e) = self.persist(&alarms) {
+                log::error!("Failed to save alarms: {}", e);
+            }
+        }
+        due
+    }
+}
+
+/// Raise the volume from 0 to `volume` over `duration`, stopping if it's changed meanwhile.
+async fn fade_in(handle: &AppHandle, volume: u8, duration: Duration) -> Result<(), String> {
+    let client = handle.state::<Client>();
+    let steps = (duration.as_secs_f64() / FADE_STEP.as_secs_f64())
+        .ceil()
+        .max(1.0) as u32;
+    let mut current = 0;
+    for step in 1..=steps {
+        tokio::time::sleep(FADE_STEP).await;
+        let status = client
+            .command(commands::Status)
+            .await
+            .map_err(|e| e.to_string())?;
+        if status.volume.abs_diff(current) > FADE_TOLERANCE {
+            return Ok(());
+        }
+        current = (volume as u32 * step / steps) as u8;
+        ipc::setvol(handle.state(), current).await?;
+    }
+    Ok(())
+}
+
+async fn ring(handle: &AppHandle, alarm: &Alarm) -> Result<(), String> {
+    let client = handle.state::<Client>();
+    let status = client
+        .command(commands::Status)
+        .await
+        .map_err(|e| e.to_string())?;
+    let first = if alarm.replace_queue {
+        client
+            .command(commands::ClearQueue)
+            .await
+            .map_err(|e| e.to_string())?;
+        0
+    } else {
+        status.playlist_length
+    };
+    match &alarm.source {
+        AlarmSource::Playlist(name) => ipc::load(handle.state(), name.clone(), None).await?,
+        AlarmSource::SmartPlaylist(name) => {
+            let store = handle.state::<SmartPlaylists>();
+            smart::materialize_saved(&client, &store, name, &PlaylistSource::Queue).await?;
+        }
+    }
+    let volume = alarm.volume.unwrap_or(status.volume);
+    let fade = Duration::from_secs(alarm.fade_in as u64);
+    let start = if fade.is_zero() { volume } else { 0 };
+    ipc::setvol(handle.state(), start).await?;
+    ipc::play(handle.state(), Some(ItemVariant::Position(first))).await?;
+    if !fade.is_zero() {
+        fade_in(handle, volume, fade).await?;
+    }
+    Ok(())
+}
+
+/// Ring alarms as they come due, emitting `alarm` with each one that went off.
+pub async fn alarm_task(handle: AppHandle) {
+    let alarms = handle.state::<Alarms>();
+    let mut checked = Local::now();
+    loop {
+        let now = Local::now();
+        for alarm in alarms.take_due(checked, now) {
+            if let Err(e) = handle.emit_all("alarm", alarm.clone()) {
+                log::error!("Failed to emit signal 'alarm': {}", e);
+            }
+            let handle = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = ring(&handle, &alarm).await {
+                    log::error!("Alarm '{}' failed: {}", alarm.name, e);
+                }
+            });
+        }
+        checked = now;
+        let wait = alarms
+            .next()
+            .and_then(|next| (next.at as i64 - now.timestamp()).try_into().ok())
+            .map_or(CHECK_INTERVAL, |secs: u64| {
+                Duration::from_secs(secs.max(1)).min(CHECK_INTERVAL)
+            });
+        let _ = tokio::time::timeout(wait, alarms.wake.notified()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, LocalResult, NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    /// UTC+1, moving to UTC+2 at 02:00 on 2024-03-31 like central Europe.
+    #[derive(Clone, Copy, Debug)]
+    struct Cet;
+
+    impl Cet {
+        fn switch() -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2024, 3, 31)
+                .unwrap()
+                .and_hms_opt(2, 0, 0)
+                .unwrap()
+        }
+
+        fn offset(summer: bool) -> FixedOffset {
+            FixedOffset::east_opt(if summer { 7200 } else { 3600 }).unwrap()
+        }
+    }
+
+    impl TimeZone for Cet {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Cet
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            LocalResult::Single(Cet::offset(*local > Cet::switch().date()))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let switch = Cet::switch();
+            if *local < switch {
+                LocalResult::Single(Cet::offset(false))
+            } else if *local < switch + chrono::Duration::hours(1) {
+                LocalResult::None
+            } else {
+                LocalResult::Single(Cet::offset(true))
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            Cet::offset(*utc > Cet::switch().date())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Cet::offset(*utc >= Cet::switch() - chrono::Duration::hours(1))
+        }
+    }
+
+    const EVERY_DAY: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    fn alarm(name: &str, hour: u32, minute: u32, weekdays: &[Weekday]) -> Alarm {
+        Alarm {
+            name: name.to_string(),
+            enabled: true,
+            hour,
+            minute,
+            weekdays: weekdays.to_vec(),
+            source: AlarmSource::Playlist("Morning".to_string()),
+            replace_queue: false,
+            volume: None,
+            fade_in: 0,
+        }
+    }
+
+    /// Local time in March 2024 in [`Cet`]; the 25th is a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Cet> {
+        Cet.with_ymd_and_hms(2024, 3, day, hour, minute, 0).unwrap()
+    }
+
+    fn april_first(hour: u32, minute: u32) -> DateTime<Cet> {
+        Cet.with_ymd_and_hms(2024, 4, 1, hour, minute, 0).unwrap()
+    }
+
+    fn names(alarms: Vec<Alarm>) -> Vec<String> {
+        alarms.into_iter().map(|a| a.name).collect()
+    }
+
+    #[test]
+    fn goes_off_on_its_weekdays() {
+        let daily = alarm("daily", 7, 0, &[]);
+        assert_eq!(daily.next_after(at(25, 6, 0)), Some(at(25, 7, 0)));
+        // Strictly after: not again at the same minute.
+        assert_eq!(daily.next_after(at(25, 7, 0)), Some(at(26, 7, 0)));
+
+        let weekend = alarm("weekend", 9, 30, &[Weekday::Saturday, Weekday::Sunday]);
+        assert_eq!(weekend.next_after(at(25, 6, 0)), Some(at(30, 9, 30)));
+        assert_eq!(weekend.next_after(at(30, 10, 0)), Some(at(31, 9, 30)));
+
+        let monday = alarm("monday", 7, 0, &[Weekday::Monday]);
+        assert_eq!(monday.next_after(at(25, 8, 0)), Some(april_first(7, 0)));
+    }
+
+    #[test]
+    fn skips_times_lost_to_dst() {
+        let early = alarm("early", 2, 30, &[]);
+        assert_eq!(early.next_after(at(30, 23, 0)), Some(april_first(2, 30)));
+        let after = alarm("after", 3, 0, &[]);
+        let three = after.next_after(at(30, 23, 0)).unwrap();
+        assert_eq!(three, at(31, 3, 0));
+        assert_eq!(three - at(31, 1, 0), chrono::Duration::hours(1));
+    }
+
+    #[test]
+    fn takes_due_alarms_and_disables_one_offs() {
+        let alarms = Alarms::open(None);
+        alarms.save(alarm("once", 7, 0, &[])).unwrap();
+        alarms
+            .save(alarm("weekdays", 7, 0, &[Weekday::Monday]))
+            .unwrap();
+        alarms.save(alarm("later", 8, 0, &[])).unwrap();
+        let mut off = alarm("off", 7, 0, &[]);
+        off.enabled = false;
+        alarms.save(off).unwrap();
+
+        assert_eq!(
+            names(alarms.take_due(at(25, 6, 59), at(25, 7, 1))),
+            ["once", "weekdays"]
+        );
+        let enabled: Vec<_> = alarms.list().into_iter().filter(|a| a.enabled).collect();
+        assert_eq!(names(enabled), ["later", "weekdays"]);
+        // Nothing is taken twice.
+        assert!(alarms.take_due(at(25, 7, 1), at(25, 7, 2)).is_empty());
+        assert_eq!(
+            names(alarms.take_due(at(26, 6, 59), at(26, 8, 0))),
+            ["later"]
+        );
+    }
+
+    #[test]
+    fn skips_alarms_missed_for_too_long() {
+        let alarms = Alarms::open(None);
+        alarms.save(alarm("once", 7, 0, &[])).unwrap();
+        alarms.save(alarm("daily", 7, 0, &EVERY_DAY)).unwrap();
+        // Asleep from 6:00 to 7:06.
+        assert!(alarms.take_due(at(25, 6, 0), at(25, 7, 6)).is_empty());
+        // The one-off alarm is used up all the same.
+        let enabled: Vec<_> = alarms.list().into_iter().filter(|a| a.enabled).collect();
+        assert_eq!(names(enabled), ["daily"]);
+        // Within the allowed lateness it still goes off.
+        assert_eq!(
+            names(alarms.take_due(at(26, 6, 0), at(26, 7, 4))),
+            ["daily"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_alarms() {
+        let alarms = Alarms::open(None);
+        assert!(alarms.save(alarm("late", 24, 0, &[])).is_err());
+        let mut loud = alarm("loud", 7, 0, &[]);
+        loud.volume = Some(101);
+        assert!(alarms.save(loud.clone()).is_err());
+        loud.volume = Some(100);
+        assert!(alarms.save(loud).is_ok());
+    }
+}
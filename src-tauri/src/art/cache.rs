@@ -0,0 +1,531 @@
+//! On-disk album art cache.
+//!
+//! Art is stored once per album (album artist + album, or the directory for untagged files)
+//! under `<cache dir>/<key hash>/`, as the original picture plus JPEG thumbnails. Entries are
+//! evicted least recently used first once the cache grows over its size limit, and are
+//! revalidated against the database after MPD reports a database change.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use image::ImageOutputFormat;
+use mpd_client::{commands, filter::Filter, responses::Song, tag::Tag, Client};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime;
+
+use super::fetch_art;
+
+/// Edge lengths of the generated thumbnails, in pixels.
+pub const THUMBNAIL_SIZES: [u32; 3] = [128, 256, 512];
+pub const DEFAULT_CACHE_LIMIT: u64 = 256 * 1024 * 1024;
+
+const INDEX_FILE: &str = "index.json";
+const FULL_FILE: &str = "full";
+const THUMBNAIL_QUALITY: u8 = 85;
+/// Flush the index after this many unsaved changes...
+const SAVE_EVERY: usize = 32;
+/// ...or when the last flush is older than this many seconds.
+const SAVE_INTERVAL: u64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Entry {
+    /// Song the art was fetched for.
+    uri: String,
+    last_modified: Option<String>,
+    /// `None` if the album has no art, so we don't ask MPD again.
+    mime: Option<String>,
+    thumbnails: Vec<u32>,
+    bytes: u64,
+    last_used: u64,
+    stale: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    /// Song URI to album key.
+    uris: HashMap<String, String>,
+    #[serde(skip)]
+    unsaved: usize,
+    #[serde(skip)]
+    last_saved: Option<Instant>,
+}
+
+/// A picture in the cache, ready to be served.
+pub struct CachedArt {
+    pub path: PathBuf,
+    pub mime: String,
+    /// Read while the index is locked, so eviction can't remove the file before it is served.
+    pub data: Vec<u8>,
+}
+
+pub struct ArtCache {
+    dir: PathBuf,
+    limit: AtomicU64,
+    index: Mutex<Index>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// FNV-1a, used to turn album keys into directory names that are stable across builds.
+fn hash_key(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Whether a stale entry can be kept: it has art and its source song, as (album key hash, last
+/// modification), still belongs to `key` and wasn't modified. Art may have been added for albums
+/// without any, so those are always refetched.
+fn unchanged(
+    key: &str,
+    has_art: bool,
+    last_modified: &Option<String>,
+    source: Option<(String, Option<String>)>,
+) -> bool {
+    has_art
+        && source
+            .is_some_and(|(source_key, modified)| source_key == key && modified == *last_modified)
+}
+
+/// The album a song belongs to, as used for cache keys.
+pub fn album_key(song: &Song) -> String {
+    let artist = song
+        .album_artists()
+        .first()
+        .or_else(|| song.artists().first());
+    match (artist, song.album()) {
+        (Some(artist), Some(album)) => format!("album:{}\u{1f}{}", artist, album),
+        _ => {
+            let dir = song.url.rsplit_once('/').map_or("", |(dir, _)| dir);
+            format!("dir:{}", dir)
+        }
+    }
+}
+
+/// Look up a single song of the database by URI.
+pub async fn find_song(client: &Client, uri: &str) -> Result<Option<Song>, String> {
+    let filter = Filter::tag(Tag::Other("file".into()), uri);
+    let songs = client
+        .command(commands::Find::new(filter))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(songs.into_iter().next())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+fn write_thumbnails(dir: &Path, data: &[u8]) -> Vec<u32> {
+    let image = match image::load_from_memory(data) {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("Failed to decode album art in {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    THUMBNAIL_SIZES
+        .into_iter()
+        .filter(|&size| {
+            let thumb = image.thumbnail(size, size).to_rgb8();
+            let mut buf = Cursor::new(Vec::new());
+            image::DynamicImage::ImageRgb8(thumb)
+                .write_to(&mut buf, ImageOutputFormat::Jpeg(THUMBNAIL_QUALITY))
+                .map_err(|e| e.to_string())
+                .and_then(|_| {
+                    fs::write(dir.join(format!("{}.jpg", size)), buf.into_inner())
+                        .map_err(|e| e.to_string())
+                })
+                .map_err(|e| log::warn!("Failed to write {}px thumbnail: {}", size, e))
+                .is_ok()
+        })
+        .collect()
+}
+
+impl ArtCache {
+    /// Open the cache in `dir`, dropping anything the index doesn't know about.
+    pub fn open(dir: PathBuf, limit: u64) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::error!("Failed to create art cache {}: {}", dir.display(), e);
+        }
+        let mut index: Index = fs::read(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+        index.entries.retain(|key, _| dir.join(key).is_dir());
+        let entries = &index.entries;
+        index.uris.retain(|_, key| entries.contains_key(key));
+        if let Ok(children) = fs::read_dir(&dir) {
+            for child in children.flatten() {
+                let name = child.file_name();
+                let known = name.to_str().is_some_and(|n| entries.contains_key(n));
+                if child.path().is_dir() && !known {
+                    let _ = fs::remove_dir_all(child.path());
+                }
+            }
+        }
+        let cache = Self {
+            dir,
+            limit: AtomicU64::new(limit),
+            index: Mutex::new(index),
+        };
+        cache.evict();
+        cache
+    }
+
+    /// Change the size limit, evicting entries right away if the cache is over it.
+    pub fn set_limit(&self, limit: u64) {
+        if self.limit.swap(limit, Ordering::Relaxed) != limit {
+            self.evict();
+        }
+    }
+
+    /// Mark every entry for revalidation, called when the Database subsystem changes.
+    pub fn invalidate(&self) {
+        let mut index = self.index.lock().unwrap();
+        for entry in index.entries.values_mut() {
+            entry.stale = true;
+        }
+        index.uris.clear();
+        index.unsaved += 1;
+        self.save(&mut index, true);
+    }
+
+    /// Get the art of the album `uri` belongs to if it is cached, without asking MPD.
+    ///
+    /// `None` means unknown or stale, `Some(None)` that the album has no art.
+    pub fn peek(&self, uri: &str, size: Option<u32>) -> Option<Option<CachedArt>> {
+        let key = self.index.lock().unwrap().uris.get(uri).cloned()?;
+        self.serve(&key, size)
+    }
+
+    /// Get the art of the album `uri` belongs to, fetching it from MPD on a miss.
+    ///
+    /// With `size`, the smallest thumbnail covering it is returned instead of the original.
+    pub async fn get(
+        &self,
+        client: &Client,
+        uri: &str,
+        size: Option<u32>,
+    ) -> Result<Option<CachedArt>, String> {
+        if let Some(art) = self.peek(uri, size) {
+            return Ok(art);
+        }
+
+        let Some(song) = find_song(client, uri).await? else {
+            return Ok(None);
+        };
+        let key = hash_key(&album_key(&song));
+        let source = {
+            let index = self.index.lock().unwrap();
+            index
+                .entries
+                .get(&key)
+                .filter(|e| e.stale)
+                .map(|e| (e.uri.clone(), e.last_modified.clone(), e.mime.is_some()))
+        };
+        if let Some((source_uri, last_modified, has_art)) = source {
+            let source_song = if source_uri == uri {
+                Some(song.clone())
+            } else {
+                find_song(client, &source_uri).await?
+            };
+            let source = source_song.map(|s| {
+                let modified = s.last_modified.as_ref().map(|t| t.raw().to_string());
+                (hash_key(&album_key(&s)), modified)
+            });
+            self.revalidate(&key, unchanged(&key, has_art, &last_modified, source));
+        }
+
+        let cached = {
+            let mut index = self.index.lock().unwrap();
+            let cached = index.entries.contains_key(&key);
+            if cached {
+                index.uris.insert(uri.to_string(), key.clone());
+            }
+            cached
+        };
+        if !cached {
+            let art = fetch_art(client, uri).await?;
+            let modified = song.last_modified.as_ref().map(|t| t.raw().to_string());
+            self.insert(&key, uri, modified, art).await?;
+        }
+        Ok(self.serve(&key, size).flatten())
+    }
+
+    /// Keep a stale entry if its source song is `unchanged`, otherwise drop it to be refetched.
+    fn revalidate(&self, key: &str, unchanged: bool) {
+        let mut index = self.index.lock().unwrap();
+        if unchanged {
+            if let Some(entry) = index.entries.get_mut(key) {
+                entry.stale = false;
+            }
+        } else {
+            index.entries.remove(key);
+            let _ = fs::remove_dir_all(self.dir.join(key));
+        }
+        index.unsaved += 1;
+    }
+
+    fn serve(&self, key: &str, size: Option<u32>) -> Option<Option<CachedArt>> {
+        let mut index = self.index.lock().unwrap();
+        let entry = index.entries.get_mut(key).filter(|e| !e.stale)?;
+        entry.last_used = now();
+        // Saved along with other changes, so eviction stays least recently used after a restart.
+        index.unsaved += 1;
+        self.save(&mut index, false);
+        let entry = &index.entries[key];
+        let Some(mime) = entry.mime.clone() else {
+            return Some(None);
+        };
+        let thumbnail = size.and_then(|size| {
+            entry
+                .thumbnails
+                .iter()
+                .copied()
+                .filter(|&t| t >= size)
+                .min()
+        });
+        let dir = self.dir.join(key);
+        let (path, mime) = match thumbnail {
+            Some(t) => (dir.join(format!("{}.jpg", t)), "image/jpeg".to_string()),
+            None => (dir.join(FULL_FILE), mime),
+        };
+        match fs::read(&path) {
+            Ok(data) => Some(Some(CachedArt { path, mime, data })),
+            Err(e) => {
+                // Removed behind our back; drop the entry so the art is fetched again.
+                log::warn!("Failed to read cached art {}: {}", path.display(), e);
+                index.entries.remove(key);
+                index.uris.retain(|_, k| k != key);
+                index.unsaved += 1;
+                None
+            }
+        }
+    }
+
+    /// Store the art fetched for the song at `uri`, decoding it and writing the thumbnails on
+    /// a blocking thread.
+    async fn insert(
+        &self,
+        key: &str,
+        uri: &str,
+        last_modified: Option<String>,
+        art: Option<(Vec<u8>, String)>,
+    ) -> Result<(), String> {
+        let dir = self.dir.join(key);
+        let written = dir.clone();
+        let (mime, thumbnails) = async_runtime::spawn_blocking(move || {
+            fs::create_dir_all(&written).map_err(|e| e.to_string())?;
+            let Some((data, mime)) = art else {
+                return Ok((None, Vec::new()));
+            };
+            fs::write(written.join(FULL_FILE), &data).map_err(|e| e.to_string())?;
+            Ok::<_, String>((Some(mime), write_thumbnails(&written, &data)))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        let entry = Entry {
+            uri: uri.to_string(),
+            last_modified,
+            mime,
+            thumbnails,
+            bytes: dir_size(&dir),
+            last_used: now(),
+            stale: false,
+        };
+        {
+            let mut index = self.index.lock().unwrap();
+            index.entries.insert(key.to_string(), entry);
+            index.uris.insert(uri.to_string(), key.to_string());
+            index.unsaved += 1;
+        }
+        self.evict();
+        Ok(())
+    }
+
+    /// Drop least recently used entries until the cache fits its limit.
+    fn evict(&self) {
+        let mut index = self.index.lock().unwrap();
+        let limit = self.limit.load(Ordering::Relaxed);
+        let mut total: u64 = index.entries.values().map(|e| e.bytes).sum();
+        while total > limit {
+            let Some(key) = index
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = index.entries.remove(&key) {
+                total -= entry.bytes;
+            }
+            index.uris.retain(|_, k| *k != key);
+            let _ = fs::remove_dir_all(self.dir.join(&key));
+            index.unsaved += 1;
+        }
+        self.save(&mut index, false);
+    }
+
+    fn save(&self, index: &mut Index, force: bool) {
+        if index.unsaved == 0 {
+            return;
+        }
+        let due = index
+            .last_saved
+            .is_none_or(|t| t.elapsed().as_secs() >= SAVE_INTERVAL);
+        if !(force || due || index.unsaved >= SAVE_EVERY) {
+            return;
+        }
+        match serde_json::to_vec(&*index) {
+            Ok(raw) => {
+                if let Err(e) = fs::write(self.dir.join(INDEX_FILE), raw) {
+                    log::error!("Failed to save art cache index: {}", e);
+                }
+            }
+            Err(e) => log::error!("Failed to serialize art cache index: {}", e),
+        }
+        index.unsaved = 0;
+        index.last_saved = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::DynamicImage::new_rgb8(width, height);
+        let mut buf = Cursor::new(Vec::new());
+        image.write_to(&mut buf, ImageOutputFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    fn insert(cache: &ArtCache, key: &str, uri: &str, art: Option<(Vec<u8>, String)>) {
+        async_runtime::block_on(cache.insert(key, uri, None, art)).unwrap();
+    }
+
+    #[test]
+    fn hashes_keys_with_fnv1a() {
+        assert_eq!(hash_key(""), "cbf29ce484222325");
+        assert_eq!(hash_key("a"), "af63dc4c8601ec8c");
+        assert_ne!(hash_key("album:A\u{1f}B"), hash_key("album:A\u{1f}C"));
+    }
+
+    #[test]
+    fn keeps_only_unchanged_stale_entries() {
+        let modified = Some("2023-01-01T00:00:00Z".to_string());
+        let source =
+            |key: &str, modified: &Option<String>| Some((key.to_string(), modified.clone()));
+        assert!(unchanged("k", true, &modified, source("k", &modified)));
+        // Modified, moved to another album or removed from the database.
+        assert!(!unchanged("k", true, &modified, source("k", &None)));
+        assert!(!unchanged("k", true, &modified, source("other", &modified)));
+        assert!(!unchanged("k", true, &modified, None));
+        // Albums without art are always asked for again.
+        assert!(!unchanged("k", false, &modified, source("k", &modified)));
+    }
+
+    #[test]
+    fn serves_originals_and_thumbnails() {
+        let dir = std::env::temp_dir().join(format!("rmpd-art-serve-{}", std::process::id()));
+        let cache = ArtCache::open(dir.clone(), DEFAULT_CACHE_LIMIT);
+        let data = png(300, 200);
+        insert(
+            &cache,
+            "a",
+            "a/1.flac",
+            Some((data.clone(), "image/png".to_string())),
+        );
+        insert(&cache, "b", "b/1.flac", None);
+
+        let full = cache.peek("a/1.flac", None).unwrap().unwrap();
+        assert_eq!((full.mime.as_str(), full.data), ("image/png", data));
+        let thumb = cache.peek("a/1.flac", Some(200)).unwrap().unwrap();
+        assert_eq!(thumb.path, dir.join("a").join("256.jpg"));
+        assert_eq!(thumb.mime, "image/jpeg");
+        assert!(cache.peek("b/1.flac", None).unwrap().is_none());
+        assert!(cache.peek("c/1.flac", None).is_none());
+
+        // Touches are counted as changes to the index.
+        cache.index.lock().unwrap().unsaved = 0;
+        cache.peek("a/1.flac", None);
+        assert_eq!(cache.index.lock().unwrap().unsaved, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let dir = std::env::temp_dir().join(format!("rmpd-art-evict-{}", std::process::id()));
+        let cache = ArtCache::open(dir.clone(), DEFAULT_CACHE_LIMIT);
+        let data = vec![0u8; 1000];
+        for key in ["a", "b", "c"] {
+            let uri = format!("{}/1.flac", key);
+            insert(
+                &cache,
+                key,
+                &uri,
+                Some((data.clone(), "image/png".to_string())),
+            );
+        }
+        {
+            let mut index = cache.index.lock().unwrap();
+            index.entries.get_mut("a").unwrap().last_used = 30;
+            index.entries.get_mut("b").unwrap().last_used = 10;
+            index.entries.get_mut("c").unwrap().last_used = 20;
+        }
+        cache.set_limit(2000);
+        let index = cache.index.lock().unwrap();
+        let mut kept: Vec<_> = index.entries.keys().cloned().collect();
+        kept.sort();
+        assert_eq!(kept, ["a", "c"]);
+        assert!(!index.uris.contains_key("b/1.flac"));
+        assert!(!dir.join("b").exists());
+        drop(index);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn revalidates_stale_entries() {
+        let dir = std::env::temp_dir().join(format!("rmpd-art-stale-{}", std::process::id()));
+        let cache = ArtCache::open(dir.clone(), DEFAULT_CACHE_LIMIT);
+        let art = || Some((vec![0u8; 10], "image/png".to_string()));
+        insert(&cache, "a", "a/1.flac", art());
+        insert(&cache, "b", "b/1.flac", art());
+        cache.invalidate();
+        assert!(cache.peek("a/1.flac", None).is_none());
+        assert!(cache.serve("a", None).is_none());
+
+        cache.revalidate("a", true);
+        assert!(cache.serve("a", None).unwrap().is_some());
+        cache.revalidate("b", false);
+        assert!(cache.serve("b", None).is_none());
+        assert!(!dir.join("b").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
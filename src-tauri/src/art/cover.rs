@@ -0,0 +1,273 @@
+//! Resolving a song to the cover image served for it by the `mpdart://` protocol.
+//!
+//! The chain is: image files next to the song under the music directory, matched against the
+//! configured filename patterns in order, then the art MPD serves through `albumart` and
+//! `readpicture` (stored in the [`ArtCache`]), then the bundled placeholder.
+//!
+//! Cover files found next to songs are remembered per directory, so [`CoverResolver::lookup`]
+//! can answer from memory and the cache without touching MPD.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use mpd_client::Client;
+use tokio::sync::OnceCell;
+
+use crate::{
+    extension::GetConfig,
+    scope::{is_within, join_within},
+};
+
+use super::{cache::CachedArt, ArtCache};
+
+pub const PLACEHOLDER_RESOURCE: &str = "resources/placeholder.png";
+
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| open + i) else {
+        return vec![pattern.to_string()];
+    };
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alt| expand_braces(&format!("{}{}{}", head, alt, tail)))
+        .collect()
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Match a filename against a cover pattern, ignoring case.
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    expand_braces(&pattern.to_lowercase()).iter().any(|p| {
+        let p: Vec<char> = p.chars().collect();
+        wildcard_match(&p, &name)
+    })
+}
+
+/// Find the first file in `dir` matching `patterns`, in pattern order.
+fn find_cover_file(dir: &Path, patterns: &[String]) -> Option<PathBuf> {
+    let mut files: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| Some((e.file_name().into_string().ok()?, e.path())))
+        .collect();
+    files.sort();
+    patterns.iter().find_map(|pattern| {
+        files
+            .iter()
+            .find(|(name, _)| pattern_matches(pattern, name))
+            .map(|(_, path)| path.clone())
+    })
+}
+
+/// Ask MPD for its music directory.
+///
+/// `config` is only allowed on local socket connections, so if the managed connection is refused
+/// the usual socket locations are tried as well.
+async fn query_music_directory(client: &Client) -> Option<String> {
+    if let Ok(config) = client.command(GetConfig).await {
+        return config.music_directory;
+    }
+    #[cfg(unix)]
+    {
+        let mut sockets = vec![
+            PathBuf::from("/run/mpd/socket"),
+            PathBuf::from("/var/run/mpd/socket"),
+        ];
+        if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") {
+            sockets.insert(0, PathBuf::from(runtime).join("mpd/socket"));
+        }
+        for socket in sockets {
+            let Ok(stream) = tokio::net::UnixStream::connect(&socket).await else {
+                continue;
+            };
+            let Ok((local, _)) = Client::connect(stream).await else {
+                continue;
+            };
+            if let Ok(config) = local.command(GetConfig).await {
+                return config.music_directory;
+            }
+        }
+    }
+    None
+}
+
+/// A resolved cover file, `mime` being `None` if it has to be sniffed from the data.
+pub struct Cover {
+    pub path: PathBuf,
+    pub mime: Option<String>,
+    /// Contents of `path` if they were already read, as they are for cached art.
+    pub data: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct ResolverConfig {
+    /// Overrides the music directory reported by MPD.
+    music_directory: Option<String>,
+    patterns: Vec<String>,
+}
+
+/// Configured from the `music_directory` and `cover_patterns` settings.
+pub struct CoverResolver {
+    config: RwLock<ResolverConfig>,
+    /// Only set once MPD reported it, so a failed query is retried on the next lookup.
+    mpd_music_directory: OnceCell<String>,
+    /// Cover file found in each song directory, if any.
+    local: Mutex<HashMap<String, Option<PathBuf>>>,
+    /// Songs whose cover is being resolved in the background.
+    pending: Mutex<HashSet<String>>,
+    placeholder: PathBuf,
+}
+
+/// Directory of the song at `uri`, relative to the music directory.
+fn song_dir(uri: &str) -> &str {
+    uri.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+impl CoverResolver {
+    pub fn new(placeholder: PathBuf) -> Self {
+        Self {
+            config: RwLock::default(),
+            mpd_music_directory: OnceCell::new(),
+            local: Mutex::default(),
+            pending: Mutex::default(),
+            placeholder,
+        }
+    }
+
+    pub fn configure(&self, music_directory: Option<String>, patterns: Vec<String>) {
+        *self.config.write().unwrap() = ResolverConfig {
+            music_directory,
+            patterns,
+        };
+        self.forget();
+    }
+
+    /// Forget the cover files found so far, e.g. after the database changed.
+    pub fn forget(&self) {
+        self.local.lock().unwrap().clear();
+    }
+
+    pub fn placeholder(&self) -> &Path {
+        &self.placeholder
+    }
+
+    /// Mark the cover of `uri` as being resolved, `false` if it already is.
+    pub fn start_pending(&self, uri: &str) -> bool {
+        self.pending.lock().unwrap().insert(uri.to_string())
+    }
+
+    pub fn finish_pending(&self, uri: &str) {
+        self.pending.lock().unwrap().remove(uri);
+    }
+
+    pub async fn music_directory(&self, client: &Client) -> Option<PathBuf> {
+        if let Some(dir) = self.config.read().unwrap().music_directory.clone() {
+            return Some(PathBuf::from(dir));
+        }
+        if let Some(dir) = self.mpd_music_directory.get() {
+            return Some(PathBuf::from(dir));
+        }
+        let dir = query_music_directory(client).await?;
+        if self.mpd_music_directory.set(dir.clone()).is_ok() {
+            // Directories looked at without a music directory are worth another look.
+            self.forget();
+        }
+        Some(PathBuf::from(dir))
+    }
+
+    /// Find a cover file next to the song at `uri`, only ever returning files inside the music
+    /// directory.
+    async fn local_cover(&self, client: &Client, uri: &str) -> Option<PathBuf> {
+        let parent = song_dir(uri);
+        let patterns = self.config.read().unwrap().patterns.clone();
+        let cover = self.music_directory(client).await.and_then(|music_dir| {
+            join_within(&music_dir, parent)
+                .and_then(|dir| find_cover_file(&dir, &patterns))
+                .filter(|path| is_within(path, &music_dir))
+        });
+        self.local
+            .lock()
+            .unwrap()
+            .insert(parent.to_string(), cover.clone());
+        cover
+    }
+
+    /// Resolve the cover of the song at `uri` from what is already known, `None` if that takes
+    /// asking MPD, which [`CoverResolver::resolve`] does.
+    pub fn lookup(&self, cache: &ArtCache, uri: &str, size: Option<u32>) -> Option<Cover> {
+        if let Some(path) = self.local.lock().unwrap().get(song_dir(uri))? {
+            return Some(Cover {
+                path: path.clone(),
+                mime: None,
+                data: None,
+            });
+        }
+        Some(match cache.peek(uri, size)? {
+            Some(CachedArt { path, mime, data }) => Cover {
+                path,
+                mime: Some(mime),
+                data: Some(data),
+            },
+            None => Cover {
+                path: self.placeholder.clone(),
+                mime: None,
+                data: None,
+            },
+        })
+    }
+
+    /// Resolve the cover of the song at `uri`.
+    ///
+    /// `size` selects a thumbnail of art from the cache; local files are served as they are.
+    pub async fn resolve(
+        &self,
+        client: &Client,
+        cache: &ArtCache,
+        uri: &str,
+        size: Option<u32>,
+    ) -> Cover {
+        if let Some(path) = self.local_cover(client, uri).await {
+            return Cover {
+                path,
+                mime: None,
+                data: None,
+            };
+        }
+        match cache.get(client, uri, size).await {
+            Ok(Some(CachedArt { path, mime, data })) => {
+                return Cover {
+                    path,
+                    mime: Some(mime),
+                    data: Some(data),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to fetch album art of '{}': {}", uri, e),
+        }
+        Cover {
+            path: self.placeholder.clone(),
+            mime: None,
+            data: None,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! `mpdart://` protocol serving album art straight to the webview.
+//!
+//! The frontend points `<img>` tags at `convertFileSrc(song.url, "mpdart")`, optionally with a
+//! `?size=<px>` query to get a thumbnail. The handler answers with the cover picked by the
+//! [`CoverResolver`], which falls back to the [`ArtCache`] (fetching every chunk of the picture
+//! from MPD on a miss) and finally to the bundled placeholder. This is the only way the webview
+//! gets to see files from the music and cache directories.
+//!
+//! The handler runs on the UI thread, so it only serves covers that are already known. Others
+//! get the placeholder, uncached, while they are resolved in the background; `art` is emitted
+//! with the song URI once they can be served.
+
+pub mod cache;
+pub mod cover;
+
+use std::{error::Error, fs};
+
+use mpd_client::{
+    client::CommandError,
+    commands::{self, Command},
+    responses, Client,
+};
+use percent_encoding::percent_decode_str;
+use tauri::{
+    async_runtime,
+    http::{status::StatusCode, Request, Response, ResponseBuilder},
+    AppHandle, Manager,
+};
+
+use self::cover::Cover;
+
+pub use cache::ArtCache;
+pub use cover::CoverResolver;
+
+pub const ART_SCHEME: &str = "mpdart";
+
+const ACK_ERROR_UNKNOWN: u64 = 5;
+const ACK_ERROR_NO_EXIST: u64 = 50;
+
+/// Extract the song URI from a request URL.
+///
+/// The URL is `mpdart://localhost/<uri>` on Linux and macOS and
+/// `https://mpdart.localhost/<uri>` on Windows, with `<uri>` percent-encoded.
+fn song_uri(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let (_, path) = rest.split_once('/')?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let uri = percent_decode_str(path).decode_utf8().ok()?;
+    if uri.is_empty() {
+        None
+    } else {
+        Some(uri.into_owned())
+    }
+}
+
+/// Extract the requested thumbnail size from the `size` query parameter.
+fn requested_size(url: &str) -> Option<u32> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("size="))
+        .and_then(|size| size.parse().ok())
+}
+
+/// Guess the MIME type of an image from its magic bytes.
+pub fn sniff_mime(data: &[u8]) -> &'static str {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'B', b'M', ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Read a picture in as many chunks as it takes, `None` if the server has none.
+async fn read_chunks<F, C>(
+    client: &Client,
+    command: F,
+) -> Result<Option<(Vec<u8>, Option<String>)>, CommandError>
+where
+    F: Fn(usize) -> C,
+    C: Command<Response = Option<responses::AlbumArt>>,
+{
+    let mut data = Vec::new();
+    let mut mime = None;
+    loop {
+        let chunk = match client.command(command(data.len())).await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => return Ok(None),
+            // No such file / unknown command (`readpicture` before MPD 0.22).
+            Err(CommandError::ErrorResponse { error, .. })
+                if error.code == ACK_ERROR_NO_EXIST || error.code == ACK_ERROR_UNKNOWN =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        if chunk.size == 0 || chunk.data.is_empty() {
+            return Ok(None);
+        }
+        if data.is_empty() {
+            data.reserve(chunk.size);
+            mime = chunk.mime;
+        }
+        data.extend_from_slice(&chunk.data);
+        if data.len() >= chunk.size {
+            return Ok(Some((data, mime)));
+        }
+    }
+}
+
+/// Fetch the complete album art of `uri`: the cover file in its directory through `albumart`,
+/// then the picture embedded in the song through `readpicture`.
+pub async fn fetch_art(client: &Client, uri: &str) -> Result<Option<(Vec<u8>, String)>, String> {
+    let mut res = read_chunks(client, |offset| commands::AlbumArt::new(uri).offset(offset))
+        .await
+        .map_err(|e| e.to_string())?;
+    if res.is_none() {
+        res = read_chunks(client, |offset| {
+            commands::AlbumArtEmbedded::new(uri).offset(offset)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(res.map(|(data, mime)| {
+        let mime = mime.unwrap_or_else(|| sniff_mime(&data).to_string());
+        (data, mime)
+    }))
+}
+
+fn status(code: StatusCode) -> Result<Response, Box<dyn Error>> {
+    ResponseBuilder::new().status(code).body(Vec::new())
+}
+
+/// Resolve the cover of `uri` in the background, emitting `art` once it can be served.
+fn resolve_later(handle: &AppHandle, uri: String) {
+    if !handle.state::<CoverResolver>().start_pending(&uri) {
+        return;
+    }
+    let handle = handle.clone();
+    async_runtime::spawn(async move {
+        let resolver = handle.state::<CoverResolver>();
+        let cache = handle.state::<ArtCache>();
+        resolver
+            .resolve(&handle.state::<Client>(), &cache, &uri, None)
+            .await;
+        resolver.finish_pending(&uri);
+        // Failures, e.g. while MPD is away, are tried again on the next request.
+        if resolver.lookup(&cache, &uri, None).is_none() {
+            return;
+        }
+        if let Err(e) = handle.emit_all("art", uri) {
+            log::error!("Failed to emit signal 'art': {}", e);
+        }
+    });
+}
+
+pub fn art_protocol(handle: &AppHandle, request: &Request) -> Result<Response, Box<dyn Error>> {
+    let Some(uri) = song_uri(request.uri()) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let size = requested_size(request.uri());
+    let resolver = handle.state::<CoverResolver>();
+    let (cover, cache_control) = match resolver.lookup(&handle.state::<ArtCache>(), &uri, size) {
+        Some(cover) => (cover, "max-age=3600"),
+        None => {
+            resolve_later(handle, uri);
+            let placeholder = Cover {
+                path: resolver.placeholder().to_path_buf(),
+                mime: None,
+                data: None,
+            };
+            (placeholder, "no-store")
+        }
+    };
+    match cover.data.map_or_else(|| fs::read(&cover.path), Ok) {
+        Ok(data) => {
+            let mime = cover.mime.unwrap_or_else(|| sniff_mime(&data).to_string());
+            ResponseBuilder::new()
+                .status(StatusCode::OK)
+                .mimetype(&mime)
+                .header("Cache-Control", cache_control)
+                .body(data)
+        }
+        Err(e) => {
+            log::error!("Failed to read cover {}: {}", cover.path.display(), e);
+            status(StatusCode::NOT_FOUND)
+        }
+    }
+}
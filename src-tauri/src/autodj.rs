@@ -0,0 +1,293 @@
+//! Auto-DJ: keeps a few songs queued after the current one.
+//!
+//! The background task wakes up on `player` and `queue` changes; when fewer than
+//! [`AutoDjConfig::min_upcoming`] songs follow the current one it appends songs picked from the
+//! configured source, skipping songs played or added recently.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+};
+
+use mpd_client::{commands, responses::Song, Client};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    ipc::Filter,
+    library::Library,
+    playlist::smart::{self, SmartPlaylists},
+};
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub enum AutoDjSource {
+    /// A saved smart playlist, by name.
+    SmartPlaylist(String),
+    Filter(Filter),
+    /// A whole album at a time, picked at random from the library.
+    RandomAlbum,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct AutoDjConfig {
+    pub source: AutoDjSource,
+    /// Songs to keep queued after the current one.
+    pub min_upcoming: usize,
+    /// Number of recently played or added songs that won't be picked again.
+    pub history: usize,
+}
+
+impl Default for AutoDjConfig {
+    fn default() -> Self {
+        Self {
+            source: AutoDjSource::RandomAlbum,
+            min_upcoming: 5,
+            history: 100,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct AutoDjStatus {
+    pub running: bool,
+    pub config: AutoDjConfig,
+    /// Songs added since the Auto-DJ was started.
+    pub added: usize,
+    /// Why the last attempt to fill the queue failed.
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    running: bool,
+    config: AutoDjConfig,
+    added: usize,
+    error: Option<String>,
+    recent: VecDeque<String>,
+}
+
+impl Inner {
+    fn status(&self) -> AutoDjStatus {
+        AutoDjStatus {
+            running: self.running,
+            config: self.config.clone(),
+            added: self.added,
+            error: self.error.clone(),
+        }
+    }
+
+    fn remember(&mut self, uri: &str) {
+        if self.recent.iter().any(|u| u == uri) {
+            return;
+        }
+        self.recent.push_back(uri.to_string());
+        while self.recent.len() > self.config.history {
+            self.recent.pop_front();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AutoDj {
+    inner: Mutex<Inner>,
+    wake: Notify,
+}
+
+impl AutoDj {
+    pub fn status(&self) -> AutoDjStatus {
+        self.inner.lock().unwrap().status()
+    }
+
+    /// Start filling the queue, optionally with a new configuration.
+    pub fn start(&self, config: Option<AutoDjConfig>) -> AutoDjStatus {
+        let status = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(config) = config {
+                inner.config = config;
+            }
+            if !inner.running {
+                inner.running = true;
+                inner.added = 0;
+            }
+            inner.error = None;
+            inner.status()
+        };
+        self.poke();
+        status
+    }
+
+    pub fn stop(&self) -> AutoDjStatus {
+        let mut inner = self.inner.lock().unwrap();
+        inner.running = false;
+        inner.status()
+    }
+
+    pub fn configure(&self, config: AutoDjConfig) -> AutoDjStatus {
+        let status = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.config = config;
+            while inner.recent.len() > inner.config.history {
+                inner.recent.pop_front();
+            }
+            inner.status()
+        };
+        self.poke();
+        status
+    }
+
+    /// Have the background task check the queue.
+    pub fn poke(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Songs the source currently yields, or `None` to pick a random album.
+async fn candidates(
+    handle: &AppHandle,
+    client: &Client,
+    source: &AutoDjSource,
+) -> Result<Option<Vec<Song>>, String> {
+    match source {
+        AutoDjSource::SmartPlaylist(name) => {
+            let playlist = handle
+                .state::<SmartPlaylists>()
+                .get(name)
+                .ok_or_else(|| format!("No smart playlist named '{}'", name))?;
+            smart::songs(client, &playlist).await.map(Some)
+        }
+        AutoDjSource::Filter(filter) => {
+            let Some(filter) = filter.clone().render().map_err(|e| e.to_string())? else {
+                return Err("Empty filter.".to_string());
+            };
+            client
+                .command(commands::Find::new(filter))
+                .await
+                .map(Some)
+                .map_err(|e| e.to_string())
+        }
+        AutoDjSource::RandomAlbum => Ok(None),
+    }
+}
+
+/// URIs to append, avoiding `avoid` unless the source has nothing else to offer.
+async fn pick(
+    handle: &AppHandle,
+    client: &Client,
+    source: &AutoDjSource,
+    count: usize,
+    avoid: &HashSet<String>,
+) -> Result<Vec<String>, String> {
+    let candidates = candidates(handle, client, source).await?;
+    let mut rng = rand::thread_rng();
+    match candidates {
+        Some(songs) => {
+            let fresh: Vec<&Song> = songs.iter().filter(|s| !avoid.contains(&s.url)).collect();
+            let pool: Vec<&Song> = if fresh.is_empty() {
+                songs.iter().collect()
+            } else {
+                fresh
+            };
+            Ok(pool
+                .choose_multiple(&mut rng, count)
+                .map(|s| s.url.clone())
+                .collect())
+        }
+        None => {
+            let albums = handle.state::<Library>().albums(None);
+            let album = albums
+                .iter()
+                .filter(|a| !a.tracks.iter().any(|t| avoid.contains(&t.url)))
+                .choose(&mut rng)
+                .or_else(|| albums.choose(&mut rng));
+            Ok(album
+                .map(|a| a.tracks.iter().map(|t| t.url.clone()).collect())
+                .unwrap_or_default())
+        }
+    }
+}
+
+/// Top the queue up if needed, returning the number of songs added.
+async fn fill(handle: &AppHandle, client: &Client) -> Result<usize, String> {
+    let dj = handle.state::<AutoDj>();
+    let config = {
+        let inner = dj.inner.lock().unwrap();
+        if !inner.running {
+            return Ok(0);
+        }
+        inner.config.clone()
+    };
+    let status = client
+        .command(commands::Status)
+        .await
+        .map_err(|e| e.to_string())?;
+    let upcoming = match status.current_song {
+        Some((pos, _)) => status.playlist_length.saturating_sub(pos.0 + 1),
+        None => status.playlist_length,
+    };
+    if let Some(song) = client
+        .command(commands::CurrentSong)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        dj.inner.lock().unwrap().remember(&song.song.url);
+    }
+    if upcoming >= config.min_upcoming {
+        return Ok(0);
+    }
+    let queued: HashSet<String> = client
+        .command(commands::Queue)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|s| s.song.url)
+        .collect();
+    let mut avoid = queued;
+    avoid.extend(dj.inner.lock().unwrap().recent.iter().cloned());
+    let uris = pick(
+        handle,
+        client,
+        &config.source,
+        config.min_upcoming - upcoming,
+        &avoid,
+    )
+    .await?;
+    if uris.is_empty() {
+        return Ok(0);
+    }
+    let cmds: Vec<_> = uris.iter().map(|uri| commands::Add::uri(uri)).collect();
+    client.command_list(cmds).await.map_err(|e| e.to_string())?;
+    let mut inner = dj.inner.lock().unwrap();
+    for uri in &uris {
+        inner.remember(uri);
+    }
+    inner.added += uris.len();
+    Ok(uris.len())
+}
+
+/// Run the Auto-DJ, emitting `autodj` with its status whenever it adds songs or fails to.
+pub async fn autodj_task(handle: AppHandle) {
+    let dj = handle.state::<AutoDj>();
+    loop {
+        dj.wake.notified().await;
+        let client = handle.state::<Client>();
+        let result = fill(&handle, &client).await;
+        let status = {
+            let mut inner = dj.inner.lock().unwrap();
+            match result {
+                Ok(0) if inner.error.is_none() => continue,
+                Ok(_) => inner.error = None,
+                Err(e) => {
+                    log::error!("Auto-DJ failed to fill the queue: {}", e);
+                    inner.error = Some(e);
+                }
+            }
+            inner.status()
+        };
+        if let Err(e) = handle.emit_all("autodj", status) {
+            log::error!("Failed to emit signal 'autodj': {}", e);
+        }
+    }
+}
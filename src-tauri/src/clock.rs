@@ -0,0 +1,161 @@
+//! The playback clock shared by every window and backend feature.
+//!
+//! The clock is anchored to the `elapsed` of the last `status` fetch and advanced with a
+//! monotonic clock while playing. It is resynced on every `player` event and every few seconds
+//! while playing, so seeks and pauses from other clients are picked up and drift stays small.
+//! While playing, `elapsed` is emitted at the interval set in the settings.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use mpd_client::{
+    commands,
+    responses::{PlayState, Status},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    lyrics::LyricsTracker,
+    reflection::{response::TVal, Reflect},
+};
+
+/// Default time between `elapsed` events, in milliseconds.
+pub const DEFAULT_ELAPSED_INTERVAL: u64 = 500;
+/// Shortest allowed time between `elapsed` events.
+const MIN_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the clock is resynced with MPD while playing.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Position in the current song, emitted as `elapsed`.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct PlaybackPosition {
+    pub elapsed: TVal,
+    pub duration: Option<TVal>,
+    pub playing: bool,
+}
+
+struct Anchor {
+    elapsed: Duration,
+    duration: Option<Duration>,
+    /// Set while playing, to when `elapsed` was read.
+    since: Option<Instant>,
+    synced: Instant,
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            duration: None,
+            since: None,
+            synced: Instant::now(),
+        }
+    }
+}
+
+pub struct PlaybackClock {
+    anchor: Mutex<Anchor>,
+    interval: Mutex<Duration>,
+    wake: Notify,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            anchor: Mutex::default(),
+            interval: Mutex::new(Duration::from_millis(DEFAULT_ELAPSED_INTERVAL)),
+            wake: Notify::new(),
+        }
+    }
+}
+
+impl PlaybackClock {
+    /// Elapsed time of the current song, and whether it is playing.
+    pub fn elapsed(&self) -> (Duration, bool) {
+        let anchor = self.anchor.lock().unwrap();
+        let elapsed = match anchor.since {
+            Some(since) => anchor.elapsed + since.elapsed(),
+            None => anchor.elapsed,
+        };
+        let elapsed = anchor.duration.map_or(elapsed, |d| elapsed.min(d));
+        (elapsed, anchor.since.is_some())
+    }
+
+    pub fn position(&self) -> PlaybackPosition {
+        let (elapsed, playing) = self.elapsed();
+        PlaybackPosition {
+            elapsed: elapsed.reflect(),
+            duration: self.anchor.lock().unwrap().duration.map(Reflect::reflect),
+            playing,
+        }
+    }
+
+    /// Anchor the clock to a freshly fetched `status`.
+    fn anchor(&self, status: &Status) {
+        let now = Instant::now();
+        *self.anchor.lock().unwrap() = Anchor {
+            elapsed: status.elapsed.unwrap_or_default(),
+            duration: status.duration,
+            since: (status.state == PlayState::Playing).then_some(now),
+            synced: now,
+        };
+    }
+
+    /// Set the time between `elapsed` events in milliseconds.
+    pub fn set_interval(&self, millis: u64) {
+        *self.interval.lock().unwrap() = Duration::from_millis(millis).max(MIN_INTERVAL);
+        self.wake.notify_one();
+    }
+}
+
+fn anchor(handle: &AppHandle, status: &Status) {
+    handle.state::<PlaybackClock>().anchor(status);
+    handle.state::<LyricsTracker>().poke();
+}
+
+async fn resync(handle: &AppHandle) -> bool {
+    match handle.state::<Client>().command(commands::Status).await {
+        Ok(status) => {
+            anchor(handle, &status);
+            true
+        }
+        Err(e) => {
+            log::error!("Failed to fetch the status for the playback clock: {}", e);
+            false
+        }
+    }
+}
+
+/// Resync the clock with the status fetched on a `player` event, emitting `elapsed` right away.
+pub fn sync_clock(handle: &AppHandle, status: &Status) {
+    anchor(handle, status);
+    handle.state::<PlaybackClock>().wake.notify_one();
+}
+
+/// Emit `elapsed` while playing and once after every resync, resyncing periodically.
+pub async fn clock_task(handle: AppHandle) {
+    let clock = handle.state::<PlaybackClock>();
+    resync(&handle).await;
+    loop {
+        let playing = clock.anchor.lock().unwrap().since.is_some();
+        if let Err(e) = handle.emit_all("elapsed", clock.position()) {
+            log::error!("Failed to emit signal 'elapsed': {}", e);
+        }
+        if !playing {
+            clock.wake.notified().await;
+            continue;
+        }
+        let interval = *clock.interval.lock().unwrap();
+        let woken = tokio::time::timeout(interval, clock.wake.notified()).await;
+        let stale = clock.anchor.lock().unwrap().synced.elapsed() >= RESYNC_INTERVAL;
+        if woken.is_err() && stale {
+            resync(&handle).await;
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Secrets kept out of the settings so they never reach the webview: MPD passwords and the
+//! credentials of the scrobbling services.
+//!
+//! They live in `credentials.json` in the app config directory, readable only by the user where
+//! the platform allows it. The frontend sets them through dedicated commands and only sees
+//! whether they are set, see [`crate::settings::ServerProfile::password_set`] and
+//! [`crate::scrobble::ScrobbleStatus`].
+
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+pub const CREDENTIALS_FILE: &str = "credentials.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LastFmSession {
+    pub username: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Credentials {
+    /// Shared secret of the Last.fm API account, used to sign calls.
+    pub lastfm_secret: String,
+    /// Set by logging in.
+    pub lastfm_session: Option<LastFmSession>,
+    pub listenbrainz_token: String,
+    /// MPD passwords by server address, so renaming a profile keeps its password.
+    pub mpd_passwords: BTreeMap<String, String>,
+}
+
+pub struct CredentialStore {
+    /// `None` if there's no config directory, in which case credentials aren't persisted.
+    path: Option<PathBuf>,
+    current: Mutex<Credentials>,
+}
+
+impl CredentialStore {
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let path = dir.map(|dir| dir.join(CREDENTIALS_FILE));
+        let credentials = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|raw| {
+                serde_json::from_slice(&raw)
+                    .map_err(|e| log::error!("Failed to load credentials: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            current: Mutex::new(credentials),
+        }
+    }
+
+    pub fn get(&self) -> Credentials {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Change the credentials with `f` and save them.
+    pub fn update(&self, f: impl FnOnce(&mut Credentials)) -> Result<(), String> {
+        let mut current = self.current.lock().unwrap();
+        let mut credentials = current.clone();
+        f(&mut credentials);
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            let raw = serde_json::to_vec_pretty(&credentials).map_err(|e| e.to_string())?;
+            fs::write(path, raw).map_err(|e| e.to_string())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        *current = credentials;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_reloads_credentials() {
+        let dir = std::env::temp_dir().join(format!("rmpd-credentials-{}", std::process::id()));
+        let store = CredentialStore::open(Some(dir.clone()));
+        assert_eq!(store.get(), Credentials::default());
+        store
+            .update(|c| {
+                c.lastfm_secret = "secret".to_string();
+                c.mpd_passwords
+                    .insert("localhost:6600".to_string(), "hunter2".to_string());
+                c.lastfm_session = Some(LastFmSession {
+                    username: "user".to_string(),
+                    key: "key".to_string(),
+                });
+            })
+            .unwrap();
+        let reloaded = CredentialStore::open(Some(dir.clone())).get();
+        assert_eq!(reloaded, store.get());
+        assert_eq!(reloaded.lastfm_secret, "secret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(CREDENTIALS_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
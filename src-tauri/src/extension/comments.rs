@@ -0,0 +1,24 @@
+use mpd_client::{
+    commands::Command,
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::TypedResponseError,
+};
+
+/// `readcomments` command: the raw tags of a song file, including those MPD doesn't index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadComments<'a>(pub &'a str);
+
+impl<'a> Command for ReadComments<'a> {
+    type Response = Vec<(String, String)>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("readcomments").argument(self.0)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(frame
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect())
+    }
+}
@@ -0,0 +1,31 @@
+use mpd_client::{
+    commands::Command,
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::TypedResponseError,
+};
+
+/// Server configuration, only readable over a local socket connection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    pub music_directory: Option<String>,
+    pub playlist_directory: Option<String>,
+}
+
+/// `config` command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GetConfig;
+
+impl Command for GetConfig {
+    type Response = Config;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("config")
+    }
+
+    fn response(self, mut frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(Config {
+            music_directory: frame.get("music_directory"),
+            playlist_directory: frame.get("playlist_directory"),
+        })
+    }
+}
@@ -0,0 +1,18 @@
+//! Typed commands that are not provided by `mpd_client`.
+//!
+//! These implement [`mpd_client::commands::Command`] so they can be sent with
+//! `Client::command` (and used in command lists) like the built-in ones.
+
+pub mod comments;
+pub mod config;
+pub mod mount;
+pub mod queue;
+pub mod search;
+pub mod sticker;
+
+pub use comments::*;
+pub use config::*;
+pub use mount::*;
+pub use queue::*;
+pub use search::*;
+pub use sticker::*;
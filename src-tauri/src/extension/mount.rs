@@ -0,0 +1,123 @@
+use mpd_client::{
+    commands::Command,
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::TypedResponseError,
+};
+
+/// A storage mounted on a path of the virtual database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+    pub mount: String,
+    pub storage: Option<String>,
+}
+
+/// A storage found by a neighbor plugin.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Neighbor {
+    pub neighbor: String,
+    pub name: Option<String>,
+}
+
+/// `mount` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MountStorage<'a> {
+    path: &'a str,
+    uri: &'a str,
+}
+
+impl<'a> MountStorage<'a> {
+    /// Mount the storage at `uri` on `path` of the virtual database.
+    pub fn new(path: &'a str, uri: &'a str) -> Self {
+        Self { path, uri }
+    }
+}
+
+impl<'a> Command for MountStorage<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("mount")
+            .argument(self.path)
+            .argument(self.uri)
+    }
+
+    fn response(self, _: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `unmount` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnmountStorage<'a>(pub &'a str);
+
+impl<'a> Command for UnmountStorage<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("unmount").argument(self.0)
+    }
+
+    fn response(self, _: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `listmounts` command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListMounts;
+
+impl Command for ListMounts {
+    type Response = Vec<Mount>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("listmounts")
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        let mut out: Vec<Mount> = Vec::new();
+        for (key, value) in frame {
+            match &*key {
+                "mount" => out.push(Mount {
+                    mount: value,
+                    storage: None,
+                }),
+                "storage" => match out.last_mut() {
+                    Some(m) => m.storage = Some(value),
+                    None => return Err(TypedResponseError::unexpected_field("mount", "storage")),
+                },
+                _ => {}
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// `listneighbors` command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListNeighbors;
+
+impl Command for ListNeighbors {
+    type Response = Vec<Neighbor>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("listneighbors")
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        let mut out: Vec<Neighbor> = Vec::new();
+        for (key, value) in frame {
+            match &*key {
+                "neighbor" => out.push(Neighbor {
+                    neighbor: value,
+                    name: None,
+                }),
+                "name" => match out.last_mut() {
+                    Some(n) => n.name = Some(value),
+                    None => return Err(TypedResponseError::unexpected_field("neighbor", "name")),
+                },
+                _ => {}
+            }
+        }
+        Ok(out)
+    }
+}
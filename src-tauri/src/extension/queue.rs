@@ -0,0 +1,75 @@
+use std::ops::Range;
+
+use mpd_client::{
+    commands::{self, Command, SongId, SongPosition},
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::{SongInQueue, TypedResponseError},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PriorityTarget {
+    Id(SongId),
+    Range(Range<SongPosition>),
+}
+
+/// `prio` and `prioid` commands.
+///
+/// Only has an effect in random mode, songs with a higher priority are played first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetPriority {
+    priority: u8,
+    target: PriorityTarget,
+}
+
+impl SetPriority {
+    /// Set the priority of the song with the given ID.
+    pub fn id(priority: u8, id: SongId) -> Self {
+        Self {
+            priority,
+            target: PriorityTarget::Id(id),
+        }
+    }
+
+    /// Set the priority of the songs in the given range of positions.
+    pub fn range(priority: u8, range: Range<SongPosition>) -> Self {
+        Self {
+            priority,
+            target: PriorityTarget::Range(range),
+        }
+    }
+}
+
+impl Command for SetPriority {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        match &self.target {
+            PriorityTarget::Id(id) => RawCommand::new("prioid")
+                .argument(self.priority)
+                .argument(id),
+            PriorityTarget::Range(range) => RawCommand::new("prio")
+                .argument(self.priority)
+                .argument(format!("{}:{}", range.start.0, range.end.0)),
+        }
+    }
+
+    fn response(self, _: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `playlistinfo` command for a range of positions, so a long queue needn't be fetched whole.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueRange(pub Range<usize>);
+
+impl Command for QueueRange {
+    type Response = Vec<SongInQueue>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("playlistinfo").argument(format!("{}:{}", self.0.start, self.0.end))
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        commands::Queue.response(frame)
+    }
+}
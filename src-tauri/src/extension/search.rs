@@ -0,0 +1,102 @@
+use mpd_client::{
+    commands::{self, Command},
+    filter::Filter,
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::{Song, SongInQueue, TypedResponseError},
+};
+
+/// `playlistfind` and `playlistsearch` commands, filtering the queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueFind {
+    filter: Filter,
+    case_sensitive: bool,
+}
+
+impl QueueFind {
+    /// Exact, case-sensitive matching (`playlistfind`).
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            case_sensitive: true,
+        }
+    }
+
+    /// Case-insensitive substring matching (`playlistsearch`).
+    pub fn search(filter: Filter) -> Self {
+        Self {
+            filter,
+            case_sensitive: false,
+        }
+    }
+}
+
+impl Command for QueueFind {
+    type Response = Vec<SongInQueue>;
+
+    fn command(&self) -> RawCommand {
+        let name = if self.case_sensitive {
+            "playlistfind"
+        } else {
+            "playlistsearch"
+        };
+        RawCommand::new(name).argument(&self.filter)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        // Same format as `playlistinfo`.
+        commands::Queue.response(frame)
+    }
+}
+
+/// A song of a stored playlist, `position` being `None` if the server doesn't report it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistEntry {
+    pub position: Option<usize>,
+    pub song: Song,
+}
+
+/// `searchplaylist` command, case-insensitive filtering of a stored playlist (MPD 0.24+).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchPlaylist<'a> {
+    name: &'a str,
+    filter: Filter,
+}
+
+impl<'a> SearchPlaylist<'a> {
+    pub fn new(name: &'a str, filter: Filter) -> Self {
+        Self { name, filter }
+    }
+}
+
+impl Command for SearchPlaylist<'_> {
+    type Response = Vec<PlaylistEntry>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("searchplaylist")
+            .argument(self.name)
+            .argument(&self.filter)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        let mut positions = Vec::new();
+        for (key, value) in frame.fields() {
+            match key {
+                "file" => positions.push(None),
+                "Pos" => {
+                    if let Some(last) = positions.last_mut() {
+                        *last = value.parse().ok();
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Same format as `listplaylistinfo`.
+        let songs = commands::GetPlaylist(self.name).response(frame)?;
+        Ok(positions
+            .into_iter()
+            .chain(std::iter::repeat(None))
+            .zip(songs)
+            .map(|(position, song)| PlaylistEntry { position, song })
+            .collect())
+    }
+}
@@ -0,0 +1,310 @@
+use std::ops::Range;
+
+use mpd_client::{
+    commands::Command,
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::TypedResponseError,
+};
+
+/// What a sticker is attached to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StickerType {
+    /// A song, by URI.
+    Song,
+    /// A stored playlist, by name.
+    Playlist,
+    /// A tag value, e.g. an album by its name (MPD 0.24+).
+    Tag(String),
+}
+
+impl StickerType {
+    fn as_str(&self) -> &str {
+        match self {
+            StickerType::Song => "song",
+            StickerType::Playlist => "playlist",
+            StickerType::Tag(tag) => tag,
+        }
+    }
+}
+
+/// Comparison of `sticker find`; the `Int` ones compare as integers (MPD 0.24+).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StickerOperator {
+    Eq,
+    Gt,
+    Lt,
+    IntEq,
+    IntGt,
+    IntLt,
+    Contains,
+    StartsWith,
+}
+
+impl StickerOperator {
+    fn as_str(self) -> &'static str {
+        match self {
+            StickerOperator::Eq => "=",
+            StickerOperator::Gt => ">",
+            StickerOperator::Lt => "<",
+            StickerOperator::IntEq => "eq",
+            StickerOperator::IntGt => "gt",
+            StickerOperator::IntLt => "lt",
+            StickerOperator::Contains => "contains",
+            StickerOperator::StartsWith => "starts_with",
+        }
+    }
+}
+
+/// Order of `sticker find` results (MPD 0.24+).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StickerSort {
+    Uri,
+    Value,
+    /// The value as an integer.
+    ValueInt,
+}
+
+impl StickerSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            StickerSort::Uri => "uri",
+            StickerSort::Value => "value",
+            StickerSort::ValueInt => "value_int",
+        }
+    }
+}
+
+fn split_sticker(value: String) -> Result<(String, String), TypedResponseError> {
+    match value.split_once('=') {
+        Some((name, value)) => Ok((name.to_string(), value.to_string())),
+        None => Err(TypedResponseError::invalid_value("sticker", value)),
+    }
+}
+
+/// `sticker get` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GetSticker<'a> {
+    kind: &'a StickerType,
+    uri: &'a str,
+    name: &'a str,
+}
+
+impl<'a> GetSticker<'a> {
+    pub fn new(kind: &'a StickerType, uri: &'a str, name: &'a str) -> Self {
+        Self { kind, uri, name }
+    }
+}
+
+impl<'a> Command for GetSticker<'a> {
+    type Response = String;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("sticker")
+            .argument("get")
+            .argument(self.kind.as_str())
+            .argument(self.uri)
+            .argument(self.name)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        for (key, value) in frame {
+            if &*key == "sticker" {
+                return split_sticker(value).map(|(_, value)| value);
+            }
+        }
+        Err(TypedResponseError::missing("sticker"))
+    }
+}
+
+/// `sticker set` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetSticker<'a> {
+    kind: &'a StickerType,
+    uri: &'a str,
+    name: &'a str,
+    value: &'a str,
+}
+
+impl<'a> SetSticker<'a> {
+    pub fn new(kind: &'a StickerType, uri: &'a str, name: &'a str, value: &'a str) -> Self {
+        Self {
+            kind,
+            uri,
+            name,
+            value,
+        }
+    }
+}
+
+impl<'a> Command for SetSticker<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("sticker")
+            .argument("set")
+            .argument(self.kind.as_str())
+            .argument(self.uri)
+            .argument(self.name)
+            .argument(self.value)
+    }
+
+    fn response(self, _: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `sticker delete` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeleteSticker<'a> {
+    kind: &'a StickerType,
+    uri: &'a str,
+    name: Option<&'a str>,
+}
+
+impl<'a> DeleteSticker<'a> {
+    /// Delete the sticker `name`, or all stickers of `uri` without a name.
+    pub fn new(kind: &'a StickerType, uri: &'a str, name: Option<&'a str>) -> Self {
+        Self { kind, uri, name }
+    }
+}
+
+impl<'a> Command for DeleteSticker<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        let cmd = RawCommand::new("sticker")
+            .argument("delete")
+            .argument(self.kind.as_str())
+            .argument(self.uri);
+        match self.name {
+            Some(name) => cmd.argument(name),
+            None => cmd,
+        }
+    }
+
+    fn response(self, _: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `sticker list` command, responding with `(name, value)` pairs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListStickers<'a> {
+    kind: &'a StickerType,
+    uri: &'a str,
+}
+
+impl<'a> ListStickers<'a> {
+    pub fn new(kind: &'a StickerType, uri: &'a str) -> Self {
+        Self { kind, uri }
+    }
+}
+
+impl<'a> Command for ListStickers<'a> {
+    type Response = Vec<(String, String)>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("sticker")
+            .argument("list")
+            .argument(self.kind.as_str())
+            .argument(self.uri)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        frame
+            .into_iter()
+            .filter(|(key, _)| &**key == "sticker")
+            .map(|(_, value)| split_sticker(value))
+            .collect()
+    }
+}
+
+/// `sticker find` command, responding with `(uri, value)` pairs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FindStickers<'a> {
+    kind: &'a StickerType,
+    uri: &'a str,
+    name: &'a str,
+    filter: Option<(StickerOperator, &'a str)>,
+    sort: Option<(StickerSort, bool)>,
+    window: Option<Range<usize>>,
+}
+
+impl<'a> FindStickers<'a> {
+    /// Find the stickers `name` of everything under `uri`.
+    ///
+    /// For songs `uri` is a directory, `""` being the whole database; for other types it is
+    /// usually `""`.
+    pub fn new(kind: &'a StickerType, uri: &'a str, name: &'a str) -> Self {
+        Self {
+            kind,
+            uri,
+            name,
+            filter: None,
+            sort: None,
+            window: None,
+        }
+    }
+
+    /// Only find stickers whose value compares to `value` with `operator`.
+    pub fn filter(mut self, operator: StickerOperator, value: &'a str) -> Self {
+        self.filter = Some((operator, value));
+        self
+    }
+
+    pub fn sort(mut self, sort: StickerSort, descending: bool) -> Self {
+        self.sort = Some((sort, descending));
+        self
+    }
+
+    pub fn window(mut self, window: Range<usize>) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+impl<'a> Command for FindStickers<'a> {
+    type Response = Vec<(String, String)>;
+
+    fn command(&self) -> RawCommand {
+        let mut cmd = RawCommand::new("sticker")
+            .argument("find")
+            .argument(self.kind.as_str())
+            .argument(self.uri)
+            .argument(self.name);
+        if let Some((operator, value)) = self.filter {
+            cmd = cmd.argument(operator.as_str()).argument(value);
+        }
+        if let Some((sort, descending)) = self.sort {
+            let sort = if descending {
+                format!("-{}", sort.as_str())
+            } else {
+                sort.as_str().to_string()
+            };
+            cmd = cmd.argument("sort").argument(sort);
+        }
+        if let Some(window) = &self.window {
+            cmd = cmd
+                .argument("window")
+                .argument(format!("{}:{}", window.start, window.end));
+        }
+        cmd
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        let mut out = Vec::new();
+        let mut uri = None;
+        for (key, value) in frame {
+            if &*key == "sticker" {
+                let Some(uri) = uri.take() else {
+                    return Err(TypedResponseError::unexpected_field("file", "sticker"));
+                };
+                out.push((uri, split_sticker(value)?.1));
+            } else {
+                // `file`, `playlist` or the tag name, depending on the sticker type.
+                uri = Some(value);
+            }
+        }
+        Ok(out)
+    }
+}
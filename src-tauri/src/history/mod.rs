@@ -0,0 +1,541 @@
+//! Local listening history.
+//!
+//! Every song that stops being the current one is recorded with a snapshot of its tags, when it
+//! started, how long it was actually listened to and whether that counted as a play. Plays are
+//! stored in the `plays` table of `history.sqlite3` in the app data directory, indexed by start
+//! time, artist and album so queries and [`reports`] only read the rows they need.
+
+pub mod reports;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use rusqlite::{params, params_from_iter, types::Value, Connection, Row};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{
+    plays::Play,
+    reflection::{response::TVal, tag::Tag, Reflect},
+};
+
+pub const HISTORY_FILE: &str = "history.sqlite3";
+
+/// Tags are kept as a JSON object; artist, album and title get columns of their own for lookups.
+/// `NOCASE` makes comparisons ignore ASCII case, in the indexes too.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS plays (
+        id INTEGER PRIMARY KEY,
+        uri TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        title TEXT,
+        artist TEXT COLLATE NOCASE,
+        album_artist TEXT COLLATE NOCASE,
+        album TEXT COLLATE NOCASE,
+        started INTEGER NOT NULL,
+        duration REAL,
+        listened REAL NOT NULL,
+        completed INTEGER NOT NULL,
+        server TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS plays_started ON plays (started);
+    CREATE INDEX IF NOT EXISTS plays_artist ON plays (artist);
+    CREATE INDEX IF NOT EXISTS plays_album_artist ON plays (album_artist);
+    CREATE INDEX IF NOT EXISTS plays_album ON plays (album);
+";
+
+const COLUMNS: &str = "id, uri, tags, started, duration, listened, completed, server";
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub uri: String,
+    pub tags: HashMap<String, Vec<String>>,
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    pub duration: Option<TVal>,
+    pub listened: TVal,
+    /// Listened to long enough to count as played rather than skipped.
+    pub completed: bool,
+    /// Name of the server profile it was played on.
+    pub server: String,
+}
+
+impl HistoryEntry {
+    pub fn values(&self, tag: Tag) -> impl Iterator<Item = &String> {
+        self.tags.get(&tag.to_string()).into_iter().flatten()
+    }
+
+    fn first(&self, tag: Tag) -> Option<&str> {
+        self.values(tag).next().map(String::as_str)
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.first(Tag::Title)
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.first(Tag::Artist)
+            .or_else(|| self.first(Tag::AlbumArtist))
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.first(Tag::Album)
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let tags: String = row.get(2)?;
+        let secs = |s: f64| Duration::from_secs_f64(s.max(0.0)).reflect();
+        Ok(Self {
+            id: row.get::<_, i64>(0)? as u64,
+            uri: row.get(1)?,
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+            started: row.get::<_, i64>(3)? as u64,
+            duration: row.get::<_, Option<f64>>(4)?.map(secs),
+            listened: secs(row.get(5)?),
+            completed: row.get(6)?,
+            server: row.get(7)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, Default)]
+pub struct HistoryQuery {
+    /// Earliest start time, in seconds since the Unix epoch.
+    pub from: Option<u64>,
+    /// Start time before which entries must have started.
+    pub to: Option<u64>,
+    /// Matches the first artist or album artist, ignoring ASCII case.
+    pub artist: Option<String>,
+    /// Matches the first album, ignoring ASCII case.
+    pub album: Option<String>,
+    /// Leave out skipped songs.
+    pub completed_only: bool,
+}
+
+impl HistoryQuery {
+    /// The `WHERE` clause selecting the matching plays, and its parameters.
+    fn filter(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec!["1"];
+        let mut values = Vec::new();
+        if let Some(from) = self.from {
+            conditions.push("started >= ?");
+            values.push(Value::Integer(from as i64));
+        }
+        if let Some(to) = self.to {
+            conditions.push("started < ?");
+            values.push(Value::Integer(to as i64));
+        }
+        if let Some(artist) = &self.artist {
+            conditions.push("(artist = ? OR album_artist = ?)");
+            values.push(Value::Text(artist.clone()));
+            values.push(Value::Text(artist.clone()));
+        }
+        if let Some(album) = &self.album {
+            conditions.push("album = ?");
+            values.push(Value::Text(album.clone()));
+        }
+        if self.completed_only {
+            conditions.push("completed");
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct HistoryPage {
+    /// Newest first.
+    pub entries: Vec<HistoryEntry>,
+    /// Number of entries matching the query.
+    pub total: usize,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug)]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+impl HistoryFormat {
+    /// Guess the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "csv" => Some(HistoryFormat::Csv),
+            "json" => Some(HistoryFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+pub struct History {
+    conn: Mutex<Connection>,
+}
+
+fn open_database(dir: Option<PathBuf>) -> rusqlite::Result<Connection> {
+    let conn = match dir {
+        Some(dir) => {
+            if let Err(e) = fs::create_dir_all(&dir) {
+                log::error!("Failed to create {}: {}", dir.display(), e);
+            }
+            Connection::open(dir.join(HISTORY_FILE))?
+        }
+        None => Connection::open_in_memory()?,
+    };
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+impl History {
+    /// Open the history in `dir`, keeping it in memory if there's no data directory or the
+    /// database can't be opened.
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let conn = open_database(dir).unwrap_or_else(|e| {
+            log::error!(
+                "Failed to open the history database, not saving history: {}",
+                e
+            );
+            Connection::open_in_memory()
+                .and_then(|conn| conn.execute_batch(SCHEMA).map(|_| conn))
+                .expect("in-memory database")
+        });
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    /// Record a finished song played on the server profile `server`.
+    pub fn record(&self, play: &Play, server: &str) -> HistoryEntry {
+        let mut entry = HistoryEntry {
+            id: 0,
+            uri: play.song.url.clone(),
+            tags: play.song.clone().reflect().tags,
+            started: play
+                .started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration: play.song.duration.reflect(),
+            listened: play.listened.reflect(),
+            completed: play.completed,
+            server: server.to_string(),
+        };
+        match self.insert(&entry) {
+            Ok(id) => entry.id = id,
+            Err(e) => log::error!("Failed to save history entry: {}", e),
+        }
+        entry
+    }
+
+    fn insert(&self, entry: &HistoryEntry) -> rusqlite::Result<u64> {
+        let secs = |t: &TVal| Duration::from(t.clone()).as_secs_f64();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO plays (uri, tags, title, artist, album_artist, album, started, duration,
+                listened, completed, server)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                entry.uri,
+                serde_json::to_string(&entry.tags).unwrap_or_default(),
+                entry.title(),
+                entry.first(Tag::Artist),
+                entry.first(Tag::AlbumArtist),
+                entry.album(),
+                entry.started as i64,
+                entry.duration.as_ref().map(secs),
+                secs(&entry.listened),
+                entry.completed,
+                entry.server,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid() as u64)
+    }
+
+    /// Entries matching `query`, newest first, skipping `offset` and returning at most `limit`.
+    pub fn query(
+        &self,
+        query: &HistoryQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<HistoryPage, String> {
+        let (filter, values) = query.filter();
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM plays WHERE {}", filter),
+                params_from_iter(&values),
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let sql = format!(
+            "SELECT {} FROM plays WHERE {} ORDER BY started DESC, id DESC LIMIT {} OFFSET {}",
+            COLUMNS, filter, limit, offset
+        );
+        let entries = conn
+            .prepare(&sql)
+            .and_then(|mut stmt| {
+                stmt.query_map(params_from_iter(&values), HistoryEntry::from_row)?
+                    .collect()
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(HistoryPage {
+            entries,
+            total: total as usize,
+        })
+    }
+
+    /// Every entry matching `query`, oldest first.
+    pub fn entries(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+        let (filter, values) = query.filter();
+        let sql = format!(
+            "SELECT {} FROM plays WHERE {} ORDER BY started, id",
+            COLUMNS, filter
+        );
+        self.conn
+            .lock()
+            .unwrap()
+            .prepare(&sql)
+            .and_then(|mut stmt| {
+                stmt.query_map(params_from_iter(&values), HistoryEntry::from_row)?
+                    .collect()
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    /// Local days with at least one completed play.
+    pub fn play_days(&self) -> Result<BTreeSet<NaiveDate>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT DISTINCT date(started, 'unixepoch', 'localtime') FROM plays
+                 WHERE completed",
+            )
+            .map_err(|e| e.to_string())?;
+        let days = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            .map_err(|e| e.to_string())?;
+        Ok(days
+            .iter()
+            .filter_map(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+            .collect())
+    }
+
+    /// Songs with at least `plays` completed plays before `before`, the last one before `cutoff`,
+    /// most played first, with the tags of their last play.
+    pub fn stale_favourites(
+        &self,
+        plays: usize,
+        cutoff: u64,
+        before: u64,
+        limit: usize,
+    ) -> Result<Vec<(HistoryEntry, usize)>, String> {
+        let conn = self.conn.lock().unwrap();
+        // With a single max(), SQLite takes the other columns from the row holding the maximum.
+        let sql = format!(
+            "SELECT {}, COUNT(*) AS plays, MAX(started) AS last FROM plays
+             WHERE completed AND started < ?1 GROUP BY uri
+             HAVING plays >= ?2 AND last < ?3 ORDER BY plays DESC, last LIMIT ?4",
+            COLUMNS
+        );
+        conn.prepare(&sql)
+            .and_then(|mut stmt| {
+                stmt.query_map(
+                    params![before as i64, plays as i64, cutoff as i64, limit as i64],
+                    |row| Ok((HistoryEntry::from_row(row)?, row.get::<_, i64>(8)? as usize)),
+                )?
+                .collect()
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    /// Write the entries matching `query` to `path`, oldest first, returning how many were written.
+    pub fn export(
+        &self,
+        query: &HistoryQuery,
+        path: &Path,
+        format: HistoryFormat,
+    ) -> Result<usize, String> {
+        let entries = self.entries(query)?;
+        let raw = match format {
+            HistoryFormat::Json => {
+                serde_json::to_vec_pretty(&entries).map_err(|e| e.to_string())?
+            }
+            HistoryFormat::Csv => to_csv(&entries).into_bytes(),
+        };
+        fs::write(path, raw).map_err(|e| e.to_string())?;
+        Ok(entries.len())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut out =
+        String::from("started,uri,artist,album,title,duration,listened,completed,server\n");
+    for entry in entries {
+        let started = Local
+            .timestamp_opt(entry.started as i64, 0)
+            .single()
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        let secs = |t: &TVal| format!("{:.3}", t.secs as f64 + t.nanos as f64 / 1e9);
+        let fields = [
+            started,
+            entry.uri.clone(),
+            entry.artist().unwrap_or_default().to_string(),
+            entry.album().unwrap_or_default().to_string(),
+            entry.title().unwrap_or_default().to_string(),
+            entry.duration.as_ref().map(secs).unwrap_or_default(),
+            secs(&entry.listened),
+            entry.completed.to_string(),
+            entry.server.clone(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(uri: &str, artist: &str, album: &str, started: u64, completed: bool) -> HistoryEntry {
+        HistoryEntry {
+            id: 0,
+            uri: uri.to_string(),
+            tags: HashMap::from([
+                ("Title".to_string(), vec![uri.to_uppercase()]),
+                ("Artist".to_string(), vec![artist.to_string()]),
+                ("Album".to_string(), vec![album.to_string()]),
+            ]),
+            started,
+            duration: Some(Duration::from_millis(200_500).reflect()),
+            listened: Duration::from_secs(150).reflect(),
+            completed,
+            server: "home".to_string(),
+        }
+    }
+
+    fn history(entries: &[HistoryEntry]) -> History {
+        let history = History::open(None);
+        for entry in entries {
+            history.insert(entry).unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let history = history(&[entry("a.flac", "Artist", "Album", 100, true)]);
+        let page = history.query(&HistoryQuery::default(), 0, 10).unwrap();
+        assert_eq!(page.total, 1);
+        let read = &page.entries[0];
+        assert_eq!(read.id, 1);
+        assert_eq!(read.title(), Some("A.FLAC"));
+        assert_eq!(
+            read.duration.as_ref().map(|d| (d.secs, d.nanos)),
+            Some((200, 500_000_000))
+        );
+        assert_eq!(read.listened.secs, 150);
+        assert!(read.completed);
+    }
+
+    #[test]
+    fn pages_newest_first() {
+        let entries: Vec<HistoryEntry> = (0..5)
+            .map(|i| entry(&format!("{}.flac", i), "A", "B", i * 10, true))
+            .collect();
+        let history = history(&entries);
+        let page = history.query(&HistoryQuery::default(), 1, 2).unwrap();
+        assert_eq!(page.total, 5);
+        let uris: Vec<&str> = page.entries.iter().map(|e| e.uri.as_str()).collect();
+        assert_eq!(uris, ["3.flac", "2.flac"]);
+    }
+
+    #[test]
+    fn filters_by_range_artist_album_and_completion() {
+        let history = history(&[
+            entry("a.flac", "Björk", "Post", 100, true),
+            entry("b.flac", "blur", "Parklife", 200, true),
+            entry("c.flac", "Blur", "Parklife", 300, false),
+        ]);
+        let count = |query: HistoryQuery| history.entries(&query).unwrap().len();
+        let range = HistoryQuery {
+            from: Some(100),
+            to: Some(300),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(count(range), 2);
+        let artist = HistoryQuery {
+            artist: Some("BLUR".to_string()),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(count(artist.clone()), 2);
+        let completed = HistoryQuery {
+            completed_only: true,
+            ..artist
+        };
+        assert_eq!(count(completed), 1);
+        let album = HistoryQuery {
+            album: Some("post".to_string()),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(count(album), 1);
+    }
+
+    #[test]
+    fn finds_stale_favourites_with_their_last_play() {
+        let mut entries = vec![];
+        for started in [10, 20, 30] {
+            entries.push(entry("old.flac", "A", "B", started, true));
+        }
+        for started in [10, 20, 900] {
+            entries.push(entry("recent.flac", "A", "B", started, true));
+        }
+        entries.push(entry("once.flac", "A", "B", 10, true));
+        let history = history(&entries);
+        let stale = history.stale_favourites(3, 500, 1000, 10).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].0.uri, "old.flac");
+        assert_eq!(stale[0].0.started, 30);
+        assert_eq!(stale[0].1, 3);
+    }
+
+    #[test]
+    fn play_days_are_local_dates_of_completed_plays() {
+        let noon = |day: u32| {
+            Local
+                .with_ymd_and_hms(2024, 3, day, 12, 0, 0)
+                .unwrap()
+                .timestamp() as u64
+        };
+        let history = history(&[
+            entry("a.flac", "A", "B", noon(1), true),
+            entry("b.flac", "A", "B", noon(1) + 60, true),
+            entry("c.flac", "A", "B", noon(2), false),
+            entry("d.flac", "A", "B", noon(3), true),
+        ]);
+        let days: Vec<String> = history
+            .play_days()
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(days, ["2024-03-01", "2024-03-03"]);
+    }
+}
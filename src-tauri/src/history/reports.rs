@@ -0,0 +1,460 @@
+//! Listening statistics computed from the local history.
+//!
+//! Periods and days are in local time; weeks start on Monday. Only completed entries count as
+//! plays, while listening time includes skipped songs.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
+
+use chrono::{DateTime, Datelike, Duration as Days, Local, NaiveDate, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::reflection::{response::TVal, tag::Tag, Reflect};
+
+use super::{History, HistoryEntry, HistoryQuery};
+
+/// Days without a play after which a song can be a forgotten favourite.
+const FORGOTTEN_AFTER_DAYS: i64 = 90;
+/// Plays a song needs before it counts as a favourite.
+const FAVOURITE_PLAYS: usize = 3;
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Week,
+    Month,
+    Year,
+    AllTime,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct RankedItem {
+    pub name: String,
+    /// Artist of an album or track.
+    pub artist: Option<String>,
+    /// URI of a track.
+    pub uri: Option<String>,
+    pub plays: usize,
+    pub listening_time: TVal,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Streak {
+    /// Consecutive days with at least one play.
+    pub days: u32,
+    /// First day, as `YYYY-MM-DD`.
+    pub start: String,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct ForgottenFavourite {
+    pub uri: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub plays: usize,
+    /// Seconds since the Unix epoch.
+    pub last_played: u64,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct ListeningReport {
+    pub period: ReportPeriod,
+    /// Start of the period, in seconds since the Unix epoch; `0` for all time.
+    pub from: u64,
+    /// End of the period (exclusive).
+    pub to: u64,
+    pub plays: usize,
+    pub skips: usize,
+    pub listening_time: TVal,
+    pub top_artists: Vec<RankedItem>,
+    pub top_albums: Vec<RankedItem>,
+    pub top_tracks: Vec<RankedItem>,
+    pub top_genres: Vec<RankedItem>,
+    /// Plays per weekday (Monday first) and hour of day.
+    pub heatmap: Vec<Vec<u32>>,
+    /// Longest streak within the period.
+    pub longest_streak: Option<Streak>,
+    /// Streak running up to today or yesterday, over the whole history.
+    pub current_streak: Option<Streak>,
+    /// Songs played often before but not in the last 90 days before the end of the period.
+    pub forgotten_favourites: Vec<ForgottenFavourite>,
+}
+
+fn local(secs: u64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(secs as i64, 0).single()
+}
+
+fn midnight(date: NaiveDate) -> u64 {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map_or(0, |t| t.timestamp().max(0) as u64)
+}
+
+/// Bounds of the period containing `at`.
+fn bounds(period: ReportPeriod, at: DateTime<Local>) -> (u64, u64) {
+    let today = at.date_naive();
+    let (start, end) = match period {
+        ReportPeriod::Week => {
+            let start = today - Days::days(today.weekday().num_days_from_monday() as i64);
+            (start, start + Days::days(7))
+        }
+        ReportPeriod::Month => {
+            let start = today.with_day(1).unwrap_or(today);
+            let end = if start.month() == 12 {
+                NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+            };
+            (start, end.unwrap_or(start))
+        }
+        ReportPeriod::Year => {
+            let start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap_or(today);
+            let end = NaiveDate::from_ymd_opt(today.year() + 1, 1, 1).unwrap_or(start);
+            (start, end)
+        }
+        ReportPeriod::AllTime => return (0, at.timestamp().max(0) as u64 + 1),
+    };
+    (midnight(start), midnight(end))
+}
+
+#[derive(Default)]
+struct Tally {
+    artist: Option<String>,
+    uri: Option<String>,
+    plays: usize,
+    listened: Duration,
+}
+
+fn add(
+    tallies: &mut HashMap<String, Tally>,
+    key: String,
+    entry: &HistoryEntry,
+    init: impl FnOnce(&mut Tally),
+) {
+    let tally = tallies.entry(key).or_insert_with(|| {
+        let mut tally = Tally::default();
+        init(&mut tally);
+        tally
+    });
+    if entry.completed {
+        tally.plays += 1;
+    }
+    tally.listened += entry.listened.clone().into();
+}
+
+fn top(
+    tallies: HashMap<String, Tally>,
+    names: impl Fn(&str) -> String,
+    limit: usize,
+) -> Vec<RankedItem> {
+    let mut items: Vec<RankedItem> = tallies
+        .into_iter()
+        .filter(|(_, t)| t.plays > 0)
+        .map(|(key, t)| RankedItem {
+            name: names(&key),
+            artist: t.artist,
+            uri: t.uri,
+            plays: t.plays,
+            listening_time: t.listened.reflect(),
+        })
+        .collect();
+    items.sort_by(|a, b| {
+        b.plays
+            .cmp(&a.plays)
+            .then_with(|| b.listening_time.secs.cmp(&a.listening_time.secs))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    items.truncate(limit);
+    items
+}
+
+/// Streaks of consecutive days in `days`, as (first day, length).
+fn streaks(days: &BTreeSet<NaiveDate>) -> Vec<(NaiveDate, u32)> {
+    let mut out: Vec<(NaiveDate, u32)> = Vec::new();
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        match (previous, out.last_mut()) {
+            (Some(p), Some((_, length))) if p + Days::days(1) == day => *length += 1,
+            _ => out.push((day, 1)),
+        }
+        previous = Some(day);
+    }
+    out
+}
+
+fn streak((start, days): (NaiveDate, u32)) -> Streak {
+    Streak {
+        days,
+        start: start.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Compute the report for the period containing `at`, keeping `limit` items in every top list.
+pub fn report(
+    history: &History,
+    period: ReportPeriod,
+    at: DateTime<Local>,
+    limit: usize,
+) -> Result<ListeningReport, String> {
+    let (from, to) = bounds(period, at);
+    let query = HistoryQuery {
+        from: Some(from),
+        to: Some(to),
+        ..HistoryQuery::default()
+    };
+    let entries = history.entries(&query)?;
+    let days = history.play_days()?;
+    let cutoff = to.saturating_sub(Days::days(FORGOTTEN_AFTER_DAYS).num_seconds() as u64);
+    let forgotten = history
+        .stale_favourites(FAVOURITE_PLAYS, cutoff, to, limit)?
+        .into_iter()
+        .map(|(entry, plays)| ForgottenFavourite {
+            title: entry.title().map(String::from),
+            artist: entry.artist().map(String::from),
+            uri: entry.uri,
+            plays,
+            last_played: entry.started,
+        })
+        .collect();
+    let today = Local::now().date_naive();
+    Ok(summarize(
+        &entries,
+        &days,
+        (period, from, to),
+        today,
+        limit,
+        forgotten,
+    ))
+}
+
+/// Build the report for the `period` from `from` to `to` out of the `entries` in it, given
+/// every day with plays for the current streak.
+fn summarize(
+    in_period: &[HistoryEntry],
+    all_days: &BTreeSet<NaiveDate>,
+    (period, from, to): (ReportPeriod, u64, u64),
+    today: NaiveDate,
+    limit: usize,
+    forgotten_favourites: Vec<ForgottenFavourite>,
+) -> ListeningReport {
+    let mut artists = HashMap::new();
+    let mut albums = HashMap::new();
+    let mut tracks = HashMap::new();
+    let mut genres = HashMap::new();
+    let mut heatmap = vec![vec![0u32; 24]; 7];
+    let mut days = BTreeSet::new();
+    let mut listened = Duration::ZERO;
+    for entry in in_period {
+        listened += entry.listened.clone().into();
+        let artist = entry.artist().map(String::from);
+        if let Some(name) = &artist {
+            add(&mut artists, name.clone(), entry, |_| {});
+        }
+        if let Some(album) = entry.album() {
+            let album_artist = entry
+                .values(Tag::AlbumArtist)
+                .next()
+                .cloned()
+                .or_else(|| artist.clone());
+            let key = format!(
+                "{}\u{0}{}",
+                album_artist.as_deref().unwrap_or_default(),
+                album
+            );
+            add(&mut albums, key, entry, |t| t.artist = album_artist);
+        }
+        add(&mut tracks, entry.uri.clone(), entry, |t| {
+            t.artist = artist.clone();
+            t.uri = Some(entry.uri.clone());
+        });
+        for genre in entry.values(Tag::Genre) {
+            add(&mut genres, genre.clone(), entry, |_| {});
+        }
+        if entry.completed {
+            if let Some(time) = local(entry.started) {
+                heatmap[time.weekday().num_days_from_monday() as usize][time.hour() as usize] += 1;
+                days.insert(time.date_naive());
+            }
+        }
+    }
+    let titles: HashMap<&str, &str> = in_period
+        .iter()
+        .filter_map(|e| Some((e.uri.as_str(), e.title()?)))
+        .collect();
+
+    let current_streak = streaks(all_days)
+        .last()
+        .copied()
+        .filter(|(start, length)| {
+            let last = *start + Days::days(*length as i64 - 1);
+            last == today || last + Days::days(1) == today
+        })
+        .map(streak);
+    let longest_streak = streaks(&days)
+        .into_iter()
+        .max_by_key(|(start, length)| (*length, std::cmp::Reverse(*start)))
+        .map(streak);
+
+    ListeningReport {
+        period,
+        from,
+        to,
+        plays: in_period.iter().filter(|e| e.completed).count(),
+        skips: in_period.iter().filter(|e| !e.completed).count(),
+        listening_time: listened.reflect(),
+        top_artists: top(artists, str::to_string, limit),
+        top_albums: top(
+            albums,
+            |key| {
+                key.split_once('\u{0}')
+                    .map_or(key, |(_, album)| album)
+                    .to_string()
+            },
+            limit,
+        ),
+        top_tracks: top(
+            tracks,
+            |uri| titles.get(uri).copied().unwrap_or(uri).to_string(),
+            limit,
+        ),
+        top_genres: top(genres, str::to_string, limit),
+        heatmap,
+        longest_streak,
+        current_streak,
+        forgotten_favourites,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn at(date: NaiveDate, hour: u32) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+    }
+
+    fn entry(uri: &str, artist: &str, album: &str, started: DateTime<Local>) -> HistoryEntry {
+        HistoryEntry {
+            id: 0,
+            uri: uri.to_string(),
+            tags: HashMap::from([
+                ("Title".to_string(), vec![uri.to_uppercase()]),
+                ("Artist".to_string(), vec![artist.to_string()]),
+                ("Album".to_string(), vec![album.to_string()]),
+                (
+                    "Genre".to_string(),
+                    vec!["Rock".to_string(), "Pop".to_string()],
+                ),
+            ]),
+            started: started.timestamp() as u64,
+            duration: None,
+            listened: Duration::from_secs(60).reflect(),
+            completed: true,
+            server: String::new(),
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // 2024-03-06 is a Wednesday.
+        let (from, to) = bounds(ReportPeriod::Week, at(day(2024, 3, 6), 15));
+        assert_eq!(from, midnight(day(2024, 3, 4)));
+        assert_eq!(to, midnight(day(2024, 3, 11)));
+    }
+
+    #[test]
+    fn months_and_years_wrap() {
+        let december = at(day(2023, 12, 31), 23);
+        assert_eq!(
+            bounds(ReportPeriod::Month, december),
+            (midnight(day(2023, 12, 1)), midnight(day(2024, 1, 1)))
+        );
+        assert_eq!(
+            bounds(ReportPeriod::Year, december),
+            (midnight(day(2023, 1, 1)), midnight(day(2024, 1, 1)))
+        );
+        let (from, to) = bounds(ReportPeriod::AllTime, december);
+        assert_eq!(from, 0);
+        assert_eq!(to, december.timestamp() as u64 + 1);
+    }
+
+    #[test]
+    fn finds_streaks_of_consecutive_days() {
+        let days = BTreeSet::from([
+            day(2024, 2, 28),
+            day(2024, 2, 29),
+            day(2024, 3, 1),
+            day(2024, 3, 3),
+        ]);
+        assert_eq!(
+            streaks(&days),
+            [(day(2024, 2, 28), 3), (day(2024, 3, 3), 1)]
+        );
+        assert!(streaks(&BTreeSet::new()).is_empty());
+    }
+
+    #[test]
+    fn summarizes_plays_skips_and_tops() {
+        let monday = day(2024, 3, 4);
+        let mut skipped = entry("b.flac", "Blur", "Parklife", at(monday, 9));
+        skipped.completed = false;
+        let entries = [
+            entry("a.flac", "Air", "Moon Safari", at(monday, 9)),
+            entry("a.flac", "Air", "Moon Safari", at(day(2024, 3, 5), 21)),
+            entry("c.flac", "Blur", "Parklife", at(day(2024, 3, 5), 22)),
+            skipped,
+        ];
+        let all_days = BTreeSet::from([monday, day(2024, 3, 5)]);
+        let report = summarize(
+            &entries,
+            &all_days,
+            (ReportPeriod::Week, 0, 1),
+            day(2024, 3, 6),
+            1,
+            vec![],
+        );
+        assert_eq!(report.plays, 3);
+        assert_eq!(report.skips, 1);
+        assert_eq!(report.listening_time.secs, 240);
+        assert_eq!(report.top_artists.len(), 1);
+        assert_eq!(report.top_artists[0].name, "Air");
+        assert_eq!(report.top_artists[0].plays, 2);
+        assert_eq!(report.top_albums[0].name, "Moon Safari");
+        assert_eq!(report.top_albums[0].artist.as_deref(), Some("Air"));
+        assert_eq!(report.top_tracks[0].name, "A.FLAC");
+        assert_eq!(report.top_tracks[0].uri.as_deref(), Some("a.flac"));
+        assert_eq!(report.top_genres[0].plays, 3);
+        assert_eq!(report.heatmap[0][9], 1);
+        assert_eq!(report.heatmap[1][21], 1);
+        let longest = report.longest_streak.unwrap();
+        assert_eq!((longest.days, longest.start.as_str()), (2, "2024-03-04"));
+        let current = report.current_streak.unwrap();
+        assert_eq!((current.days, current.start.as_str()), (2, "2024-03-04"));
+    }
+
+    #[test]
+    fn current_streak_ends_yesterday_at_the_latest() {
+        let all_days = BTreeSet::from([day(2024, 3, 4)]);
+        let report = |today| {
+            summarize(
+                &[],
+                &all_days,
+                (ReportPeriod::AllTime, 0, 1),
+                today,
+                10,
+                vec![],
+            )
+        };
+        assert!(report(day(2024, 3, 5)).current_streak.is_some());
+        assert!(report(day(2024, 3, 6)).current_streak.is_none());
+        assert!(report(day(2024, 3, 6)).longest_streak.is_none());
+    }
+}
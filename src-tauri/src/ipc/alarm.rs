@@ -0,0 +1,30 @@
+use tauri::State;
+
+use crate::alarm::{Alarm, Alarms, NextAlarm};
+
+#[tauri::command]
+#[specta::specta]
+pub fn alarms(store: State<'_, Alarms>) -> Vec<Alarm> {
+    store.list()
+}
+
+/// Add an alarm, or replace the one with the same name.
+#[tauri::command]
+#[specta::specta]
+pub fn save_alarm(store: State<'_, Alarms>, alarm: Alarm) -> Result<(), String> {
+    store.save(alarm)
+}
+
+/// Remove an alarm, returning whether it existed.
+#[tauri::command]
+#[specta::specta]
+pub fn delete_alarm(store: State<'_, Alarms>, name: String) -> Result<bool, String> {
+    store.delete(&name)
+}
+
+/// The enabled alarm going off next.
+#[tauri::command]
+#[specta::specta]
+pub fn next_alarm(store: State<'_, Alarms>) -> Option<NextAlarm> {
+    store.next()
+}
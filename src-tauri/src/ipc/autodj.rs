@@ -0,0 +1,44 @@
+use tauri::{AppHandle, Manager, State};
+
+use crate::autodj::{AutoDj, AutoDjConfig, AutoDjStatus};
+
+fn emit_status(handle: &AppHandle, status: AutoDjStatus) -> Result<AutoDjStatus, String> {
+    handle
+        .emit_all("autodj", status.clone())
+        .map_err(|e| e.to_string())?;
+    Ok(status)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn autodj_status(dj: State<'_, AutoDj>) -> AutoDjStatus {
+    dj.status()
+}
+
+/// Start keeping the queue filled, with `config` if given or the last configuration otherwise.
+#[tauri::command]
+#[specta::specta]
+pub fn autodj_start(
+    handle: AppHandle,
+    dj: State<'_, AutoDj>,
+    config: Option<AutoDjConfig>,
+) -> Result<AutoDjStatus, String> {
+    emit_status(&handle, dj.start(config))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn autodj_stop(handle: AppHandle, dj: State<'_, AutoDj>) -> Result<AutoDjStatus, String> {
+    emit_status(&handle, dj.stop())
+}
+
+/// Change the source or thresholds, taking effect immediately if the Auto-DJ is running.
+#[tauri::command]
+#[specta::specta]
+pub fn autodj_configure(
+    handle: AppHandle,
+    dj: State<'_, AutoDj>,
+    config: AutoDjConfig,
+) -> Result<AutoDjStatus, String> {
+    emit_status(&handle, dj.configure(config))
+}
@@ -0,0 +1,152 @@
+use mpd_client::{
+    client::CommandError,
+    commands::{self, Command},
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::TypedResponseError,
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::State;
+
+use crate::reflection::{
+    request::{SeekMode, SingleMode},
+    response::{BatchError, BatchResponse, BatchResult},
+};
+
+use super::mpd::{AddVariant, DeleteVariant, ItemVariant, MoveTarget, MoveVariant, PrioVariant};
+
+/// A single queue/playback operation that can be sent as part of a command list.
+#[derive(Serialize, Deserialize, Type, Clone)]
+pub enum BatchOperation {
+    Add {
+        uri: String,
+        prop: AddVariant,
+    },
+    Delete(DeleteVariant),
+    Move {
+        variant: MoveVariant,
+        target: MoveTarget,
+    },
+    Prio {
+        priority: u8,
+        variant: PrioVariant,
+    },
+    Clear,
+    Load {
+        playlist: String,
+        range: Option<(usize, usize)>,
+    },
+    PlaylistAdd {
+        playlist: String,
+        uri: String,
+        position: Option<usize>,
+    },
+    Play(Option<ItemVariant>),
+    Pause(bool),
+    Stop,
+    Next,
+    Previous,
+    SeekCur(SeekMode),
+    SetVol(u8),
+    Consume(bool),
+    Random(bool),
+    Repeat(bool),
+    Single(SingleMode),
+}
+
+impl Command for BatchOperation {
+    type Response = BatchResult;
+
+    fn command(&self) -> RawCommand {
+        match self {
+            BatchOperation::Add { uri, prop } => prop.apply(commands::Add::uri(uri)).command(),
+            BatchOperation::Delete(variant) => commands::Delete::from(*variant).command(),
+            BatchOperation::Move { variant, target } => variant.to(*target).command(),
+            BatchOperation::Prio { priority, variant } => {
+                variant.with_priority(*priority).command()
+            }
+            BatchOperation::Clear => commands::ClearQueue.command(),
+            BatchOperation::Load { playlist, range } => {
+                let mut cmd = commands::LoadPlaylist::name(playlist);
+                if let Some((start, end)) = range {
+                    cmd = cmd.range(*start..*end);
+                }
+                cmd.command()
+            }
+            BatchOperation::PlaylistAdd {
+                playlist,
+                uri,
+                position,
+            } => {
+                let mut cmd = commands::AddToPlaylist::new(playlist, uri);
+                if let Some(pos) = position {
+                    cmd = cmd.at(*pos);
+                }
+                cmd.command()
+            }
+            BatchOperation::Play(variant) => match variant {
+                Some(v) => commands::Play::song(commands::Song::from(*v)).command(),
+                None => commands::Play::current().command(),
+            },
+            BatchOperation::Pause(pause) => commands::SetPause(*pause).command(),
+            BatchOperation::Stop => commands::Stop.command(),
+            BatchOperation::Next => commands::Next.command(),
+            BatchOperation::Previous => commands::Previous.command(),
+            BatchOperation::SeekCur(mode) => commands::Seek(mode.clone().into()).command(),
+            BatchOperation::SetVol(volume) => commands::SetVolume(*volume).command(),
+            BatchOperation::Consume(consume) => commands::SetConsume(*consume).command(),
+            BatchOperation::Random(random) => commands::SetRandom(*random).command(),
+            BatchOperation::Repeat(repeat) => commands::SetRepeat(*repeat).command(),
+            BatchOperation::Single(single) => commands::SetSingle(single.clone().into()).command(),
+        }
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        match self {
+            BatchOperation::Add { uri, prop } => prop
+                .apply(commands::Add::uri(&uri))
+                .response(frame)
+                .map(|id| BatchResult::Id(id.0)),
+            _ => Ok(BatchResult::Done),
+        }
+    }
+}
+
+/// Send `operations` as one command list.
+///
+/// MPD stops at the first failing operation, in which case the results of the operations before
+/// it are returned together with the index and message from the ACK.
+#[tauri::command]
+#[specta::specta]
+pub async fn batch(
+    client: State<'_, Client>,
+    operations: Vec<BatchOperation>,
+) -> Result<BatchResponse, String> {
+    match client.command_list(operations.clone()).await {
+        Ok(results) => Ok(BatchResponse {
+            results,
+            error: None,
+        }),
+        Err(CommandError::ErrorResponse {
+            error,
+            succesful_frames,
+        }) => {
+            let results = operations
+                .into_iter()
+                .zip(succesful_frames)
+                .map(|(op, frame)| op.response(frame))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok(BatchResponse {
+                results,
+                error: Some(BatchError {
+                    index: error.command_index,
+                    code: error.code,
+                    message: error.message.to_string(),
+                }),
+            })
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,10 @@
+use tauri::State;
+
+use crate::clock::{PlaybackClock, PlaybackPosition};
+
+/// Position in the current song according to the backend clock, as also emitted by `elapsed`.
+#[tauri::command]
+#[specta::specta]
+pub fn elapsed(clock: State<'_, PlaybackClock>) -> PlaybackPosition {
+    clock.position()
+}
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use chrono::{Local, TimeZone};
+use tauri::{AppHandle, State};
+
+use crate::{
+    history::{
+        reports::{self, ListeningReport, ReportPeriod},
+        History, HistoryFormat, HistoryPage, HistoryQuery,
+    },
+    library::DEFAULT_SEARCH_LIMIT,
+    scope::file_within,
+};
+
+const DEFAULT_TOP_LIMIT: usize = 10;
+/// Directory under the app data dir that history is exported to.
+const EXPORT_DIR: &str = "exports";
+
+fn export_dir(handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("No app data directory")?;
+    Ok(dir.join(EXPORT_DIR))
+}
+
+/// Listening history matching `query`, newest first, `limit` entries (50 by default) after
+/// skipping `offset`.
+#[tauri::command]
+#[specta::specta]
+pub fn history(
+    history: State<'_, History>,
+    query: Option<HistoryQuery>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<HistoryPage, String> {
+    history.query(
+        &query.unwrap_or_default(),
+        offset.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+    )
+}
+
+/// Export the listening history matching `query` to `file` in the app's export directory,
+/// returning the number of entries.
+///
+/// Without `format`, it is guessed from the file extension.
+#[tauri::command]
+#[specta::specta]
+pub fn export_history(
+    handle: AppHandle,
+    history: State<'_, History>,
+    file: String,
+    format: Option<HistoryFormat>,
+    query: Option<HistoryQuery>,
+) -> Result<usize, String> {
+    let path = file_within(&export_dir(&handle)?, &file)?;
+    let format = format
+        .or_else(|| HistoryFormat::from_path(&path))
+        .ok_or_else(|| format!("Unknown history format: {}", path.display()))?;
+    history.export(&query.unwrap_or_default(), &path, format)
+}
+
+/// Listening statistics for the week, month or year containing `at` (seconds since the Unix
+/// epoch, now by default), with `limit` entries (10 by default) in every top list.
+#[tauri::command]
+#[specta::specta]
+pub fn listening_report(
+    history: State<'_, History>,
+    period: ReportPeriod,
+    at: Option<u64>,
+    limit: Option<usize>,
+) -> Result<ListeningReport, String> {
+    let at = match at {
+        Some(secs) => Local
+            .timestamp_opt(secs as i64, 0)
+            .single()
+            .ok_or_else(|| format!("Invalid time: {}", secs))?,
+        None => Local::now(),
+    };
+    reports::report(&history, period, at, limit.unwrap_or(DEFAULT_TOP_LIMIT))
+}
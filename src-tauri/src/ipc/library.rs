@@ -0,0 +1,36 @@
+use tauri::State;
+
+use crate::{
+    library::{Library, DEFAULT_SEARCH_LIMIT},
+    reflection::response::{Album, Artist, SearchHit},
+};
+
+/// Fuzzy search over titles, artists, albums and paths of the indexed library.
+///
+/// Every word of `query` has to match, allowing for prefixes and typos. Results are ranked best
+/// first, at most `limit` of them (50 by default).
+#[tauri::command]
+#[specta::specta]
+pub async fn quick_search(
+    library: State<'_, Library>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    Ok(library.search(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)))
+}
+
+/// Albums of the indexed library, or only those `artist` appears on.
+#[tauri::command]
+#[specta::specta]
+pub async fn albums(
+    library: State<'_, Library>,
+    artist: Option<String>,
+) -> Result<Vec<Album>, String> {
+    Ok(library.albums(artist.as_deref()))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn artists(library: State<'_, Library>) -> Result<Vec<Artist>, String> {
+    Ok(library.artists())
+}
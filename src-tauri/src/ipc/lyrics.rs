@@ -0,0 +1,28 @@
+use mpd_client::Client;
+use tauri::State;
+
+use crate::{
+    art::CoverResolver,
+    lyrics::{self, Lyrics, LyricsTracker},
+};
+
+/// Lyrics of the song at `uri`, from a `.lrc` file next to it or its tags.
+#[tauri::command]
+#[specta::specta]
+pub async fn lyrics(
+    client: State<'_, Client>,
+    resolver: State<'_, CoverResolver>,
+    uri: String,
+) -> Result<Option<Lyrics>, String> {
+    Ok(lyrics::load(&client, &resolver, &uri).await)
+}
+
+/// Lyrics of the current song.
+///
+/// Also has the active line emitted again as `lyrics-line`, so a reloaded UI can pick it up.
+#[tauri::command]
+#[specta::specta]
+pub fn current_lyrics(tracker: State<'_, LyricsTracker>) -> Option<Lyrics> {
+    tracker.resend();
+    tracker.current()
+}
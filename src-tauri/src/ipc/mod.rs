@@ -1,5 +1,29 @@
+mod alarm;
+mod autodj;
+mod batch;
+mod clock;
+mod history;
+mod library;
+mod lyrics;
 mod mpd;
-mod sync;
+mod playlist;
+mod ratings;
+mod scrobble;
+mod settings;
+mod sleep;
+mod update;
 
+pub use alarm::*;
+pub use autodj::*;
+pub use batch::*;
+pub use clock::*;
+pub use history::*;
+pub use library::*;
+pub use lyrics::*;
 pub use mpd::*;
-pub use sync::*;
+pub use playlist::*;
+pub use ratings::*;
+pub use scrobble::*;
+pub use settings::*;
+pub use sleep::*;
+pub use update::*;
@@ -8,19 +8,26 @@ use mpd_client::{
 };
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use tauri::State;
-
-use crate::reflection::{
-    request::{SeekMode, SingleMode},
-    response::{
-        AlbumArt, Count, List, Playlist, Song, SongInQueue, Stats, Status, StickerFind, StickerGet,
-        StickerList, TVal,
+use tauri::{AppHandle, State};
+
+use crate::{
+    extension,
+    reflection::{
+        request::{
+            NumericComparison, SeekMode, SingleMode, StickerOperator, StickerSort, StickerType,
+        },
+        response::{
+            Count, List, Mount, Neighbor, Playlist, PlaylistMatch, Song, SongInQueue, Stats,
+            Status, TVal,
+        },
+        tag::Tag,
+        Reflect,
     },
-    tag::Tag,
-    Reflect,
+    stickers,
+    update::{emit_update_events, UpdateTracker},
 };
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
 pub enum AddVariant {
     Append,
     At(usize),
@@ -28,7 +35,18 @@ pub enum AddVariant {
     AfterCurrent(usize),
 }
 
-#[derive(Serialize, Deserialize, Type)]
+impl AddVariant {
+    pub fn apply(self, cmd: commands::Add<'_>) -> commands::Add<'_> {
+        match self {
+            AddVariant::Append => cmd,
+            AddVariant::At(pos) => cmd.at(pos),
+            AddVariant::BeforeCurrent(delta) => cmd.before_current(delta),
+            AddVariant::AfterCurrent(delta) => cmd.after_current(delta),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub enum Operator {
     Equal,
     NotEqual,
@@ -49,28 +67,28 @@ impl From<Operator> for filter::Operator {
     }
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub enum FilterVariant {
     Expr { op: Operator, val: String },
     Exist,
     Absent,
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub struct FilterElement {
     tag: Tag,
     variant: FilterVariant,
     negate: bool,
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub struct Filter {
     elements: Vec<FilterElement>,
     negate: bool,
 }
 
 impl Filter {
-    fn render(self) -> Result<Option<filter::Filter>, TagError> {
+    pub fn render(self) -> Result<Option<filter::Filter>, TagError> {
         if self.elements.is_empty() {
             Ok(None)
         } else {
@@ -102,21 +120,50 @@ impl Filter {
     }
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
 pub enum DeleteVariant {
     Id(u64),
     Position(usize),
     Range(usize, usize),
 }
 
-#[derive(Serialize, Deserialize, Type)]
+impl From<DeleteVariant> for commands::Delete {
+    fn from(value: DeleteVariant) -> Self {
+        match value {
+            DeleteVariant::Id(id) => commands::Delete::id(SongId(id)),
+            DeleteVariant::Position(pos) => commands::Delete::position(SongPosition(pos)),
+            DeleteVariant::Range(start, end) => {
+                commands::Delete::range(SongPosition(start)..SongPosition(end))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
 pub enum MoveVariant {
     Id(u64),
     Position(usize),
     Range(usize, usize),
 }
 
-#[derive(Serialize, Deserialize, Type)]
+impl MoveVariant {
+    pub fn to(self, target: MoveTarget) -> commands::Move {
+        let cmd_builder = match self {
+            MoveVariant::Id(id) => commands::Move::id(SongId(id)),
+            MoveVariant::Position(pos) => commands::Move::position(SongPosition(pos)),
+            MoveVariant::Range(start, end) => {
+                commands::Move::range(SongPosition(start)..SongPosition(end))
+            }
+        };
+        match target {
+            MoveTarget::Position(pos) => cmd_builder.to_position(SongPosition(pos)),
+            MoveTarget::AfterCurrent(delta) => cmd_builder.after_current(delta),
+            MoveTarget::BeforeCurrent(delta) => cmd_builder.before_current(delta),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
 pub enum MoveTarget {
     Position(usize),
     BeforeCurrent(usize),
@@ -129,7 +176,7 @@ pub enum PlaylistDeleteVariant {
     Range(usize, usize),
 }
 
-#[derive(Serialize, Deserialize, Type)]
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
 pub enum ItemVariant {
     Id(u64),
     Position(usize),
@@ -144,23 +191,27 @@ impl From<ItemVariant> for commands::Song {
     }
 }
 
-#[derive(Serialize, Deserialize, Type)]
-pub enum StickerFindVariant {
-    Eq,
-    Gt,
-    Lt,
+#[derive(Serialize, Deserialize, Type, Clone, Copy)]
+pub enum PrioVariant {
+    Id(u64),
+    Range(usize, usize),
+}
+
+impl PrioVariant {
+    pub fn with_priority(self, priority: u8) -> extension::SetPriority {
+        match self {
+            PrioVariant::Id(id) => extension::SetPriority::id(priority, SongId(id)),
+            PrioVariant::Range(start, end) => {
+                extension::SetPriority::range(priority, SongPosition(start)..SongPosition(end))
+            }
+        }
+    }
 }
 
 #[tauri::command]
 #[specta::specta]
 pub async fn add(client: State<'_, Client>, uri: String, prop: AddVariant) -> Result<u64, String> {
-    let mut cmd = commands::Add::uri(&uri);
-    cmd = match prop {
-        AddVariant::Append => cmd,
-        AddVariant::At(pos) => cmd.at(pos),
-        AddVariant::BeforeCurrent(delta) => cmd.before_current(delta),
-        AddVariant::AfterCurrent(delta) => cmd.after_current(delta),
-    };
+    let cmd = prop.apply(commands::Add::uri(&uri));
     let res = client
         .command(cmd)
         .await
@@ -185,38 +236,6 @@ pub async fn playlistadd(
     Ok(())
 }
 
-#[tauri::command]
-#[specta::specta]
-pub async fn albumart(
-    client: State<'_, Client>,
-    uri: String,
-    offset: usize,
-) -> Result<Option<AlbumArt>, String> {
-    let cmd = commands::AlbumArt::new(&uri).offset(offset);
-    let res = client
-        .command(cmd)
-        .await
-        .map(|x| x.reflect())
-        .map_err(|e| e.to_string())?;
-    Ok(res)
-}
-
-#[tauri::command]
-#[specta::specta]
-pub async fn readpicture(
-    client: State<'_, Client>,
-    uri: String,
-    offset: usize,
-) -> Result<Option<AlbumArt>, String> {
-    let cmd = commands::AlbumArtEmbedded::new(&uri).offset(offset);
-    let res = client
-        .command(cmd)
-        .await
-        .map(|x| x.reflect())
-        .map_err(|e| e.to_string())?;
-    Ok(res)
-}
-
 #[tauri::command]
 #[specta::specta]
 pub async fn playlistclear(client: State<'_, Client>, playlist: String) -> Result<(), String> {
@@ -293,14 +312,10 @@ pub async fn currentsong(client: State<'_, Client>) -> Result<Option<SongInQueue
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_item(client: State<'_, Client>, variant: DeleteVariant) -> Result<(), String> {
-    let cmd = match variant {
-        DeleteVariant::Id(id) => commands::Delete::id(SongId(id)),
-        DeleteVariant::Position(pos) => commands::Delete::position(SongPosition(pos)),
-        DeleteVariant::Range(start, end) => {
-            commands::Delete::range(SongPosition(start)..SongPosition(end))
-        }
-    };
-    client.command(cmd).await.map_err(|e| e.to_string())?;
+    client
+        .command(commands::Delete::from(variant))
+        .await
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -455,6 +470,28 @@ pub async fn channels(client: State<'_, Client>) -> Result<Vec<String>, String>
     Ok(res)
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn listmounts(client: State<'_, Client>) -> Result<Vec<Mount>, String> {
+    let res = client
+        .command(extension::ListMounts)
+        .await
+        .map(|x| x.reflect())
+        .map_err(|e| e.to_string())?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn listneighbors(client: State<'_, Client>) -> Result<Vec<Neighbor>, String> {
+    let res = client
+        .command(extension::ListNeighbors)
+        .await
+        .map(|x| x.reflect())
+        .map_err(|e| e.to_string())?;
+    Ok(res)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn load(
@@ -477,19 +514,20 @@ pub async fn move_song(
     variant: MoveVariant,
     target: MoveTarget,
 ) -> Result<(), String> {
-    let cmd_builder = match variant {
-        MoveVariant::Id(id) => commands::Move::id(SongId(id)),
-        MoveVariant::Position(pos) => mpd_client::commands::Move::position(SongPosition(pos)),
-        MoveVariant::Range(start, end) => {
-            commands::Move::range(SongPosition(start)..SongPosition(end))
-        }
-    };
-    let cmd = match target {
-        MoveTarget::Position(pos) => cmd_builder.to_position(SongPosition(pos)),
-        MoveTarget::AfterCurrent(delta) => cmd_builder.after_current(delta),
-        MoveTarget::BeforeCurrent(delta) => cmd_builder.before_current(delta),
-    };
-    client.command(cmd).await.map_err(|e| e.to_string())?;
+    client
+        .command(variant.to(target))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn mount(client: State<'_, Client>, path: String, uri: String) -> Result<(), String> {
+    client
+        .command(extension::MountStorage::new(&path, &uri))
+        .await
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -546,6 +584,20 @@ pub async fn playlistinfo(client: State<'_, Client>) -> Result<Vec<SongInQueue>,
     Ok(res)
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn prio(
+    client: State<'_, Client>,
+    priority: u8,
+    variant: PrioVariant,
+) -> Result<(), String> {
+    client
+        .command(variant.with_priority(priority))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn playlistdelete(
@@ -565,6 +617,104 @@ pub async fn playlistdelete(
     Ok(())
 }
 
+/// Songs in the queue matching `filter` exactly.
+#[tauri::command]
+#[specta::specta]
+pub async fn playlistfind(
+    client: State<'_, Client>,
+    filter: Filter,
+) -> Result<Vec<SongInQueue>, String> {
+    let Some(f) = filter.render().map_err(|e| e.to_string())? else {
+        return Err("Empty filter.".to_string());
+    };
+    let res = client
+        .command(extension::QueueFind::new(f))
+        .await
+        .map(|x| x.reflect())
+        .map_err(|e| e.to_string())?;
+    Ok(res)
+}
+
+/// Songs in the queue matching `filter`, ignoring case.
+#[tauri::command]
+#[specta::specta]
+pub async fn playlistsearch(
+    client: State<'_, Client>,
+    filter: Filter,
+) -> Result<Vec<SongInQueue>, String> {
+    let Some(f) = filter.render().map_err(|e| e.to_string())? else {
+        return Err("Empty filter.".to_string());
+    };
+    let res = client
+        .command(extension::QueueFind::search(f))
+        .await
+        .map(|x| x.reflect())
+        .map_err(|e| e.to_string())?;
+    Ok(res)
+}
+
+/// Songs of a stored playlist matching `filter`, ignoring case. Requires MPD 0.24.
+#[tauri::command]
+#[specta::specta]
+pub async fn searchplaylist(
+    client: State<'_, Client>,
+    playlist: String,
+    filter: Filter,
+) -> Result<Vec<PlaylistMatch>, String> {
+    let Some(f) = filter.render().map_err(|e| e.to_string())? else {
+        return Err("Empty filter.".to_string());
+    };
+    let entries = client
+        .command(extension::SearchPlaylist::new(&playlist, f))
+        .await
+        .map_err(|e| e.to_string())?;
+    // Without positions from the server, line the matches up with the playlist; they come back
+    // in playlist order.
+    let all = if entries.iter().any(|e| e.position.is_none()) {
+        client
+            .command(commands::GetPlaylist(&playlist))
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    let mut next = 0;
+    let mut res = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let position = match entry.position {
+            Some(pos) => pos,
+            None => {
+                let offset = all[next..]
+                    .iter()
+                    .position(|s| s.url == entry.song.url)
+                    .ok_or_else(|| format!("'{}' is not in '{}'", entry.song.url, playlist))?;
+                next + offset
+            }
+        };
+        next = position + 1;
+        res.push(PlaylistMatch {
+            position,
+            song: entry.song.reflect(),
+        });
+    }
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn playlistmove(
+    client: State<'_, Client>,
+    playlist: String,
+    from: usize,
+    to: usize,
+) -> Result<(), String> {
+    client
+        .command(commands::MoveInPlaylist::new(&playlist, from, to))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn rename(client: State<'_, Client>, from: String, to: String) -> Result<(), String> {
@@ -577,7 +727,12 @@ pub async fn rename(client: State<'_, Client>, from: String, to: String) -> Resu
 
 #[tauri::command]
 #[specta::specta]
-pub async fn rescan(client: State<'_, Client>, uri: Option<String>) -> Result<u64, String> {
+pub async fn rescan(
+    handle: AppHandle,
+    client: State<'_, Client>,
+    tracker: State<'_, UpdateTracker>,
+    uri: Option<String>,
+) -> Result<u64, String> {
     let res = if let Some(uri) = uri {
         let cmd = commands::Rescan::new().uri(&uri);
         client.command(cmd).await.map_err(|e| e.to_string())?
@@ -585,6 +740,7 @@ pub async fn rescan(client: State<'_, Client>, uri: Option<String>) -> Result<u6
         let cmd = commands::Rescan::new();
         client.command(cmd).await.map_err(|e| e.to_string())?
     };
+    track_update_job(&handle, &client, &tracker, res).await?;
     Ok(res)
 }
 
@@ -704,78 +860,130 @@ pub async fn status(client: State<'_, Client>) -> Result<Status, String> {
     Ok(res)
 }
 
+/// Delete the sticker `name` of `uri`, or all of its stickers without a name.
 #[tauri::command]
 #[specta::specta]
 pub async fn sticker_delete(
     client: State<'_, Client>,
+    sticker_type: StickerType,
     uri: String,
-    name: String,
+    name: Option<String>,
 ) -> Result<(), String> {
+    let kind = sticker_type.into();
     client
-        .command(commands::StickerDelete::new(&uri, &name))
+        .command(extension::DeleteSticker::new(&kind, &uri, name.as_deref()))
         .await
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Find the stickers `name` under `uri` as `(uri, value)` pairs.
+///
+/// Integer comparisons, `sort` and `window` need MPD 0.24 or later.
 #[tauri::command]
 #[specta::specta]
 pub async fn sticker_find(
     client: State<'_, Client>,
+    sticker_type: StickerType,
     uri: String,
     name: String,
-    value: String,
-    variant: StickerFindVariant,
-) -> Result<StickerFind, String> {
-    let cmd = match variant {
-        StickerFindVariant::Eq => commands::StickerFind::new(&uri, &name).where_eq(&value),
-        StickerFindVariant::Gt => commands::StickerFind::new(&uri, &name).where_gt(&value),
-        StickerFindVariant::Lt => commands::StickerFind::new(&uri, &name).where_lt(&value),
-    };
-    let res = client
-        .command(cmd)
-        .await
-        .map(|x| x.reflect())
-        .map_err(|e| e.to_string())?;
-    Ok(res)
+    filter: Option<(StickerOperator, String)>,
+    sort: Option<(StickerSort, bool)>,
+    window: Option<(usize, usize)>,
+) -> Result<Vec<(String, String)>, String> {
+    let kind = sticker_type.into();
+    let mut cmd = extension::FindStickers::new(&kind, &uri, &name);
+    if let Some((operator, value)) = &filter {
+        cmd = cmd.filter((*operator).into(), value);
+    }
+    if let Some((sort, descending)) = sort {
+        cmd = cmd.sort(sort.into(), descending);
+    }
+    if let Some((start, end)) = window {
+        cmd = cmd.window(start..end);
+    }
+    client.command(cmd).await.map_err(|e| e.to_string())
+}
+
+/// Find the integer stickers `name` under `uri`, comparing and sorting by value locally so it
+/// works with any MPD version. Values that aren't integers are skipped.
+#[tauri::command]
+#[specta::specta]
+pub async fn sticker_find_number(
+    client: State<'_, Client>,
+    sticker_type: StickerType,
+    uri: String,
+    name: String,
+    filter: Option<(NumericComparison, i64)>,
+    descending: Option<bool>,
+    window: Option<(usize, usize)>,
+) -> Result<Vec<(String, i64)>, String> {
+    stickers::find_numbers(
+        &client,
+        &sticker_type.into(),
+        &uri,
+        &name,
+        filter,
+        descending,
+        window.map(|(start, end)| start..end),
+    )
+    .await
 }
 
 #[tauri::command]
 #[specta::specta]
 pub async fn sticker_get(
     client: State<'_, Client>,
+    sticker_type: StickerType,
     uri: String,
     name: String,
-) -> Result<StickerGet, String> {
-    let res = client
-        .command(commands::StickerGet::new(&uri, &name))
+) -> Result<String, String> {
+    let kind = sticker_type.into();
+    client
+        .command(extension::GetSticker::new(&kind, &uri, &name))
         .await
-        .map(|x| x.reflect())
-        .map_err(|e| e.to_string())?;
-    Ok(res)
+        .map_err(|e| e.to_string())
 }
 
+/// The sticker `name` of `uri` as an integer, `None` if it is missing or not a number.
 #[tauri::command]
 #[specta::specta]
-pub async fn sticker_list(client: State<'_, Client>, uri: String) -> Result<StickerList, String> {
-    let res = client
-        .command(commands::StickerList::new(&uri))
+pub async fn sticker_get_number(
+    client: State<'_, Client>,
+    sticker_type: StickerType,
+    uri: String,
+    name: String,
+) -> Result<Option<i64>, String> {
+    stickers::get_number(&client, &sticker_type.into(), &uri, &name).await
+}
+
+/// All stickers of `uri` as `(name, value)` pairs.
+#[tauri::command]
+#[specta::specta]
+pub async fn sticker_list(
+    client: State<'_, Client>,
+    sticker_type: StickerType,
+    uri: String,
+) -> Result<Vec<(String, String)>, String> {
+    let kind = sticker_type.into();
+    client
+        .command(extension::ListStickers::new(&kind, &uri))
         .await
-        .map(|x| x.reflect())
-        .map_err(|e| e.to_string())?;
-    Ok(res)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 #[specta::specta]
 pub async fn sticker_set(
     client: State<'_, Client>,
+    sticker_type: StickerType,
     uri: String,
     name: String,
     value: String,
 ) -> Result<(), String> {
+    let kind = sticker_type.into();
     client
-        .command(commands::StickerSet::new(&uri, &name, &value))
+        .command(extension::SetSticker::new(&kind, &uri, &name, &value))
         .await
         .map_err(|e| e.to_string())?;
     Ok(())
@@ -813,7 +1021,22 @@ pub async fn unsubscribe(client: State<'_, Client>, channel: String) -> Result<(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn update(client: State<'_, Client>, uri: Option<String>) -> Result<u64, String> {
+pub async fn unmount(client: State<'_, Client>, path: String) -> Result<(), String> {
+    client
+        .command(extension::UnmountStorage(&path))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update(
+    handle: AppHandle,
+    client: State<'_, Client>,
+    tracker: State<'_, UpdateTracker>,
+    uri: Option<String>,
+) -> Result<u64, String> {
     let res = match uri {
         Some(uri) => client
             .command(commands::Update::new().uri(&uri))
@@ -824,5 +1047,20 @@ pub async fn update(client: State<'_, Client>, uri: Option<String>) -> Result<u6
             .await
             .map_err(|e| e.to_string())?,
     };
+    track_update_job(&handle, &client, &tracker, res).await?;
     Ok(res)
 }
+
+async fn track_update_job(
+    handle: &AppHandle,
+    client: &Client,
+    tracker: &UpdateTracker,
+    job: u64,
+) -> Result<(), String> {
+    let current = client
+        .command(commands::Status)
+        .await
+        .map_err(|e| e.to_string())?
+        .update_job;
+    emit_update_events(handle, tracker.track(job, current)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use mpd_client::Client;
+use tauri::{AppHandle, State};
+
+use crate::{
+    art::CoverResolver,
+    library::Library,
+    playlist::{
+        self,
+        edit::PlaylistEdit,
+        smart::{SmartPlaylist, SmartPlaylists},
+        PlaylistFormat, PlaylistSource,
+    },
+    reflection::{
+        response::{ImportReport, Song},
+        Reflect,
+    },
+    scope::file_within,
+};
+
+/// Directory under the app data dir that playlist files are imported from and exported to.
+const PLAYLIST_DIR: &str = "playlists";
+
+fn format_for(path: &Path, format: Option<PlaylistFormat>) -> Result<PlaylistFormat, String> {
+    format
+        .or_else(|| PlaylistFormat::from_path(path))
+        .ok_or_else(|| format!("Unknown playlist format: {}", path.display()))
+}
+
+fn playlist_dir(handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("No app data directory")?;
+    Ok(dir.join(PLAYLIST_DIR))
+}
+
+/// Names of the playlist files in the app's playlist directory, which can be imported.
+#[tauri::command]
+#[specta::specta]
+pub fn playlist_files(handle: AppHandle) -> Result<Vec<String>, String> {
+    let mut files: Vec<String> = match fs::read_dir(playlist_dir(&handle)?) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| PlaylistFormat::from_path(Path::new(name)).is_some())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    Ok(files)
+}
+
+/// Export the queue or a stored playlist to `file` in the app's playlist directory, returning
+/// the number of entries written.
+///
+/// Without `format`, it is guessed from the file extension.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_playlist(
+    handle: AppHandle,
+    client: State<'_, Client>,
+    resolver: State<'_, CoverResolver>,
+    source: PlaylistSource,
+    file: String,
+    format: Option<PlaylistFormat>,
+) -> Result<usize, String> {
+    let path = file_within(&playlist_dir(&handle)?, &file)?;
+    let format = format_for(&path, format)?;
+    let music_dir = resolver.music_directory(&client).await;
+    playlist::export(&client, music_dir.as_deref(), &source, &path, format).await
+}
+
+/// Append the songs of `file` in the app's playlist directory to the queue or a stored
+/// playlist.
+///
+/// Entries are matched to songs in the indexed library by path, then by title, artist and
+/// duration; those that match nothing are reported back.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_playlist(
+    handle: AppHandle,
+    client: State<'_, Client>,
+    resolver: State<'_, CoverResolver>,
+    library: State<'_, Library>,
+    file: String,
+    target: PlaylistSource,
+    format: Option<PlaylistFormat>,
+) -> Result<ImportReport, String> {
+    let path = file_within(&playlist_dir(&handle)?, &file)?;
+    let format = format_for(&path, format)?;
+    let music_dir = resolver.music_directory(&client).await;
+    playlist::import(
+        &client,
+        &library,
+        music_dir.as_deref(),
+        &path,
+        format,
+        &target,
+    )
+    .await
+}
+
+/// Sort, dedupe, reverse or shuffle a stored playlist in place.
+///
+/// Returns the number of entries moved or removed.
+#[tauri::command]
+#[specta::specta]
+pub async fn playlistedit(
+    client: State<'_, Client>,
+    playlist: String,
+    edit: PlaylistEdit,
+) -> Result<usize, String> {
+    playlist::edit::apply(&client, &playlist, &edit).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn smart_playlists(store: State<'_, SmartPlaylists>) -> Vec<SmartPlaylist> {
+    store.list()
+}
+
+/// Save a smart playlist, replacing any with the same name.
+#[tauri::command]
+#[specta::specta]
+pub fn save_smart_playlist(
+    store: State<'_, SmartPlaylists>,
+    playlist: SmartPlaylist,
+) -> Result<(), String> {
+    store.save(playlist)
+}
+
+/// Delete a smart playlist. A stored playlist it was materialised into is kept, but no longer
+/// refreshed.
+#[tauri::command]
+#[specta::specta]
+pub fn delete_smart_playlist(
+    store: State<'_, SmartPlaylists>,
+    name: String,
+) -> Result<bool, String> {
+    store.delete(&name)
+}
+
+/// The songs a smart playlist currently yields, saved or not.
+#[tauri::command]
+#[specta::specta]
+pub async fn preview_smart_playlist(
+    client: State<'_, Client>,
+    playlist: SmartPlaylist,
+) -> Result<Vec<Song>, String> {
+    let songs = playlist::smart::songs(&client, &playlist).await?;
+    Ok(songs.into_iter().map(Reflect::reflect).collect())
+}
+
+/// Append the songs of a saved smart playlist to the queue, or replace a stored playlist with
+/// them. A stored playlist is then refreshed whenever the database changes.
+#[tauri::command]
+#[specta::specta]
+pub async fn materialize_smart_playlist(
+    client: State<'_, Client>,
+    store: State<'_, SmartPlaylists>,
+    name: String,
+    target: PlaylistSource,
+) -> Result<usize, String> {
+    playlist::smart::materialize_saved(&client, &store, &name, &target).await
+}
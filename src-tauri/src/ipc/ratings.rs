@@ -0,0 +1,41 @@
+use mpd_client::Client;
+use tauri::State;
+
+use crate::{
+    ipc::Filter,
+    library::{Library, DEFAULT_SEARCH_LIMIT},
+    ratings,
+    reflection::response::{RatedSong, SongStats},
+};
+
+/// Rate a song from 1 to 10, or clear its rating with 0.
+#[tauri::command]
+#[specta::specta]
+pub async fn rate_song(client: State<'_, Client>, uri: String, rating: u8) -> Result<(), String> {
+    ratings::rate(&client, &uri, rating).await
+}
+
+/// Rating, play and skip counts and last play time of a song.
+#[tauri::command]
+#[specta::specta]
+pub async fn song_stats(client: State<'_, Client>, uri: String) -> Result<SongStats, String> {
+    ratings::song_stats(&client, &uri).await
+}
+
+/// Rated songs matching `filter`, or in the whole library, best rated first.
+#[tauri::command]
+#[specta::specta]
+pub async fn top_rated(
+    client: State<'_, Client>,
+    library: State<'_, Library>,
+    filter: Option<Filter>,
+    limit: Option<usize>,
+) -> Result<Vec<RatedSong>, String> {
+    ratings::top_rated(
+        &client,
+        &library,
+        filter,
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+    )
+    .await
+}
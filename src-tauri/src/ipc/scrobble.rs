@@ -0,0 +1,86 @@
+use tauri::State;
+
+use crate::{
+    credentials::{CredentialStore, Credentials, LastFmSession},
+    scrobble::{self, ScrobbleStatus, Scrobbler},
+    settings::SettingsStore,
+};
+
+#[tauri::command]
+#[specta::specta]
+pub fn scrobble_status(
+    scrobbler: State<'_, Scrobbler>,
+    credentials: State<'_, CredentialStore>,
+) -> ScrobbleStatus {
+    scrobbler.status(&credentials.get())
+}
+
+/// Submit queued scrobbles now instead of waiting for the next retry.
+#[tauri::command]
+#[specta::specta]
+pub fn scrobble_flush(scrobbler: State<'_, Scrobbler>) {
+    scrobbler.flush();
+}
+
+/// Save the changed credentials and retry the queue with them.
+fn save_credentials(
+    scrobbler: &Scrobbler,
+    credentials: &CredentialStore,
+    f: impl FnOnce(&mut Credentials),
+) -> Result<ScrobbleStatus, String> {
+    credentials.update(f)?;
+    scrobbler.flush();
+    Ok(scrobbler.status(&credentials.get()))
+}
+
+/// Log in to the configured Last.fm compatible API, keeping the session key in the backend.
+///
+/// The password is only sent to the API and never stored.
+#[tauri::command]
+#[specta::specta]
+pub async fn lastfm_login(
+    store: State<'_, SettingsStore>,
+    scrobbler: State<'_, Scrobbler>,
+    credentials: State<'_, CredentialStore>,
+    username: String,
+    password: String,
+) -> Result<ScrobbleStatus, String> {
+    let settings = store.get().scrobbling.lastfm;
+    let secret = credentials.get().lastfm_secret;
+    let key = scrobble::lastfm_login(&settings, &secret, &username, &password).await?;
+    save_credentials(&scrobbler, &credentials, |c| {
+        c.lastfm_session = Some(LastFmSession { username, key })
+    })
+}
+
+/// Forget the Last.fm session.
+#[tauri::command]
+#[specta::specta]
+pub fn lastfm_logout(
+    scrobbler: State<'_, Scrobbler>,
+    credentials: State<'_, CredentialStore>,
+) -> Result<ScrobbleStatus, String> {
+    save_credentials(&scrobbler, &credentials, |c| c.lastfm_session = None)
+}
+
+/// Set the shared secret of the Last.fm API account. It can't be read back.
+#[tauri::command]
+#[specta::specta]
+pub fn set_lastfm_secret(
+    scrobbler: State<'_, Scrobbler>,
+    credentials: State<'_, CredentialStore>,
+    secret: String,
+) -> Result<ScrobbleStatus, String> {
+    save_credentials(&scrobbler, &credentials, |c| c.lastfm_secret = secret)
+}
+
+/// Set the ListenBrainz user token, or clear it with an empty one. It can't be read back.
+#[tauri::command]
+#[specta::specta]
+pub fn set_listenbrainz_token(
+    scrobbler: State<'_, Scrobbler>,
+    credentials: State<'_, CredentialStore>,
+    token: String,
+) -> Result<ScrobbleStatus, String> {
+    save_credentials(&scrobbler, &credentials, |c| c.listenbrainz_token = token)
+}
@@ -0,0 +1,59 @@
+use tauri::State;
+
+use crate::{
+    credentials::CredentialStore,
+    settings::{Settings, SettingsStore},
+};
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_settings(
+    store: State<'_, SettingsStore>,
+    credentials: State<'_, CredentialStore>,
+) -> Settings {
+    store.get().with_password_set(&credentials.get())
+}
+
+/// Replace the settings, returning them as saved.
+///
+/// Listeners of `settings-changed` are notified once the change is applied.
+#[tauri::command]
+#[specta::specta]
+pub fn update_settings(
+    store: State<'_, SettingsStore>,
+    credentials: State<'_, CredentialStore>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    Ok(store
+        .update(settings)?
+        .with_password_set(&credentials.get()))
+}
+
+/// Set the MPD password of the server profile at `server`, or clear it with `None`. It can't be
+/// read back.
+///
+/// Passwords belong to the server address and take effect on the next start.
+#[tauri::command]
+#[specta::specta]
+pub fn set_server_password(
+    store: State<'_, SettingsStore>,
+    credentials: State<'_, CredentialStore>,
+    server: usize,
+    password: Option<String>,
+) -> Result<Settings, String> {
+    let settings = store.get();
+    let address = settings
+        .servers
+        .get(server)
+        .ok_or_else(|| format!("no server profile {}", server))?
+        .address();
+    credentials.update(|c| match password.filter(|p| !p.is_empty()) {
+        Some(password) => {
+            c.mpd_passwords.insert(address, password);
+        }
+        None => {
+            c.mpd_passwords.remove(&address);
+        }
+    })?;
+    Ok(settings.with_password_set(&credentials.get()))
+}
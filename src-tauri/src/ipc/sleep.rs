@@ -0,0 +1,27 @@
+use tauri::{AppHandle, State};
+
+use crate::sleep::{self, SleepTimer, SleepTimerConfig, SleepTimerStatus};
+
+/// The armed sleep timer, if any.
+#[tauri::command]
+#[specta::specta]
+pub fn sleep_timer_status(timer: State<'_, SleepTimer>) -> Option<SleepTimerStatus> {
+    timer.status()
+}
+
+/// Arm the sleep timer, replacing the one already armed.
+#[tauri::command]
+#[specta::specta]
+pub async fn sleep_timer_start(
+    handle: AppHandle,
+    config: SleepTimerConfig,
+) -> Result<SleepTimerStatus, String> {
+    sleep::start(&handle, config).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn sleep_timer_cancel(handle: AppHandle) -> Result<(), String> {
+    sleep::cancel(&handle).await;
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use tauri::State;
+
+use crate::{reflection::response::UpdateJob, update::UpdateTracker};
+
+#[tauri::command]
+#[specta::specta]
+pub async fn wait_update(
+    tracker: State<'_, UpdateTracker>,
+    job: Option<u64>,
+) -> Result<Option<UpdateJob>, String> {
+    Ok(tracker.wait(job).await)
+}
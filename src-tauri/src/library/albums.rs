@@ -0,0 +1,400 @@
+//! Grouping of library songs into albums and artists.
+//!
+//! Songs are grouped by album artist and album. Songs without an album artist are grouped by
+//! album and directory instead, with `CD1`/`Disc 2` style subdirectories folded into their parent
+//! so multi-disc sets stay together; such a group becomes a compilation if its tracks have
+//! different artists.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use crate::reflection::{
+    response::{Album, Artist, Song, TVal},
+    tag::Tag,
+    Reflect,
+};
+
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+fn tag(song: &Song, tag: Tag) -> Option<&str> {
+    song.tags
+        .get(&tag.to_string())
+        .and_then(|values| values.first())
+        .map(String::as_str)
+        .filter(|v| !v.is_empty())
+}
+
+fn tags(song: &Song, tag: Tag) -> &[String] {
+    song.tags
+        .get(&tag.to_string())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Leading number of a `Track`/`Disc` value such as `3` or `3/12`.
+fn number(value: Option<&str>) -> u32 {
+    value
+        .and_then(|v| v.split('/').next())
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn is_disc_dir(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["cd", "disc", "disk"].iter().any(|prefix| {
+        name.strip_prefix(prefix).is_some_and(|rest| {
+            let rest = rest.trim_start_matches([' ', '_', '-']);
+            !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())
+        })
+    })
+}
+
+/// Directory of a song, without a trailing disc subdirectory.
+fn album_dir(url: &str) -> &str {
+    let dir = url.rsplit_once('/').map_or("", |(dir, _)| dir);
+    match dir.rsplit_once('/') {
+        Some((parent, last)) if is_disc_dir(last) => parent,
+        None if is_disc_dir(dir) => "",
+        _ => dir,
+    }
+}
+
+fn to_duration(t: &TVal) -> Duration {
+    Duration::new(t.secs, t.nanos)
+}
+
+fn sort_key(value: &str) -> String {
+    value.to_lowercase()
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum GroupKey {
+    AlbumArtist(String, String),
+    Directory(String, String),
+}
+
+fn group_key(song: &Song) -> Option<GroupKey> {
+    let album = tag(song, Tag::Album)?.to_string();
+    Some(match tag(song, Tag::AlbumArtist) {
+        Some(artist) => GroupKey::AlbumArtist(artist.to_string(), album),
+        None => GroupKey::Directory(album_dir(&song.url).to_string(), album),
+    })
+}
+
+fn build_album(key: GroupKey, mut tracks: Vec<Song>) -> Album {
+    tracks.sort_by(|a, b| {
+        let disc = |s: &Song| number(tag(s, Tag::Disc));
+        let track = |s: &Song| number(tag(s, Tag::Track));
+        (disc(a), track(a), &a.url).cmp(&(disc(b), track(b), &b.url))
+    });
+    let id = match &key {
+        GroupKey::AlbumArtist(artist, title) => format!("album:{}\u{1f}{}", artist, title),
+        GroupKey::Directory(dir, title) => format!("dir:{}\u{1f}{}", dir, title),
+    };
+    let (artist, title) = match key {
+        GroupKey::AlbumArtist(artist, title) => (artist, title),
+        GroupKey::Directory(_, title) => {
+            let first = tracks.first().and_then(|s| tag(s, Tag::Artist));
+            let single = tracks.iter().all(|s| tag(s, Tag::Artist) == first);
+            let artist = match first {
+                Some(artist) if single => artist.to_string(),
+                _ => VARIOUS_ARTISTS.to_string(),
+            };
+            (artist, title)
+        }
+    };
+    let compilation = artist.eq_ignore_ascii_case(VARIOUS_ARTISTS);
+    let artist_sort = tracks
+        .iter()
+        .find_map(|s| tag(s, Tag::AlbumArtistSort))
+        .filter(|_| !compilation)
+        .unwrap_or(&artist)
+        .to_string();
+    let title_sort = tracks
+        .iter()
+        .find_map(|s| tag(s, Tag::AlbumSort))
+        .unwrap_or(&title)
+        .to_string();
+    let date = tracks.iter().filter_map(|s| tag(s, Tag::Date)).min();
+    let discs = tracks
+        .iter()
+        .map(|s| number(tag(s, Tag::Disc)))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let duration: Duration = tracks
+        .iter()
+        .filter_map(|s| s.duration.as_ref())
+        .map(to_duration)
+        .sum();
+    let mut genres: Vec<String> = Vec::new();
+    for genre in tracks.iter().flat_map(|s| tags(s, Tag::Genre)) {
+        if !genres.contains(genre) {
+            genres.push(genre.clone());
+        }
+    }
+    Album {
+        id,
+        date: date.map(String::from),
+        cover_key: tracks.first().map(|s| s.url.clone()).unwrap_or_default(),
+        artist,
+        artist_sort,
+        title,
+        title_sort,
+        compilation,
+        discs,
+        duration: duration.reflect(),
+        genres,
+        tracks,
+    }
+}
+
+/// Group `songs` into albums, ordered by artist, date and title.
+///
+/// Songs without an `Album` tag don't belong to any album.
+pub fn group_albums(songs: &[Song]) -> Vec<Album> {
+    let mut groups: BTreeMap<GroupKey, Vec<Song>> = BTreeMap::new();
+    for song in songs {
+        if let Some(key) = group_key(song) {
+            groups.entry(key).or_default().push(song.clone());
+        }
+    }
+    let mut albums: Vec<Album> = groups
+        .into_iter()
+        .map(|(key, tracks)| build_album(key, tracks))
+        .collect();
+    albums.sort_by(|a, b| {
+        (sort_key(&a.artist_sort), &a.date, sort_key(&a.title_sort)).cmp(&(
+            sort_key(&b.artist_sort),
+            &b.date,
+            sort_key(&b.title_sort),
+        ))
+    });
+    albums
+}
+
+fn artist_entry<'a>(artists: &'a mut HashMap<String, Artist>, name: &str) -> &'a mut Artist {
+    artists.entry(name.to_string()).or_insert_with(|| Artist {
+        name: name.to_string(),
+        sort_name: name.to_string(),
+        albums: Vec::new(),
+        song_count: 0,
+    })
+}
+
+/// Collect the artists of `songs` with the albums they appear on, ordered by sort name.
+///
+/// An artist is anyone named in `Artist` or `AlbumArtist`; `song_count` only counts songs
+/// where they are a track artist.
+pub fn group_artists(songs: &[Song], albums: &[Album]) -> Vec<Artist> {
+    let mut artists: HashMap<String, Artist> = HashMap::new();
+    for song in songs {
+        let names = tags(song, Tag::Artist);
+        let sort_names = tags(song, Tag::ArtistSort);
+        for (i, name) in names.iter().enumerate() {
+            let artist = artist_entry(&mut artists, name);
+            artist.song_count += 1;
+            if let Some(sort_name) = sort_names.get(i) {
+                artist.sort_name = sort_name.clone();
+            }
+        }
+    }
+    for album in albums {
+        let mut names: Vec<&str> = Vec::new();
+        if !album.compilation {
+            names.push(&album.artist);
+        }
+        names.extend(
+            album
+                .tracks
+                .iter()
+                .flat_map(|s| tags(s, Tag::Artist))
+                .map(String::as_str),
+        );
+        names.sort_unstable();
+        names.dedup();
+        for name in names {
+            let artist = artist_entry(&mut artists, name);
+            if album.artist == name && album.artist_sort != name {
+                artist.sort_name = album.artist_sort.clone();
+            }
+            artist.albums.push(album.id.clone());
+        }
+    }
+    let mut artists: Vec<Artist> = artists.into_values().collect();
+    artists.sort_by_key(|a| (sort_key(&a.sort_name), a.name.clone()));
+    artists
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(url: &str, tags: &[(Tag, &str)]) -> Song {
+        let mut song = Song {
+            url: url.to_string(),
+            duration: Some(TVal { secs: 60, nanos: 0 }),
+            tags: HashMap::new(),
+            format: None,
+            last_modified: None,
+        };
+        for (tag, value) in tags {
+            song.tags
+                .entry(tag.to_string())
+                .or_default()
+                .push(value.to_string());
+        }
+        song
+    }
+
+    fn urls(album: &Album) -> Vec<&str> {
+        album.tracks.iter().map(|s| s.url.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_leading_numbers() {
+        assert_eq!(number(Some("3/12")), 3);
+        assert_eq!(number(Some(" 7 ")), 7);
+        assert_eq!(number(Some("A1")), 0);
+        assert_eq!(number(None), 0);
+    }
+
+    #[test]
+    fn folds_disc_directories() {
+        assert_eq!(album_dir("Artist/Album/CD1/01.flac"), "Artist/Album");
+        assert_eq!(album_dir("Artist/Album/Disc 2/01.flac"), "Artist/Album");
+        assert_eq!(album_dir("Artist/Album/disk_3/01.flac"), "Artist/Album");
+        assert_eq!(album_dir("CD1/01.flac"), "");
+        assert_eq!(album_dir("Artist/CDs/01.flac"), "Artist/CDs");
+        assert_eq!(album_dir("01.flac"), "");
+    }
+
+    #[test]
+    fn keeps_multi_disc_sets_together() {
+        let tags = |disc, track| {
+            [
+                (Tag::Artist, "Artist"),
+                (Tag::Album, "Album"),
+                (Tag::Disc, disc),
+                (Tag::Track, track),
+            ]
+        };
+        let songs = [
+            song("Artist/Album/CD2/01.flac", &tags("2/2", "1")),
+            song("Artist/Album/CD1/02.flac", &tags("1/2", "2")),
+            song("Artist/Album/CD1/01.flac", &tags("1/2", "1")),
+            song("Artist/Other/01.flac", &tags("1", "1")),
+        ];
+        let albums = group_albums(&songs);
+        assert_eq!(albums.len(), 2);
+        let album = &albums[0];
+        assert_eq!(album.id, "dir:Artist/Album\u{1f}Album");
+        assert_eq!((album.artist.as_str(), album.discs), ("Artist", 2));
+        assert!(!album.compilation);
+        assert_eq!(
+            urls(album),
+            [
+                "Artist/Album/CD1/01.flac",
+                "Artist/Album/CD1/02.flac",
+                "Artist/Album/CD2/01.flac"
+            ]
+        );
+        assert_eq!(album.duration.secs, 180);
+        assert_eq!(album.cover_key, "Artist/Album/CD1/01.flac");
+    }
+
+    #[test]
+    fn groups_compilations_by_album_artist() {
+        let track = |artist, track| {
+            [
+                (Tag::Artist, artist),
+                (Tag::AlbumArtist, VARIOUS_ARTISTS),
+                (Tag::AlbumArtistSort, "Zzz"),
+                (Tag::Album, "Hits"),
+                (Tag::Track, track),
+            ]
+        };
+        // Spread over directories, but held together by the album artist.
+        let songs = [
+            song("A/01.mp3", &track("A", "1")),
+            song("B/02.mp3", &track("B", "2")),
+            // No album artist: one album per directory.
+            song("Mix/01.mp3", &[(Tag::Artist, "C"), (Tag::Album, "Mix")]),
+            song("Mix/02.mp3", &[(Tag::Artist, "D"), (Tag::Album, "Mix")]),
+            // No album at all.
+            song("Loose/01.mp3", &[(Tag::Artist, "E")]),
+        ];
+        let albums = group_albums(&songs);
+        assert_eq!(albums.len(), 2);
+        for album in &albums {
+            assert!(album.compilation, "{}", album.id);
+            assert_eq!(album.artist, VARIOUS_ARTISTS);
+            // Sort tags don't move compilations away from each other.
+            assert_eq!(album.artist_sort, VARIOUS_ARTISTS);
+        }
+        assert_eq!(albums[0].id, "album:Various Artists\u{1f}Hits");
+        assert_eq!(urls(&albums[0]), ["A/01.mp3", "B/02.mp3"]);
+        assert_eq!(albums[1].id, "dir:Mix\u{1f}Mix");
+
+        // Compilations are listed under their track artists only.
+        let artists = group_artists(&songs, &albums);
+        let names: Vec<&str> = artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "C", "D", "E"]);
+        assert_eq!(artists[0].albums, [albums[0].id.clone()]);
+        assert!(artists[4].albums.is_empty());
+    }
+
+    #[test]
+    fn orders_by_sort_tags_then_date() {
+        let songs = [
+            song(
+                "Beatles/Abbey/01.flac",
+                &[
+                    (Tag::AlbumArtist, "The Beatles"),
+                    (Tag::AlbumArtistSort, "Beatles, The"),
+                    (Tag::Album, "Abbey Road"),
+                    (Tag::Date, "1969"),
+                ],
+            ),
+            song(
+                "Beatles/White/01.flac",
+                &[
+                    (Tag::AlbumArtist, "The Beatles"),
+                    (Tag::AlbumArtistSort, "Beatles, The"),
+                    (Tag::Album, "The Beatles"),
+                    (Tag::AlbumSort, "Beatles, The"),
+                    (Tag::Date, "1968"),
+                ],
+            ),
+            song(
+                "Blur/01.flac",
+                &[(Tag::AlbumArtist, "blur"), (Tag::Album, "Blur")],
+            ),
+            song(
+                "Blur/02.flac",
+                &[(Tag::AlbumArtist, "blur"), (Tag::Album, "Aa")],
+            ),
+        ];
+        let albums = group_albums(&songs);
+        let titles: Vec<&str> = albums.iter().map(|a| a.title.as_str()).collect();
+        // Without sort tags The Beatles would come after blur, and dates beat titles.
+        assert_eq!(titles, ["The Beatles", "Abbey Road", "Aa", "Blur"]);
+        assert_eq!(albums[0].artist_sort, "Beatles, The");
+        assert_eq!(albums[0].title_sort, "Beatles, The");
+        assert_eq!(albums[2].artist_sort, "blur");
+
+        let songs = [song(
+            "x.flac",
+            &[
+                (Tag::Artist, "The Beatles"),
+                (Tag::ArtistSort, "Beatles, The"),
+                (Tag::Album, "Single"),
+            ],
+        )];
+        let artists = group_artists(&songs, &group_albums(&songs));
+        assert_eq!(artists[0].sort_name, "Beatles, The");
+        assert_eq!(artists[0].song_count, 1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Typo-tolerant matching of query words against library words.
+
+/// Split text into lowercase alphanumeric words.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+/// Typos tolerated in a query word of `len` characters.
+fn max_typos(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Optimal string alignment distance (Levenshtein plus transpositions), `None` above `max`.
+fn bounded_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev2: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(prev2[j - 2] + 1);
+            }
+        }
+        if row.iter().min().is_some_and(|&m| m > max) {
+            return None;
+        }
+        prev2 = std::mem::replace(&mut prev, row);
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+/// How well the query word `query` matches `word`, from 0 (no match) to 1 (exact).
+///
+/// In order of preference: exact, prefix (the word still being typed), substring, and a match
+/// within a few typos of the whole word or of its prefix.
+pub fn word_score(query: &[char], word: &str) -> f32 {
+    let word: Vec<char> = word.chars().collect();
+    if word == query {
+        return 1.0;
+    }
+    if word.starts_with(query) {
+        return 0.6 + 0.3 * query.len() as f32 / word.len() as f32;
+    }
+    if query.len() >= 3 && word.windows(query.len()).any(|w| w == query) {
+        return 0.5;
+    }
+    let max = max_typos(query.len());
+    if max == 0 {
+        return 0.0;
+    }
+    if let Some(d) = bounded_distance(query, &word, max) {
+        return 0.6 - 0.15 * d as f32;
+    }
+    if word.len() > query.len() {
+        if let Some(d) = bounded_distance(query, &word[..query.len()], max) {
+            return 0.45 - 0.15 * d as f32;
+        }
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    fn distance(a: &str, b: &str, max: usize) -> Option<usize> {
+        bounded_distance(&chars(a), &chars(b), max)
+    }
+
+    #[test]
+    fn tokenize_splits_on_punctuation() {
+        let words: Vec<String> = tokenize("AC/DC - Back in Black (Remaster)").collect();
+        assert_eq!(words, ["ac", "dc", "back", "in", "black", "remaster"]);
+        assert_eq!(tokenize("Björk").collect::<Vec<_>>(), ["björk"]);
+    }
+
+    #[test]
+    fn distance_counts_edits() {
+        assert_eq!(distance("kitten", "kitten", 2), Some(0));
+        assert_eq!(distance("kitten", "sitten", 2), Some(1));
+        assert_eq!(distance("kitten", "sittin", 2), Some(2));
+        assert_eq!(distance("kitten", "sitting", 2), None);
+        assert_eq!(distance("", "abc", 3), Some(3));
+    }
+
+    #[test]
+    fn distance_counts_transposition_once() {
+        assert_eq!(distance("beatles", "baetles", 1), Some(1));
+        // OSA doesn't edit a substring twice, so this isn't 2 like in Damerau-Levenshtein.
+        assert_eq!(distance("ca", "abc", 3), Some(3));
+    }
+
+    #[test]
+    fn distance_gives_up_above_max() {
+        assert_eq!(distance("a", "abcd", 2), None);
+        assert_eq!(distance("abcd", "wxyz", 3), None);
+    }
+
+    #[test]
+    fn scores_prefer_exact_then_prefix_then_typos() {
+        let query = chars("radio");
+        let exact = word_score(&query, "radio");
+        let prefix = word_score(&query, "radiohead");
+        let typo = word_score(&query, "radoi");
+        assert_eq!(exact, 1.0);
+        assert!(exact > prefix && prefix > typo && typo > 0.0);
+        assert_eq!(word_score(&chars("blur"), "mogwai"), 0.0);
+    }
+
+    #[test]
+    fn short_queries_need_exact_letters() {
+        assert_eq!(word_score(&chars("abc"), "abd"), 0.0);
+        assert!(word_score(&chars("abc"), "abcd") > 0.0);
+    }
+
+    #[test]
+    fn typo_in_prefix_being_typed() {
+        assert!(word_score(&chars("radoih"), "radiohead") > 0.0);
+    }
+}
@@ -0,0 +1,285 @@
+//! In-memory index of the whole MPD database for instant searching.
+//!
+//! The index is built from `listallinfo` when connecting and after every database change, and
+//! is persisted to the cache directory so it only has to be rebuilt when the database's
+//! `db_update` time no longer matches.
+
+pub mod albums;
+pub mod fuzzy;
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use mpd_client::{commands, Client};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::reflection::{
+    response::{Album, Artist, SearchHit, Song},
+    tag::Tag,
+    Reflect,
+};
+
+use albums::{group_albums, group_artists};
+use fuzzy::{tokenize, word_score};
+
+pub const LIBRARY_FILE: &str = "library.json";
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+#[derive(Clone, Copy)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    Path,
+}
+
+impl Field {
+    fn weight(self) -> f32 {
+        match self {
+            Field::Title => 1.0,
+            Field::Artist => 0.9,
+            Field::Album => 0.8,
+            Field::Path => 0.5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Snapshot {
+    db_update: u64,
+    songs: Vec<Song>,
+}
+
+struct Index {
+    db_update: u64,
+    songs: Vec<Song>,
+    vocabulary: Vec<String>,
+    /// Per song, the words it contains with the most important field each appears in.
+    words: Vec<Vec<(u32, Field)>>,
+    /// Grouped on first use.
+    albums: OnceLock<Vec<Album>>,
+    artists: OnceLock<Vec<Artist>>,
+}
+
+fn tag_values<'a>(song: &'a Song, tags: &[Tag]) -> Vec<&'a String> {
+    tags.iter()
+        .filter_map(|tag| song.tags.get(&tag.to_string()))
+        .flatten()
+        .collect()
+}
+
+impl Index {
+    fn build(snapshot: Snapshot) -> Self {
+        let mut ids: HashMap<String, u32> = HashMap::new();
+        let mut vocabulary = Vec::new();
+        let words = snapshot
+            .songs
+            .iter()
+            .map(|song| {
+                let fields = [
+                    (Field::Title, tag_values(song, &[Tag::Title])),
+                    (
+                        Field::Artist,
+                        tag_values(song, &[Tag::Artist, Tag::AlbumArtist]),
+                    ),
+                    (Field::Album, tag_values(song, &[Tag::Album])),
+                    (Field::Path, vec![&song.url]),
+                ];
+                let mut song_words: Vec<(u32, Field)> = Vec::new();
+                for (field, values) in fields {
+                    for word in values.into_iter().flat_map(|v| tokenize(v)) {
+                        let id = *ids.entry(word).or_insert_with_key(|word| {
+                            vocabulary.push(word.clone());
+                            vocabulary.len() as u32 - 1
+                        });
+                        // Fields are visited by importance, so the first occurrence wins.
+                        if !song_words.iter().any(|&(w, _)| w == id) {
+                            song_words.push((id, field));
+                        }
+                    }
+                }
+                song_words
+            })
+            .collect();
+        Self {
+            db_update: snapshot.db_update,
+            songs: snapshot.songs,
+            vocabulary,
+            words,
+            albums: OnceLock::new(),
+            artists: OnceLock::new(),
+        }
+    }
+
+    fn albums(&self) -> &[Album] {
+        self.albums.get_or_init(|| group_albums(&self.songs))
+    }
+
+    fn artists(&self) -> &[Artist] {
+        self.artists
+            .get_or_init(|| group_artists(&self.songs, self.albums()))
+    }
+
+    /// Songs matching every word of `query`, best first.
+    ///
+    /// A song's score is the mean over query words of its best weighted word match.
+    fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let terms: Vec<Vec<char>> = tokenize(query).map(|t| t.chars().collect()).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        // Scoring the vocabulary once keeps the per-song work down to lookups.
+        let term_scores: Vec<Vec<f32>> = terms
+            .iter()
+            .map(|term| {
+                self.vocabulary
+                    .iter()
+                    .map(|word| word_score(term, word))
+                    .collect()
+            })
+            .collect();
+        let mut hits: Vec<(f32, usize)> = self
+            .words
+            .iter()
+            .enumerate()
+            .filter_map(|(i, words)| {
+                let mut total = 0.0;
+                for scores in &term_scores {
+                    let best = words
+                        .iter()
+                        .map(|&(w, field)| scores[w as usize] * field.weight())
+                        .fold(0.0, f32::max);
+                    if best <= 0.0 {
+                        return None;
+                    }
+                    total += best;
+                }
+                Some((total / terms.len() as f32, i))
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap_or(Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        });
+        hits.into_iter()
+            .take(limit)
+            .map(|(score, i)| SearchHit {
+                song: self.songs[i].clone(),
+                score,
+            })
+            .collect()
+    }
+}
+
+pub struct Library {
+    /// `None` if there's no cache directory, in which case the index isn't persisted.
+    file: Option<PathBuf>,
+    index: RwLock<Arc<Index>>,
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl Library {
+    /// Open the library, starting from the snapshot in `dir` if there is one.
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let file = dir.map(|dir| dir.join(LIBRARY_FILE));
+        let snapshot = file
+            .as_ref()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            file,
+            index: RwLock::new(Arc::new(Index::build(snapshot))),
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn current(&self) -> Arc<Index> {
+        self.index.read().unwrap().clone()
+    }
+
+    /// Rebuild the index if the database changed since it was built.
+    ///
+    /// Returns the number of indexed songs if it was rebuilt.
+    pub async fn refresh(&self, client: &Client) -> Result<Option<usize>, String> {
+        let _guard = self.refreshing.lock().await;
+        let stats = client
+            .command(commands::Stats)
+            .await
+            .map_err(|e| e.to_string())?;
+        let current = self.current();
+        if current.db_update == stats.db_last_update && !current.songs.is_empty() {
+            return Ok(None);
+        }
+        let songs = client
+            .command(commands::ListAllIn::root())
+            .await
+            .map_err(|e| e.to_string())?
+            .reflect();
+        let snapshot = Snapshot {
+            db_update: stats.db_last_update,
+            songs,
+        };
+        if let Some(file) = &self.file {
+            let saved = fs::create_dir_all(file.parent().unwrap_or(file))
+                .and_then(|_| fs::write(file, serde_json::to_vec(&snapshot)?));
+            if let Err(e) = saved {
+                log::error!("Failed to save library index to {}: {}", file.display(), e);
+            }
+        }
+        let index = Index::build(snapshot);
+        let count = index.songs.len();
+        *self.index.write().unwrap() = Arc::new(index);
+        Ok(Some(count))
+    }
+
+    /// Run `f` over every indexed song.
+    pub fn with_songs<R>(&self, f: impl FnOnce(&[Song]) -> R) -> R {
+        f(&self.current().songs)
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        self.current().search(query, limit)
+    }
+
+    /// Albums, optionally only those `artist` appears on.
+    pub fn albums(&self, artist: Option<&str>) -> Vec<Album> {
+        let index = self.current();
+        match artist {
+            Some(name) => {
+                let Some(artist) = index.artists().iter().find(|a| a.name == name) else {
+                    return Vec::new();
+                };
+                index
+                    .albums()
+                    .iter()
+                    .filter(|album| artist.albums.contains(&album.id))
+                    .cloned()
+                    .collect()
+            }
+            None => index.albums().to_vec(),
+        }
+    }
+
+    pub fn artists(&self) -> Vec<Artist> {
+        self.current().artists().to_vec()
+    }
+}
+
+/// Refresh the library index, emitting `library` with the song count if it was rebuilt.
+pub async fn refresh_library(handle: AppHandle) -> tauri::Result<()> {
+    let client = handle.state::<Client>();
+    match handle.state::<Library>().refresh(&client).await {
+        Ok(Some(count)) => handle.emit_all("library", count)?,
+        Ok(None) => {}
+        Err(e) => log::error!("Failed to index the library: {}", e),
+    }
+    Ok(())
+}
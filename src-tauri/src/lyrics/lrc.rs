@@ -0,0 +1,208 @@
+//! Parsing of LRC lyrics.
+//!
+//! Lines start with one or more `[mm:ss.xx]` time tags, each of which shows the text at that
+//! time, so repeated lines such as choruses are written once. `[offset:±ms]` shifts every time
+//! tag, a positive offset making the lyrics appear sooner. Other `[key:value]` tags are metadata
+//! and ignored, as are the `<mm:ss.xx>` word timings of enhanced LRC. Text without any time tags
+//! is kept as plain, unsynced lyrics.
+
+use std::time::Duration;
+
+/// A parsed line, `time` being `None` for unsynced lyrics.
+pub struct ParsedLine {
+    pub time: Option<Duration>,
+    pub text: String,
+}
+
+/// Parse `mm:ss`, `mm:ss.xx` or `mm:ss:xx`, with any number of fraction digits.
+fn parse_time(tag: &str) -> Option<Duration> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let (seconds, fraction) = match rest.find(['.', ':']) {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if minutes.is_empty() || seconds.is_empty() || !all_digits(minutes) || !all_digits(seconds) {
+        return None;
+    }
+    if !all_digits(fraction) {
+        return None;
+    }
+    let fraction = format!("0.{}", fraction).parse::<f64>().unwrap_or(0.0);
+    let seconds = minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds) + Duration::from_secs_f64(fraction))
+}
+
+/// Remove the `<mm:ss.xx>` word timings of enhanced LRC from `text`.
+fn strip_word_times(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        match rest[open..].find('>') {
+            Some(close) if parse_time(&rest[open + 1..open + close]).is_some() => {
+                out.push_str(&rest[..open]);
+                rest = &rest[open + close + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=open]);
+                rest = &rest[open + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.trim().to_string()
+}
+
+/// Parse LRC (or plain) lyrics.
+///
+/// If any line has a time tag the result only holds timed lines, sorted by time; otherwise it
+/// holds the plain text lines without leading and trailing blank ones.
+pub fn parse(raw: &str) -> Vec<ParsedLine> {
+    let mut offset_ms = 0i64;
+    let mut timed = Vec::new();
+    let mut plain = Vec::new();
+    for line in raw.trim_start_matches('\u{feff}').lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        let mut metadata = false;
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(close) = tag.find(']') else {
+                break;
+            };
+            let content = &tag[..close];
+            if let Some(time) = parse_time(content) {
+                times.push(time);
+            } else if let Some((key, value)) = content.split_once(':') {
+                if key.trim().eq_ignore_ascii_case("offset") {
+                    offset_ms = value.trim().parse().unwrap_or(offset_ms);
+                }
+                metadata = true;
+            } else {
+                break;
+            }
+            rest = tag[close + 1..].trim_start();
+        }
+        let text = strip_word_times(rest);
+        if !times.is_empty() {
+            timed.extend(times.into_iter().map(|time| (time, text.clone())));
+        } else if !metadata {
+            plain.push(text);
+        }
+    }
+
+    if timed.is_empty() {
+        let start = plain
+            .iter()
+            .position(|l| !l.is_empty())
+            .unwrap_or(plain.len());
+        let end = plain
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(start, |i| i + 1);
+        return plain[start..end]
+            .iter()
+            .map(|text| ParsedLine {
+                time: None,
+                text: text.clone(),
+            })
+            .collect();
+    }
+    let shift = Duration::from_millis(offset_ms.unsigned_abs());
+    let mut lines: Vec<ParsedLine> = timed
+        .into_iter()
+        .map(|(time, text)| ParsedLine {
+            time: Some(if offset_ms >= 0 {
+                time.saturating_sub(shift)
+            } else {
+                time + shift
+            }),
+            text,
+        })
+        .collect();
+    lines.sort_by_key(|line| line.time);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    fn lines(raw: &str) -> Vec<(Option<Duration>, String)> {
+        parse(raw).into_iter().map(|l| (l.time, l.text)).collect()
+    }
+
+    #[test]
+    fn parses_time_tags() {
+        assert_eq!(parse_time("01:02"), ms(62_000));
+        assert_eq!(parse_time("01:02.5"), ms(62_500));
+        assert_eq!(parse_time("01:02.50"), ms(62_500));
+        assert_eq!(parse_time("01:02:05"), ms(62_050));
+        assert_eq!(parse_time("00:00.123"), ms(123));
+        for tag in ["", "1", ":02", "01:", "ar:Artist", "01:0x", "01:02.x"] {
+            assert_eq!(parse_time(tag), None, "{}", tag);
+        }
+    }
+
+    #[test]
+    fn repeats_lines_with_several_tags_in_order() {
+        let raw = "[ar:Artist]\n[00:10.00][00:30.00]Chorus\n[00:20.00] Verse \n";
+        assert_eq!(
+            lines(raw),
+            [
+                (ms(10_000), "Chorus".to_string()),
+                (ms(20_000), "Verse".to_string()),
+                (ms(30_000), "Chorus".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_the_offset() {
+        let raw = "[offset:+500]\n[00:00.20]First\n[00:01.00]Second";
+        assert_eq!(
+            lines(raw),
+            [
+                (ms(0), "First".to_string()),
+                (ms(500), "Second".to_string())
+            ]
+        );
+        assert_eq!(
+            lines("[offset:-250]\n[00:01.00]Late"),
+            [(ms(1_250), "Late".to_string())]
+        );
+    }
+
+    #[test]
+    fn strips_enhanced_word_times() {
+        assert_eq!(
+            lines("[00:01.00]<00:01.00>One <00:01.50>two <b>"),
+            [(ms(1_000), "One two <b>".to_string())]
+        );
+    }
+
+    #[test]
+    fn keeps_plain_lyrics_without_time_tags() {
+        let raw = "\u{feff}\n[ti:Title]\nFirst line\n\nSecond [line]\n\n";
+        assert_eq!(
+            lines(raw),
+            [
+                (None, "First line".to_string()),
+                (None, String::new()),
+                (None, "Second [line]".to_string()),
+            ]
+        );
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn ignores_untimed_lines_in_synced_lyrics() {
+        assert_eq!(
+            lines("Credits\n[00:05.00]Sung"),
+            [(ms(5_000), "Sung".to_string())]
+        );
+    }
+}
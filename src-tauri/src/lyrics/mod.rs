@@ -0,0 +1,248 @@
+//! Lyrics of songs, and the line of the current song being sung.
+//!
+//! Lyrics come from a `.lrc` file with the same name as the song under the music directory, or
+//! failing that from the song's lyrics tags (ID3 `USLT`, Vorbis `LYRICS` and the like) read with
+//! `readcomments`. Both may be synced LRC or plain text.
+//!
+//! On every `player` event the current song's lyrics are loaded if it changed, and the background
+//! task follows the [playback clock](crate::clock) to emit `lyrics-line` whenever another line
+//! becomes active.
+
+mod lrc;
+
+use std::{fs, path::Path, sync::Mutex, time::Duration};
+
+use mpd_client::{responses::SongInQueue, Client};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    art::CoverResolver,
+    clock::PlaybackClock,
+    extension::ReadComments,
+    reflection::{response::TVal, Reflect},
+    scope::{is_within, join_within},
+};
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LyricsSource {
+    /// A `.lrc` file next to the song.
+    File,
+    /// A tag of the song file.
+    Tag,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct LyricLine {
+    /// When the line starts; `None` for unsynced lyrics.
+    pub time: Option<TVal>,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Lyrics {
+    pub uri: String,
+    pub source: LyricsSource,
+    /// Whether the lines have times.
+    pub synced: bool,
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    fn parse(uri: &str, source: LyricsSource, raw: &str) -> Option<Self> {
+        let lines: Vec<LyricLine> = lrc::parse(raw)
+            .into_iter()
+            .map(|line| LyricLine {
+                time: line.time.map(Reflect::reflect),
+                text: line.text,
+            })
+            .collect();
+        (!lines.is_empty()).then(|| Self {
+            uri: uri.to_string(),
+            source,
+            synced: lines.iter().any(|line| line.time.is_some()),
+            lines,
+        })
+    }
+}
+
+/// Lowercase names of `readcomments` keys holding lyrics, as written by common taggers.
+fn is_lyrics_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    matches!(
+        key.as_str(),
+        "lyrics" | "unsyncedlyrics" | "syncedlyrics" | "uslt" | "sylt"
+    ) || key.starts_with("lyrics-")
+        || key.starts_with("uslt:")
+}
+
+/// Read `<basename>.lrc` next to the song at `uri`, only ever reading inside the music directory.
+async fn lrc_file(client: &Client, resolver: &CoverResolver, uri: &str) -> Option<String> {
+    let music_dir = resolver.music_directory(client).await?;
+    let relative = Path::new(uri).with_extension("lrc");
+    let path = join_within(&music_dir, relative.to_str()?)?;
+    if !is_within(&path, &music_dir) {
+        return None;
+    }
+    fs::read(path)
+        .ok()
+        .map(|raw| String::from_utf8_lossy(&raw).into_owned())
+}
+
+/// Find the lyrics of the song at `uri`, preferring synced ones among its tags.
+pub async fn load(client: &Client, resolver: &CoverResolver, uri: &str) -> Option<Lyrics> {
+    if uri.contains("://") {
+        return None;
+    }
+    if let Some(raw) = lrc_file(client, resolver, uri).await {
+        if let Some(lyrics) = Lyrics::parse(uri, LyricsSource::File, &raw) {
+            return Some(lyrics);
+        }
+    }
+    let comments = match client.command(ReadComments(uri)).await {
+        Ok(comments) => comments,
+        Err(e) => {
+            log::warn!("Failed to read the tags of '{}': {}", uri, e);
+            return None;
+        }
+    };
+    let mut found: Vec<Lyrics> = comments
+        .into_iter()
+        .filter(|(key, _)| is_lyrics_key(key))
+        .filter_map(|(_, value)| Lyrics::parse(uri, LyricsSource::Tag, &value))
+        .collect();
+    let synced = found.iter().position(|lyrics| lyrics.synced).unwrap_or(0);
+    (!found.is_empty()).then(|| found.swap_remove(synced))
+}
+
+/// The active line of the current song's lyrics, emitted as `lyrics-line`.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct ActiveLyric {
+    pub uri: String,
+    /// Index into [`Lyrics::lines`], `None` before the first line.
+    pub index: Option<usize>,
+    pub text: Option<String>,
+    pub elapsed: TVal,
+}
+
+#[derive(Default)]
+struct Inner {
+    uri: Option<String>,
+    lyrics: Option<Lyrics>,
+    /// Start times of the lines of synced lyrics.
+    times: Vec<Duration>,
+    active: Option<usize>,
+    /// Emit the active line even if it didn't change, e.g. after seeking.
+    dirty: bool,
+}
+
+/// Lyrics of the current song and where playback is in them.
+#[derive(Default)]
+pub struct LyricsTracker {
+    inner: Mutex<Inner>,
+    wake: Notify,
+}
+
+impl LyricsTracker {
+    pub fn current(&self) -> Option<Lyrics> {
+        self.inner.lock().unwrap().lyrics.clone()
+    }
+
+    /// Have the active line emitted again.
+    pub fn resend(&self) {
+        self.inner.lock().unwrap().dirty = true;
+        self.wake.notify_one();
+    }
+
+    /// Have the active line checked against the playback clock, e.g. after it was resynced.
+    pub fn poke(&self) {
+        self.wake.notify_one();
+    }
+
+    fn set_lyrics(&self, uri: Option<String>, lyrics: Option<Lyrics>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.times = lyrics
+            .iter()
+            .filter(|lyrics| lyrics.synced)
+            .flat_map(|lyrics| &lyrics.lines)
+            .map(|line| line.time.clone().map_or(Duration::ZERO, Duration::from))
+            .collect();
+        inner.uri = uri;
+        inner.lyrics = lyrics;
+        inner.active = None;
+        inner.dirty = true;
+    }
+
+    /// The active line at `elapsed` if it has to be emitted, and how long until the next one
+    /// starts while `playing`.
+    fn step(&self, elapsed: Duration, playing: bool) -> (Option<ActiveLyric>, Option<Duration>) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.times.is_empty() {
+            return (None, None);
+        }
+        let index = inner
+            .times
+            .partition_point(|&t| t <= elapsed)
+            .checked_sub(1);
+        let wait = inner
+            .times
+            .get(index.map_or(0, |i| i + 1))
+            .filter(|_| playing)
+            .map(|next| *next - elapsed);
+        if index == inner.active && !inner.dirty {
+            return (None, wait);
+        }
+        inner.active = index;
+        inner.dirty = false;
+        let active = ActiveLyric {
+            uri: inner.uri.clone().unwrap_or_default(),
+            index,
+            text: index.and_then(|i| {
+                let lyrics = inner.lyrics.as_ref()?;
+                Some(lyrics.lines.get(i)?.text.clone())
+            }),
+            elapsed: elapsed.reflect(),
+        };
+        (Some(active), wait)
+    }
+}
+
+/// Load the lyrics of the current song if it changed.
+pub async fn sync_lyrics(handle: &AppHandle, song: Option<&SongInQueue>) -> tauri::Result<()> {
+    let client = handle.state::<Client>();
+    let tracker = handle.state::<LyricsTracker>();
+    let uri = song.map(|song| song.song.url.clone());
+    if uri != tracker.inner.lock().unwrap().uri {
+        let lyrics = match &uri {
+            Some(uri) => load(&client, &handle.state::<CoverResolver>(), uri).await,
+            None => None,
+        };
+        tracker.set_lyrics(uri, lyrics.clone());
+        handle.emit_all("lyrics", lyrics)?;
+        tracker.poke();
+    }
+    Ok(())
+}
+
+/// Emit `lyrics-line` whenever the active line of synced lyrics changes.
+pub async fn lyrics_task(handle: AppHandle) {
+    let tracker = handle.state::<LyricsTracker>();
+    let clock = handle.state::<PlaybackClock>();
+    loop {
+        let (elapsed, playing) = clock.elapsed();
+        let (active, wait) = tracker.step(elapsed, playing);
+        if let Some(active) = active {
+            if let Err(e) = handle.emit_all("lyrics-line", active) {
+                log::error!("Failed to emit signal 'lyrics-line': {}", e);
+            }
+        }
+        match wait {
+            Some(wait) => {
+                let _ = tokio::time::timeout(wait, tracker.wake.notified()).await;
+            }
+            None => tracker.wake.notified().await,
+        }
+    }
+}
@@ -1,12 +1,50 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+mod alarm;
+mod art;
+mod autodj;
+mod clock;
+mod credentials;
+mod extension;
+mod history;
 mod ipc;
+mod library;
+mod lyrics;
 mod mpd;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod playlist;
+mod plays;
+mod ratings;
 mod reflection;
+mod scope;
+mod scrobble;
+mod settings;
+mod sleep;
+mod stickers;
+mod update;
 
+use alarm::{alarm_task, Alarms};
+use art::{
+    art_protocol,
+    cover::{CoverResolver, PLACEHOLDER_RESOURCE},
+    ArtCache, ART_SCHEME,
+};
+use autodj::{autodj_task, AutoDj};
+use clock::{clock_task, PlaybackClock};
+use credentials::CredentialStore;
+use history::History;
 use ipc::*;
+use library::{refresh_library, Library};
+use lyrics::{lyrics_task, LyricsTracker};
 use mpd::{event_handler, initialize_connection};
+use playlist::smart::SmartPlaylists;
+use plays::PlayTracker;
+use scrobble::{scrobble_task, Scrobbler};
+use settings::{apply_settings, watch_settings, SettingsStore};
+use sleep::{sleep_task, SleepTimer};
 use tauri::{async_runtime, Manager};
+use update::UpdateTracker;
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 
@@ -14,20 +52,59 @@ fn main() {
     tauri::Builder::default()
         .setup(|app| {
             let handle = app.handle();
-            let (client, events) =
-                async_runtime::block_on(initialize_connection("localhost:6600")).expect("Failed");
+            let credentials = CredentialStore::open(app.path_resolver().app_config_dir());
+            let store = SettingsStore::open(app.path_resolver().app_config_dir(), &credentials);
+            let settings = store.get();
+            let (client, events) = async_runtime::block_on(initialize_connection(
+                &settings.active_server(),
+                &credentials.get(),
+            ))
+            .expect("Failed");
             app.manage(client);
+            app.manage(store);
+            app.manage(UpdateTracker::default());
+            app.manage(AutoDj::default());
+            app.manage(PlayTracker::default());
+            app.manage(PlaybackClock::default());
+            app.manage(LyricsTracker::default());
+            app.manage(SleepTimer::default());
+            app.manage(Alarms::open(app.path_resolver().app_config_dir()));
+            app.manage(History::open(app.path_resolver().app_data_dir()));
+            app.manage(Scrobbler::open(app.path_resolver().app_data_dir()));
+            app.manage(credentials);
+            let art_dir = app
+                .path_resolver()
+                .app_cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("art");
+            app.manage(ArtCache::open(art_dir, settings.art_cache_limit));
+            let placeholder = app
+                .path_resolver()
+                .resolve_resource(PLACEHOLDER_RESOURCE)
+                .unwrap_or_default();
+            app.manage(CoverResolver::new(placeholder));
+            app.manage(Library::open(app.path_resolver().app_cache_dir()));
+            app.manage(SmartPlaylists::open(app.path_resolver().app_config_dir()));
+            apply_settings(&handle, &settings);
+            async_runtime::spawn(watch_settings(handle.clone()));
+            async_runtime::spawn(refresh_library(handle.clone()));
+            async_runtime::spawn(autodj_task(handle.clone()));
+            async_runtime::spawn(scrobble_task(handle.clone()));
+            async_runtime::spawn(clock_task(handle.clone()));
+            async_runtime::spawn(lyrics_task(handle.clone()));
+            async_runtime::spawn(sleep_task(handle.clone()));
+            async_runtime::spawn(alarm_task(handle.clone()));
+            #[cfg(target_os = "linux")]
+            async_runtime::spawn(mpris::serve_mpris(handle.clone()));
             async_runtime::spawn(event_handler(handle, events));
             Ok(())
         })
+        .register_uri_scheme_protocol(ART_SCHEME, art_protocol)
         .invoke_handler(tauri::generate_handler![
-            // sync
-            get_cover_path,
             // mpd
             add,
+            batch,
             playlistadd,
-            albumart,
-            readpicture,
             playlistclear,
             clear,
             count,
@@ -43,17 +120,25 @@ fn main() {
             list,
             listallinfo,
             channels,
+            listmounts,
+            listneighbors,
             load,
             move_song,
+            mount,
             next,
             ping,
             play,
             previous,
             playlistinfo,
+            prio,
             playlistdelete,
+            playlistfind,
+            playlistmove,
+            playlistsearch,
             rename,
             rescan,
             save,
+            searchplaylist,
             seekcur,
             seek,
             consume,
@@ -66,13 +151,70 @@ fn main() {
             status,
             sticker_delete,
             sticker_find,
+            sticker_find_number,
             sticker_get,
+            sticker_get_number,
             sticker_list,
             sticker_set,
             stop,
             subscribe,
             unsubscribe,
+            unmount,
             update,
+            // library
+            albums,
+            artists,
+            quick_search,
+            // playlist
+            export_playlist,
+            import_playlist,
+            playlist_files,
+            playlistedit,
+            smart_playlists,
+            save_smart_playlist,
+            delete_smart_playlist,
+            preview_smart_playlist,
+            materialize_smart_playlist,
+            // ratings
+            rate_song,
+            song_stats,
+            top_rated,
+            // history
+            history,
+            export_history,
+            listening_report,
+            // clock
+            elapsed,
+            // lyrics
+            lyrics,
+            current_lyrics,
+            // scrobble
+            scrobble_status,
+            scrobble_flush,
+            lastfm_login,
+            lastfm_logout,
+            set_lastfm_secret,
+            set_listenbrainz_token,
+            // sleep timer
+            sleep_timer_status,
+            sleep_timer_start,
+            sleep_timer_cancel,
+            // alarms
+            alarms,
+            save_alarm,
+            delete_alarm,
+            next_alarm,
+            // autodj
+            autodj_status,
+            autodj_start,
+            autodj_stop,
+            autodj_configure,
+            // settings
+            get_settings,
+            update_settings,
+            set_server_password,
+            // update
+            wait_update,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -89,13 +231,10 @@ mod tests {
     #[test]
     fn export_type_bindings() {
         let list = collect_types![
-            // sync
-            get_cover_path,
             // mpd
             add,
+            batch,
             playlistadd,
-            albumart,
-            readpicture,
             playlistclear,
             clear,
             count,
@@ -111,17 +250,25 @@ mod tests {
             list,
             listallinfo,
             channels,
+            listmounts,
+            listneighbors,
             load,
             move_song,
+            mount,
             next,
             ping,
             play,
             previous,
             playlistinfo,
+            prio,
             playlistdelete,
+            playlistfind,
+            playlistmove,
+            playlistsearch,
             rename,
             rescan,
             save,
+            searchplaylist,
             seekcur,
             seek,
             consume,
@@ -134,13 +281,70 @@ mod tests {
             status,
             sticker_delete,
             sticker_find,
+            sticker_find_number,
             sticker_get,
+            sticker_get_number,
             sticker_list,
             sticker_set,
             stop,
             subscribe,
             unsubscribe,
+            unmount,
             update,
+            // library
+            albums,
+            artists,
+            quick_search,
+            // playlist
+            export_playlist,
+            import_playlist,
+            playlist_files,
+            playlistedit,
+            smart_playlists,
+            save_smart_playlist,
+            delete_smart_playlist,
+            preview_smart_playlist,
+            materialize_smart_playlist,
+            // ratings
+            rate_song,
+            song_stats,
+            top_rated,
+            // history
+            history,
+            export_history,
+            listening_report,
+            // clock
+            elapsed,
+            // lyrics
+            lyrics,
+            current_lyrics,
+            // scrobble
+            scrobble_status,
+            scrobble_flush,
+            lastfm_login,
+            lastfm_logout,
+            set_lastfm_secret,
+            set_listenbrainz_token,
+            // sleep timer
+            sleep_timer_status,
+            sleep_timer_start,
+            sleep_timer_cancel,
+            // alarms
+            alarms,
+            save_alarm,
+            delete_alarm,
+            next_alarm,
+            // autodj
+            autodj_status,
+            autodj_start,
+            autodj_stop,
+            autodj_configure,
+            // settings
+            get_settings,
+            update_settings,
+            set_server_password,
+            // update
+            wait_update,
         ]
         .unwrap();
         export_with_cfg(
@@ -1,14 +1,46 @@
 use mpd_client::{
-    client::{Connection, ConnectionEvent, ConnectionEvents, Subsystem},
-    protocol::MpdProtocolError,
+    client::{
+        CommandError, ConnectWithPasswordError, Connection, ConnectionEvent, ConnectionEvents,
+        Subsystem,
+    },
+    commands,
+    responses::{SongInQueue, Status},
     Client,
 };
-use tauri::{AppHandle, Manager};
+use tauri::{async_runtime, AppHandle, Manager};
 use tokio::net::TcpStream;
 
-pub async fn initialize_connection(addr: &str) -> Result<Connection, MpdProtocolError> {
-    let conn = TcpStream::connect(addr).await?;
-    Client::connect(conn).await
+use crate::{
+    art::{ArtCache, CoverResolver},
+    autodj::AutoDj,
+    clock::sync_clock,
+    credentials::Credentials,
+    library::refresh_library,
+    lyrics::sync_lyrics,
+    playlist::smart::refresh_smart_playlists,
+    plays::track_plays,
+    settings::ServerProfile,
+    sleep::SleepTimer,
+    update::sync_update_jobs,
+};
+
+pub async fn initialize_connection(
+    server: &ServerProfile,
+    credentials: &Credentials,
+) -> Result<Connection, ConnectWithPasswordError> {
+    let address = server.address();
+    let conn = TcpStream::connect(&address)
+        .await
+        .map_err(|e| ConnectWithPasswordError::ProtocolError(e.into()))?;
+    let password = credentials.mpd_passwords.get(&address);
+    Client::connect_with_password_opt(conn, password.map(String::as_str)).await
+}
+
+/// Fetch the status and the current song in one go.
+pub async fn player_state(client: &Client) -> Result<(Status, Option<SongInQueue>), CommandError> {
+    client
+        .command_list((commands::Status, commands::CurrentSong))
+        .await
 }
 
 pub async fn event_handler(handle: AppHandle, mut events: ConnectionEvents) -> tauri::Result<()> {
@@ -16,14 +48,62 @@ pub async fn event_handler(handle: AppHandle, mut events: ConnectionEvents) -> t
         match event {
             ConnectionEvent::SubsystemChange(ev) => match ev {
                 Subsystem::Queue => {
+                    handle.state::<AutoDj>().poke();
                     handle.emit_all("queue", ())?;
+                    #[cfg(target_os = "linux")]
+                    {
+                        crate::mpris::queue_changed(&handle);
+                        crate::mpris::sync_mpris(&handle).await;
+                    }
                 }
                 Subsystem::Options => {
                     handle.emit_all("status", ())?;
+                    #[cfg(target_os = "linux")]
+                    crate::mpris::sync_mpris(&handle).await;
+                }
+                Subsystem::Mixer => {
+                    #[cfg(target_os = "linux")]
+                    crate::mpris::sync_mpris(&handle).await;
                 }
                 Subsystem::Player => {
+                    handle.state::<AutoDj>().poke();
                     handle.emit_all("status", ())?;
                     handle.emit_all("currentsong", ())?;
+                    handle.state::<SleepTimer>().poke();
+                    // Fetched once for everything following the player.
+                    match player_state(&handle.state::<Client>()).await {
+                        Ok((status, song)) => {
+                            sync_clock(&handle, &status);
+                            track_plays(&handle, &status, song.clone()).await;
+                            sync_lyrics(&handle, song.as_ref()).await?;
+                            #[cfg(target_os = "linux")]
+                            crate::mpris::update_mpris(&handle, status, song).await;
+                        }
+                        Err(e) => log::error!("Failed to fetch the player state: {}", e),
+                    }
+                }
+                Subsystem::Database => {
+                    handle.state::<ArtCache>().invalidate();
+                    handle.state::<CoverResolver>().forget();
+                    async_runtime::spawn(refresh_library(handle.clone()));
+                    async_runtime::spawn(refresh_smart_playlists(handle.clone()));
+                    handle.emit_all("database", ())?;
+                    sync_update_jobs(&handle).await?;
+                }
+                Subsystem::Update => {
+                    sync_update_jobs(&handle).await?;
+                }
+                Subsystem::StoredPlaylist => {
+                    handle.emit_all("stored-playlist", ())?;
+                }
+                Subsystem::Sticker => {
+                    handle.emit_all("sticker", ())?;
+                }
+                Subsystem::Mount => {
+                    handle.emit_all("mount", ())?;
+                }
+                Subsystem::Neighbor => {
+                    handle.emit_all("neighbor", ())?;
                 }
                 _ => {}
             },
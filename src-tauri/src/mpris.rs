@@ -0,0 +1,938 @@
+//! MPRIS D-Bus interface, so media keys, desktop widgets and `playerctl` control the player.
+//!
+//! `org.mpris.MediaPlayer2`, `.Player` and `.TrackList` are served on the session bus at
+//! `/org/mpris/MediaPlayer2`. Property reads come from a snapshot of the player that
+//! [`sync_mpris`] refreshes on `event_handler` events, which also emits `PropertiesChanged` and
+//! `Seeked`; the position comes from the [playback clock](crate::clock). The track list is a
+//! window of at most [`MAX_TRACKS`] songs from the current one on, fetched by a background task
+//! woken with [`queue_changed`], which emits `TrackListReplaced`. Method calls and property
+//! writes go to the managed [`Client`].
+//!
+//! Without a session bus the interface is simply not registered. Building on Linux needs the
+//! libdbus development files, e.g. `libdbus-1-dev` on Debian and Ubuntu.
+
+use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    channel::{MatchingReceiver, Sender},
+    message::{MatchRule, SignalArgs},
+    nonblock::{stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged, SyncConnection},
+    strings::Path,
+    Message, MethodErr,
+};
+use dbus_crossroads::{Crossroads, IfaceBuilder};
+use mpd_client::{
+    commands::{self, Command, SeekMode, SingleMode, SongId},
+    responses::{PlayState, SongInQueue, Status},
+    tag::Tag,
+    Client,
+};
+use tauri::{async_runtime, AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    art::{cover::CoverResolver, ArtCache},
+    clock::PlaybackClock,
+    extension::QueueRange,
+    mpd::player_state,
+    playlist,
+    scope::join_within,
+};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.rmpd";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_IFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_IFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TRACKLIST_IFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+/// Track ids are the queue ids of songs under this path.
+const TRACK_PREFIX: &str = "/org/mpris/MediaPlayer2/rmpd/track/";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// Position jumps larger than this between syncs are reported as `Seeked`.
+const SEEK_TOLERANCE: Duration = Duration::from_secs(1);
+/// Most songs in `Tracks`, as queues can hold many thousands.
+const MAX_TRACKS: usize = 100;
+
+#[derive(Default)]
+struct Snapshot {
+    status: Option<Status>,
+    song: Option<SongInQueue>,
+    art_url: Option<String>,
+    /// Local copy of the music directory, for `xesam:url`.
+    music_dir: Option<PathBuf>,
+    /// The songs in `Tracks`, see [`track_window`].
+    queue: Vec<SongInQueue>,
+    fetched: Option<Instant>,
+}
+
+impl Snapshot {
+    /// Where playback would be now if nothing happened since the last sync.
+    fn expected_elapsed(&self) -> Option<Duration> {
+        let status = self.status.as_ref()?;
+        let elapsed = status.elapsed?;
+        Some(match (status.state, self.fetched) {
+            (PlayState::Playing, Some(fetched)) => elapsed + fetched.elapsed(),
+            _ => elapsed,
+        })
+    }
+
+    /// Values of the `Player` properties that change with the player state.
+    fn player_properties(&self) -> PropMap {
+        let mut props = PropMap::new();
+        let Some(status) = &self.status else {
+            return props;
+        };
+        put(
+            &mut props,
+            "PlaybackStatus",
+            playback_status(status).to_string(),
+        );
+        put(&mut props, "LoopStatus", loop_status(status).to_string());
+        put(&mut props, "Shuffle", status.random);
+        put(&mut props, "Volume", status.volume as f64 / 100.0);
+        put(&mut props, "CanGoNext", status.next_song.is_some());
+        put(
+            &mut props,
+            "CanGoPrevious",
+            status.current_song.is_some_and(|(pos, _)| pos.0 > 0) || status.repeat,
+        );
+        put(&mut props, "CanPlay", status.playlist_length > 0);
+        put(&mut props, "CanSeek", status.duration.is_some());
+        put(&mut props, "Metadata", self.metadata());
+        props
+    }
+
+    fn metadata(&self) -> PropMap {
+        match &self.song {
+            Some(song) => metadata(song, self.art_url.as_deref(), self.music_dir.as_deref()),
+            None => {
+                let mut map = PropMap::new();
+                put(&mut map, "mpris:trackid", object_path(NO_TRACK));
+                map
+            }
+        }
+    }
+
+    fn tracks(&self) -> Vec<Path<'static>> {
+        self.queue.iter().map(|s| track_id(s.id.0)).collect()
+    }
+}
+
+/// Connection to the session bus and the last seen player state.
+pub struct Mpris {
+    conn: Arc<SyncConnection>,
+    snapshot: Mutex<Snapshot>,
+    queue_changed: Notify,
+}
+
+fn put(map: &mut PropMap, key: &str, value: impl RefArg + 'static) {
+    map.insert(key.to_string(), Variant(Box::new(value)));
+}
+
+fn object_path(path: &str) -> Path<'static> {
+    Path::new(path.to_string()).unwrap_or_default()
+}
+
+fn track_id(id: u64) -> Path<'static> {
+    object_path(&format!("{}{}", TRACK_PREFIX, id))
+}
+
+fn parse_track_id(path: &Path) -> Option<SongId> {
+    path.strip_prefix(TRACK_PREFIX)?.parse().ok().map(SongId)
+}
+
+fn playback_status(status: &Status) -> &'static str {
+    match status.state {
+        PlayState::Playing => "Playing",
+        PlayState::Paused => "Paused",
+        PlayState::Stopped => "Stopped",
+    }
+}
+
+fn loop_status(status: &Status) -> &'static str {
+    match (status.repeat, status.single) {
+        (false, _) => "None",
+        (true, SingleMode::Disabled) => "Playlist",
+        (true, _) => "Track",
+    }
+}
+
+/// Queue positions in `Tracks`: up to [`MAX_TRACKS`] from the current song on.
+fn track_window(current: Option<usize>, length: usize) -> Range<usize> {
+    let start = current.unwrap_or(0).min(length);
+    start..length.min(start + MAX_TRACKS)
+}
+
+/// `xesam:url` of a song: streams keep their URL and files become `file://` URIs in the music
+/// directory, without which there is none.
+fn song_url(music_dir: Option<&std::path::Path>, uri: &str) -> Option<String> {
+    if playlist::is_url(uri) {
+        return Some(uri.to_string());
+    }
+    let path = join_within(music_dir?, uri)?;
+    Some(playlist::to_uri(path.to_str()?))
+}
+
+/// Where `SetPosition` should seek to, `None` for the negative positions and positions past the
+/// end that the spec asks to ignore.
+fn set_position(position: i64, duration: Option<Duration>) -> Option<Duration> {
+    let position = Duration::from_micros(u64::try_from(position).ok()?);
+    duration.is_none_or(|d| position <= d).then_some(position)
+}
+
+fn metadata(
+    song: &SongInQueue,
+    art_url: Option<&str>,
+    music_dir: Option<&std::path::Path>,
+) -> PropMap {
+    let mut map = PropMap::new();
+    put(&mut map, "mpris:trackid", track_id(song.id.0));
+    let song = &song.song;
+    if let Some(duration) = song.duration {
+        put(&mut map, "mpris:length", duration.as_micros() as i64);
+    }
+    if let Some(url) = art_url {
+        put(&mut map, "mpris:artUrl", url.to_string());
+    }
+    if let Some(url) = song_url(music_dir, &song.url) {
+        put(&mut map, "xesam:url", url);
+    }
+    if let Some(title) = song.title() {
+        put(&mut map, "xesam:title", title.to_string());
+    }
+    if !song.artists().is_empty() {
+        put(&mut map, "xesam:artist", song.artists().to_vec());
+    }
+    if let Some(album) = song.album() {
+        put(&mut map, "xesam:album", album.to_string());
+    }
+    if !song.album_artists().is_empty() {
+        put(&mut map, "xesam:albumArtist", song.album_artists().to_vec());
+    }
+    if let Some(genres) = song.tags.get(&Tag::Genre) {
+        put(&mut map, "xesam:genre", genres.clone());
+    }
+    let (disc, track) = song.number();
+    if track > 0 {
+        put(&mut map, "xesam:trackNumber", track as i32);
+    }
+    if disc > 0 {
+        put(&mut map, "xesam:discNumber", disc as i32);
+    }
+    map
+}
+
+async fn call<C: Command>(handle: &AppHandle, command: C) -> Result<C::Response, MethodErr> {
+    handle
+        .state::<Client>()
+        .command(command)
+        .await
+        .map_err(|e| MethodErr::failed(&e))
+}
+
+/// Run `command` in the background, for property writes which can't wait for MPD.
+fn spawn_command<C>(handle: &AppHandle, command: C)
+where
+    C: Command + Send + 'static,
+    C::Response: Send,
+{
+    let handle = handle.clone();
+    async_runtime::spawn(async move {
+        if let Err(e) = call(&handle, command).await {
+            log::error!("MPRIS command failed: {}", e.description());
+        }
+    });
+}
+
+/// Register a `Player` method without arguments that sends `command()`.
+fn simple_method<C, F>(b: &mut IfaceBuilder<AppHandle>, name: &'static str, command: F)
+where
+    C: Command + Send + 'static,
+    C::Response: Send,
+    F: Fn() -> C + Send + 'static,
+{
+    b.method_with_cr_async(name, (), (), move |mut ctx, cr, ()| {
+        let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+        let command = command();
+        async move {
+            let result = match handle {
+                Some(handle) => call(&handle, command).await.map(drop),
+                None => Err(MethodErr::no_path(ctx.path())),
+            };
+            ctx.reply(result)
+        }
+    });
+}
+
+fn snapshot<T>(handle: &AppHandle, read: impl FnOnce(&Snapshot) -> T) -> T {
+    read(&handle.state::<Mpris>().snapshot.lock().unwrap())
+}
+
+fn register_root(cr: &mut Crossroads) -> dbus_crossroads::IfaceToken<AppHandle> {
+    cr.register(ROOT_IFACE, |b: &mut IfaceBuilder<AppHandle>| {
+        b.method("Raise", (), (), |_, handle, ()| {
+            if let Some(window) = handle.get_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            Ok(())
+        });
+        b.method("Quit", (), (), |_, _, ()| Ok(()));
+        b.property("CanQuit")
+            .get(|_, _| Ok(false))
+            .emits_changed_const();
+        b.property("CanRaise")
+            .get(|_, _| Ok(true))
+            .emits_changed_const();
+        b.property("HasTrackList")
+            .get(|_, _| Ok(true))
+            .emits_changed_const();
+        b.property("Identity")
+            .get(|_, _| Ok("rmpd".to_string()))
+            .emits_changed_const();
+        b.property("DesktopEntry")
+            .get(|_, _| Ok("rmpd".to_string()))
+            .emits_changed_const();
+        b.property("SupportedUriSchemes")
+            .get(|_, _| Ok(vec!["http".to_string(), "https".to_string()]))
+            .emits_changed_const();
+        b.property("SupportedMimeTypes")
+            .get(|_, _| Ok(Vec::<String>::new()))
+            .emits_changed_const();
+    })
+}
+
+fn register_player(cr: &mut Crossroads) -> dbus_crossroads::IfaceToken<AppHandle> {
+    cr.register(PLAYER_IFACE, |b: &mut IfaceBuilder<AppHandle>| {
+        b.signal::<(i64,), _>("Seeked", ("Position",));
+        simple_method(b, "Next", || commands::Next);
+        simple_method(b, "Previous", || commands::Previous);
+        simple_method(b, "Pause", || commands::SetPause(true));
+        simple_method(b, "Stop", || commands::Stop);
+        simple_method(b, "Play", commands::Play::current);
+        b.method_with_cr_async("PlayPause", (), (), |mut ctx, cr, ()| {
+            let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+            async move {
+                let Some(handle) = handle else {
+                    return ctx.reply(Err(MethodErr::no_path(ctx.path())));
+                };
+                let result = match call(&handle, commands::Status).await {
+                    Ok(status) if status.state == PlayState::Playing => {
+                        call(&handle, commands::SetPause(true)).await
+                    }
+                    Ok(_) => call(&handle, commands::Play::current()).await,
+                    Err(e) => Err(e),
+                };
+                ctx.reply(result)
+            }
+        });
+        b.method_with_cr_async("Seek", ("Offset",), (), |mut ctx, cr, (offset,): (i64,)| {
+            let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+            async move {
+                let Some(handle) = handle else {
+                    return ctx.reply(Err(MethodErr::no_path(ctx.path())));
+                };
+                let (elapsed, _) = handle.state::<PlaybackClock>().elapsed();
+                let duration = snapshot(&handle, |s| s.status.as_ref()?.duration);
+                let by = Duration::from_micros(offset.unsigned_abs());
+                let result = if offset >= 0 && duration.is_some_and(|d| elapsed + by >= d) {
+                    call(&handle, commands::Next).await
+                } else if offset >= 0 {
+                    call(&handle, commands::Seek(SeekMode::Forward(by))).await
+                } else {
+                    let to = elapsed.saturating_sub(by);
+                    call(&handle, commands::Seek(SeekMode::Absolute(to))).await
+                };
+                ctx.reply(result)
+            }
+        });
+        b.method_with_cr_async(
+            "SetPosition",
+            ("TrackId", "Position"),
+            (),
+            |mut ctx, cr, (track, position): (Path<'static>, i64)| {
+                let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+                async move {
+                    let Some(handle) = handle else {
+                        return ctx.reply(Err(MethodErr::no_path(ctx.path())));
+                    };
+                    let current = snapshot(&handle, |s| {
+                        s.song.as_ref().map(|song| (song.id, song.song.duration))
+                    });
+                    // Stale track ids are ignored as well, as the spec asks.
+                    let seek = current
+                        .filter(|(id, _)| Some(*id) == parse_track_id(&track))
+                        .and_then(|(id, duration)| Some((id, set_position(position, duration)?)));
+                    let result = match seek {
+                        Some((id, position)) => {
+                            call(&handle, commands::SeekTo(commands::Song::Id(id), position)).await
+                        }
+                        None => Ok(()),
+                    };
+                    ctx.reply(result)
+                }
+            },
+        );
+        b.method_with_cr_async("OpenUri", ("Uri",), (), |mut ctx, cr, (uri,): (String,)| {
+            let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+            async move {
+                let Some(handle) = handle else {
+                    return ctx.reply(Err(MethodErr::no_path(ctx.path())));
+                };
+                let result = match call(&handle, commands::Add::uri(&uri)).await {
+                    Ok(id) => call(&handle, commands::Play::song(commands::Song::Id(id))).await,
+                    Err(e) => Err(e),
+                };
+                ctx.reply(result)
+            }
+        });
+
+        b.property("PlaybackStatus")
+            .get(|_, handle| {
+                Ok(snapshot(handle, |s| {
+                    s.status.as_ref().map_or("Stopped", playback_status)
+                })
+                .to_string())
+            })
+            .emits_changed_true();
+        b.property("LoopStatus")
+            .get(|_, handle| {
+                Ok(snapshot(handle, |s| s.status.as_ref().map_or("None", loop_status)).to_string())
+            })
+            .set(|_, handle, value: String| {
+                let (repeat, single) = match value.as_str() {
+                    "None" => (false, SingleMode::Disabled),
+                    "Track" => (true, SingleMode::Enabled),
+                    "Playlist" => (true, SingleMode::Disabled),
+                    _ => return Err(MethodErr::invalid_arg(&value)),
+                };
+                spawn_command(handle, commands::SetRepeat(repeat));
+                spawn_command(handle, commands::SetSingle(single));
+                Ok(None)
+            })
+            .emits_changed_true();
+        b.property("Rate")
+            .get(|_, _| Ok(1.0))
+            .set(|_, _, _: f64| Ok(None))
+            .emits_changed_true();
+        b.property("MinimumRate")
+            .get(|_, _| Ok(1.0))
+            .emits_changed_const();
+        b.property("MaximumRate")
+            .get(|_, _| Ok(1.0))
+            .emits_changed_const();
+        b.property("Shuffle")
+            .get(|_, handle| {
+                Ok(snapshot(handle, |s| {
+                    s.status.as_ref().is_some_and(|s| s.random)
+                }))
+            })
+            .set(|_, handle, value: bool| {
+                spawn_command(handle, commands::SetRandom(value));
+                Ok(None)
+            })
+            .emits_changed_true();
+        b.property("Metadata")
+            .get(|_, handle| Ok(snapshot(handle, Snapshot::metadata)))
+            .emits_changed_true();
+        b.property("Volume")
+            .get(|_, handle| {
+                Ok(snapshot(handle, |s| {
+                    s.status.as_ref().map_or(0.0, |s| s.volume as f64 / 100.0)
+                }))
+            })
+            .set(|_, handle, value: f64| {
+                let volume = (value.clamp(0.0, 1.0) * 100.0).round() as u8;
+                spawn_command(handle, commands::SetVolume(volume));
+                Ok(None)
+            })
+            .emits_changed_true();
+        b.property("Position")
+            .get(|_, handle| {
+                let (elapsed, _) = handle.state::<PlaybackClock>().elapsed();
+                Ok(elapsed.as_micros() as i64)
+            })
+            .emits_changed_false();
+        for (name, get) in [
+            (
+                "CanGoNext",
+                (|s: &Status| s.next_song.is_some()) as fn(&Status) -> bool,
+            ),
+            ("CanGoPrevious", |s| {
+                s.current_song.is_some_and(|(pos, _)| pos.0 > 0) || s.repeat
+            }),
+            ("CanPlay", |s| s.playlist_length > 0),
+            ("CanSeek", |s| s.duration.is_some()),
+        ] {
+            b.property(name)
+                .get(move |_, handle| Ok(snapshot(handle, |s| s.status.as_ref().is_some_and(get))))
+                .emits_changed_true();
+        }
+        b.property("CanPause")
+            .get(|_, _| Ok(true))
+            .emits_changed_const();
+        b.property("CanControl")
+            .get(|_, _| Ok(true))
+            .emits_changed_const();
+    })
+}
+
+fn register_tracklist(cr: &mut Crossroads) -> dbus_crossroads::IfaceToken<AppHandle> {
+    cr.register(TRACKLIST_IFACE, |b: &mut IfaceBuilder<AppHandle>| {
+        b.signal::<(Vec<Path<'static>>, Path<'static>), _>(
+            "TrackListReplaced",
+            ("Tracks", "CurrentTrack"),
+        );
+        b.method(
+            "GetTracksMetadata",
+            ("TrackIds",),
+            ("Metadata",),
+            |_, handle, (ids,): (Vec<Path<'static>>,)| {
+                let metadata = snapshot(handle, |s| {
+                    ids.iter()
+                        .filter_map(parse_track_id)
+                        .filter_map(|id| s.queue.iter().find(|song| song.id == id))
+                        .map(|song| metadata(song, None, s.music_dir.as_deref()))
+                        .collect::<Vec<_>>()
+                });
+                Ok((metadata,))
+            },
+        );
+        b.method_with_cr_async(
+            "AddTrack",
+            ("Uri", "AfterTrack", "SetAsCurrent"),
+            (),
+            |mut ctx, cr, (uri, after, play): (String, Path<'static>, bool)| {
+                let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+                async move {
+                    let Some(handle) = handle else {
+                        return ctx.reply(Err(MethodErr::no_path(ctx.path())));
+                    };
+                    let position = match parse_track_id(&after) {
+                        Some(after) => snapshot(&handle, |s| {
+                            s.queue
+                                .iter()
+                                .find(|song| song.id == after)
+                                .map(|s| s.position.0)
+                        })
+                        .map_or(0, |position| position + 1),
+                        None => 0,
+                    };
+                    let result = match call(&handle, commands::Add::uri(&uri).at(position)).await {
+                        Ok(id) if play => {
+                            call(&handle, commands::Play::song(commands::Song::Id(id))).await
+                        }
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(e),
+                    };
+                    ctx.reply(result)
+                }
+            },
+        );
+        b.method_with_cr_async(
+            "RemoveTrack",
+            ("TrackId",),
+            (),
+            |mut ctx, cr, (track,): (Path<'static>,)| {
+                let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+                async move {
+                    let result = match (handle, parse_track_id(&track)) {
+                        (Some(handle), Some(id)) => call(&handle, commands::Delete::id(id)).await,
+                        _ => Err(MethodErr::invalid_arg(&track)),
+                    };
+                    ctx.reply(result)
+                }
+            },
+        );
+        b.method_with_cr_async(
+            "GoTo",
+            ("TrackId",),
+            (),
+            |mut ctx, cr, (track,): (Path<'static>,)| {
+                let handle = cr.data_mut::<AppHandle>(ctx.path()).cloned();
+                async move {
+                    let result = match (handle, parse_track_id(&track)) {
+                        (Some(handle), Some(id)) => {
+                            call(&handle, commands::Play::song(commands::Song::Id(id))).await
+                        }
+                        _ => Err(MethodErr::invalid_arg(&track)),
+                    };
+                    ctx.reply(result)
+                }
+            },
+        );
+        b.property("Tracks")
+            .get(|_, handle| Ok(snapshot(handle, Snapshot::tracks)))
+            .emits_changed_invalidates();
+        b.property("CanEditTracks")
+            .get(|_, _| Ok(true))
+            .emits_changed_const();
+    })
+}
+
+/// Connect to the session bus and serve the MPRIS interfaces until the app exits.
+pub async fn serve_mpris(handle: AppHandle) {
+    let (resource, conn) = match dbus_tokio::connection::new_session_sync() {
+        Ok(connection) => connection,
+        Err(e) => {
+            log::warn!("MPRIS is unavailable, no session bus: {}", e);
+            return;
+        }
+    };
+    async_runtime::spawn(async {
+        let e = resource.await;
+        log::error!("Lost the D-Bus connection: {}", e);
+    });
+    // Another instance may hold the name already; MPRIS allows a unique suffix.
+    let instance = format!("{}.instance{}", BUS_NAME, std::process::id());
+    for name in [BUS_NAME, &instance] {
+        match conn.request_name(name, false, false, true).await {
+            Ok(dbus::nonblock::stdintf::org_freedesktop_dbus::RequestNameReply::PrimaryOwner) => {
+                break
+            }
+            Ok(_) if name == BUS_NAME => continue,
+            Ok(_) => {
+                log::error!("Failed to own the MPRIS bus name {}", name);
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to request the MPRIS bus name: {}", e);
+                return;
+            }
+        }
+    }
+
+    let mut cr = Crossroads::new();
+    let spawn_conn = conn.clone();
+    cr.set_async_support(Some((
+        spawn_conn,
+        Box::new(|future| {
+            async_runtime::spawn(future);
+        }),
+    )));
+    let interfaces = [
+        register_root(&mut cr),
+        register_player(&mut cr),
+        register_tracklist(&mut cr),
+    ];
+    cr.insert(OBJECT_PATH, &interfaces, handle.clone());
+    handle.manage(Mpris {
+        conn: conn.clone(),
+        snapshot: Mutex::default(),
+        queue_changed: Notify::new(),
+    });
+    sync_mpris(&handle).await;
+    queue_changed(&handle);
+    async_runtime::spawn(tracklist_task(handle.clone()));
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, conn| {
+            if cr.handle_message(message, conn).is_err() {
+                log::warn!("Failed to handle an MPRIS method call");
+            }
+            true
+        }),
+    );
+}
+
+/// File URL of the cover served for `uri` if it is known.
+///
+/// Otherwise the cover is resolved in the background, refreshing the player once it can be
+/// served, so asking MPD for art doesn't hold up the event loop.
+fn art_url(handle: &AppHandle, uri: &str) -> Option<String> {
+    let resolver = handle.state::<CoverResolver>();
+    if let Some(cover) = resolver.lookup(&handle.state::<ArtCache>(), uri, None) {
+        return Some(playlist::to_uri(cover.path.to_str()?));
+    }
+    if resolver.start_pending(uri) {
+        let handle = handle.clone();
+        let uri = uri.to_string();
+        async_runtime::spawn(async move {
+            let resolver = handle.state::<CoverResolver>();
+            let cache = handle.state::<ArtCache>();
+            resolver
+                .resolve(&handle.state::<Client>(), &cache, &uri, None)
+                .await;
+            resolver.finish_pending(&uri);
+            if resolver.lookup(&cache, &uri, None).is_some() {
+                sync_mpris(&handle).await;
+            }
+        });
+    }
+    None
+}
+
+fn send(conn: &SyncConnection, message: Result<Message, String>) {
+    if message
+        .and_then(|m| conn.send(m).map_err(|_| "send failed".to_string()))
+        .is_err()
+    {
+        log::warn!("Failed to send an MPRIS signal");
+    }
+}
+
+fn tracklist_replaced(
+    tracks: Vec<Path<'static>>,
+    current: Path<'static>,
+) -> Result<Message, String> {
+    Message::new_signal(OBJECT_PATH, TRACKLIST_IFACE, "TrackListReplaced")
+        .map(|m| m.append2(tracks, current))
+}
+
+/// Have the track list refetched, e.g. after the queue changed.
+pub fn queue_changed(handle: &AppHandle) {
+    if let Some(mpris) = handle.try_state::<Mpris>() {
+        mpris.queue_changed.notify_one();
+    }
+}
+
+/// Fetch the track list window whenever it may have changed and emit `TrackListReplaced`.
+///
+/// This runs apart from `event_handler`, so a long queue never holds up other events.
+async fn tracklist_task(handle: AppHandle) {
+    let mpris = handle.state::<Mpris>();
+    loop {
+        mpris.queue_changed.notified().await;
+        let client = handle.state::<Client>();
+        let fetched = async {
+            let status = client.command(commands::Status).await?;
+            let window = track_window(
+                status.current_song.map(|(position, _)| position.0),
+                status.playlist_length,
+            );
+            let songs = match window.is_empty() {
+                true => Vec::new(),
+                false => client.command(QueueRange(window)).await?,
+            };
+            Ok::<_, mpd_client::client::CommandError>((status, songs))
+        };
+        let (status, songs) = match fetched.await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                log::error!("Failed to fetch the queue for MPRIS: {}", e);
+                continue;
+            }
+        };
+        let current = status
+            .current_song
+            .map_or(object_path(NO_TRACK), |(_, id)| track_id(id.0));
+        let signal = {
+            let mut snapshot = mpris.snapshot.lock().unwrap();
+            snapshot.queue = songs;
+            tracklist_replaced(snapshot.tracks(), current)
+        };
+        send(&mpris.conn, signal);
+    }
+}
+
+/// Fetch the player state, refresh the player snapshot and signal what changed.
+pub async fn sync_mpris(handle: &AppHandle) {
+    if handle.try_state::<Mpris>().is_none() {
+        return;
+    }
+    match player_state(&handle.state::<Client>()).await {
+        Ok((status, song)) => update_mpris(handle, status, song).await,
+        Err(e) => log::error!("Failed to fetch the player state for MPRIS: {}", e),
+    }
+}
+
+/// Refresh the player snapshot with a fetched player state and signal what changed.
+pub async fn update_mpris(handle: &AppHandle, status: Status, song: Option<SongInQueue>) {
+    let Some(mpris) = handle.try_state::<Mpris>() else {
+        return;
+    };
+    let client = handle.state::<Client>();
+    let (previous_song, previous_art) = {
+        let snapshot = mpris.snapshot.lock().unwrap();
+        let song = snapshot.song.as_ref().map(|s| (s.id, s.song.url.clone()));
+        (song, snapshot.art_url.clone())
+    };
+    let current = song.as_ref().map(|s| (s.id, s.song.url.clone()));
+    let art = match &current {
+        Some(current) if previous_song.as_ref() == Some(current) && previous_art.is_some() => {
+            previous_art
+        }
+        Some((_, uri)) => art_url(handle, uri),
+        None => None,
+    };
+    let music_dir = handle
+        .state::<CoverResolver>()
+        .music_directory(&client)
+        .await;
+
+    let path = object_path(OBJECT_PATH);
+    let mut signals = Vec::new();
+    {
+        let mut snapshot = mpris.snapshot.lock().unwrap();
+        let before = snapshot.player_properties();
+        let expected = snapshot.expected_elapsed();
+        let same_song = previous_song.map(|(id, _)| id) == song.as_ref().map(|s| s.id);
+        snapshot.status = Some(status.clone());
+        snapshot.song = song;
+        snapshot.art_url = art;
+        snapshot.music_dir = music_dir;
+        snapshot.fetched = Some(Instant::now());
+        // The window starts at the current song, so it moves along unless it holds every song.
+        if !same_song && status.playlist_length > snapshot.queue.len() {
+            mpris.queue_changed.notify_one();
+        }
+
+        let mut changed = snapshot.player_properties();
+        // Nested maps don't compare, so compare them by their debug output.
+        changed.retain(|name, value| {
+            before
+                .get(name)
+                .is_none_or(|old| format!("{:?}", old) != format!("{:?}", value))
+        });
+        if !changed.is_empty() {
+            let properties = PropertiesPropertiesChanged {
+                interface_name: PLAYER_IFACE.to_string(),
+                changed_properties: changed,
+                invalidated_properties: Vec::new(),
+            };
+            signals.push(Ok(properties.to_emit_message(&path)));
+        }
+
+        let elapsed = status.elapsed.unwrap_or_default();
+        let jumped = expected
+            .is_some_and(|expected| expected.max(elapsed) - expected.min(elapsed) > SEEK_TOLERANCE);
+        if same_song && jumped {
+            signals.push(
+                Message::new_signal(OBJECT_PATH, PLAYER_IFACE, "Seeked")
+                    .map(|m| m.append1(elapsed.as_micros() as i64)),
+            );
+        }
+    }
+    for signal in signals {
+        send(&mpris.conn, signal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+    };
+
+    use dbus::{blocking::Connection, channel::Channel};
+
+    use super::*;
+
+    /// A private session bus, stopped when dropped.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// Start a `dbus-daemon`, or `None` if it isn't installed.
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            let stdout = daemon.stdout.take().unwrap();
+            BufReader::new(stdout).read_line(&mut address).unwrap();
+            assert!(!address.trim().is_empty(), "dbus-daemon printed no address");
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            let mut channel = Channel::open_private(&self.address).unwrap();
+            channel.register().unwrap();
+            Connection::from(channel)
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn windows_long_queues() {
+        assert_eq!(track_window(None, 10), 0..10);
+        assert_eq!(track_window(Some(3), 10), 3..10);
+        assert_eq!(track_window(Some(50), 1000), 50..50 + MAX_TRACKS);
+        assert_eq!(track_window(Some(5), 0), 0..0);
+    }
+
+    #[test]
+    fn urls_are_uris() {
+        let dir = std::path::Path::new("/music");
+        assert_eq!(
+            song_url(Some(dir), "A B/01 #1.flac").as_deref(),
+            Some("file:///music/A%20B/01%20%231.flac")
+        );
+        assert_eq!(
+            song_url(None, "http://radio.example/stream").as_deref(),
+            Some("http://radio.example/stream")
+        );
+        assert_eq!(song_url(None, "a.flac"), None);
+        assert_eq!(song_url(Some(dir), "../etc/passwd"), None);
+    }
+
+    #[test]
+    fn set_position_ignores_negative_and_past_the_end() {
+        let duration = Some(Duration::from_secs(10));
+        assert_eq!(
+            set_position(2_000_000, duration),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(set_position(-1, duration), None);
+        assert_eq!(set_position(11_000_000, duration), None);
+        assert_eq!(
+            set_position(11_000_000, None),
+            Some(Duration::from_secs(11))
+        );
+    }
+
+    #[test]
+    fn tracklist_replaced_reaches_listeners() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let listener = bus.connect();
+        let received = Arc::new(Mutex::new(None));
+        let store = received.clone();
+        listener
+            .add_match(
+                MatchRule::new_signal(TRACKLIST_IFACE, "TrackListReplaced"),
+                move |args: (Vec<Path<'static>>, Path<'static>), _, _| {
+                    *store.lock().unwrap() = Some(args);
+                    true
+                },
+            )
+            .unwrap();
+
+        let sender = bus.connect();
+        let signal = tracklist_replaced(vec![track_id(1), track_id(2)], track_id(2)).unwrap();
+        sender.channel().send(signal).unwrap();
+        sender.channel().flush();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.lock().unwrap().is_none() && Instant::now() < deadline {
+            listener.process(Duration::from_millis(100)).unwrap();
+        }
+        let (tracks, current) = received.lock().unwrap().take().expect("no signal received");
+        assert_eq!(tracks, [track_id(1), track_id(2)]);
+        assert_eq!(current, track_id(2));
+        assert_eq!(parse_track_id(&current), Some(SongId(2)));
+    }
+}
@@ -0,0 +1,260 @@
+//! Whole-playlist edits of stored playlists.
+//!
+//! MPD can only move or delete single entries of a stored playlist, so an edit is computed
+//! locally as the moves (or deletions) turning the current order into the wanted one, and sent
+//! as one command list.
+
+use std::{cmp::Ordering, collections::HashSet};
+
+use mpd_client::{commands, responses::Song, Client};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::reflection::tag::Tag;
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub enum PlaylistEdit {
+    /// Stable sort by the given tags in order, ties broken by URI.
+    Sort {
+        tags: Vec<Tag>,
+        descending: bool,
+    },
+    /// Remove all but the first entry of every song.
+    Dedupe,
+    Reverse,
+    Shuffle,
+}
+
+/// Compare strings case-insensitively, with runs of digits compared by value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (number(&mut a), number(&mut b));
+                match x.len().cmp(&y.len()).then_with(|| x.cmp(&y)) {
+                    Ordering::Equal => continue,
+                    other => return other,
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn sort_value(song: &Song, tag: &Tag) -> String {
+    tag.clone()
+        .render()
+        .ok()
+        .and_then(|tag| song.tags.get(&tag))
+        .map(|values| values.join(", "))
+        .unwrap_or_default()
+}
+
+/// Order of the positions of songs with sort `keys` and `urls`, ties broken by URI.
+fn sort_order(keys: &[Vec<String>], urls: &[&str], descending: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = keys[a]
+            .iter()
+            .zip(&keys[b])
+            .map(|(x, y)| natural_cmp(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| urls[a].cmp(urls[b]));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    order
+}
+
+/// Order of the current positions after the edit, `None` for edits that only delete.
+fn target_order(songs: &[Song], edit: &PlaylistEdit) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..songs.len()).collect();
+    match edit {
+        PlaylistEdit::Sort { tags, descending } => {
+            let keys: Vec<Vec<String>> = songs
+                .iter()
+                .map(|song| tags.iter().map(|tag| sort_value(song, tag)).collect())
+                .collect();
+            let urls: Vec<&str> = songs.iter().map(|s| s.url.as_str()).collect();
+            order = sort_order(&keys, &urls, *descending);
+        }
+        PlaylistEdit::Reverse => order.reverse(),
+        PlaylistEdit::Shuffle => order.shuffle(&mut rand::thread_rng()),
+        PlaylistEdit::Dedupe => return None,
+    }
+    Some(order)
+}
+
+/// The `(from, to)` moves that rearrange positions `0..n` into `order`, applied in sequence.
+fn moves_for(order: &[usize]) -> Vec<(usize, usize)> {
+    let mut current: Vec<usize> = (0..order.len()).collect();
+    let mut moves = Vec::new();
+    for (to, wanted) in order.iter().enumerate() {
+        let from = current[to..]
+            .iter()
+            .position(|p| p == wanted)
+            .map(|i| i + to)
+            .expect("order is a permutation");
+        if from != to {
+            let item = current.remove(from);
+            current.insert(to, item);
+            moves.push((from, to));
+        }
+    }
+    moves
+}
+
+/// Positions of repeated songs, last first so deleting them in order keeps the rest valid.
+fn duplicates<'a>(urls: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    let mut positions: Vec<usize> = urls
+        .into_iter()
+        .enumerate()
+        .filter(|(_, url)| !seen.insert(*url))
+        .map(|(i, _)| i)
+        .collect();
+    positions.reverse();
+    positions
+}
+
+/// Apply `edit` to the stored playlist `name`, returning the number of entries moved or removed.
+pub async fn apply(client: &Client, name: &str, edit: &PlaylistEdit) -> Result<usize, String> {
+    let songs = client
+        .command(commands::GetPlaylist(name))
+        .await
+        .map_err(|e| e.to_string())?;
+    let changed = match target_order(&songs, edit) {
+        Some(order) => {
+            let moves: Vec<_> = moves_for(&order)
+                .into_iter()
+                .map(|(from, to)| commands::MoveInPlaylist::new(name, from, to))
+                .collect();
+            let count = moves.len();
+            if count > 0 {
+                client
+                    .command_list(moves)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            count
+        }
+        None => {
+            let deletes: Vec<_> = duplicates(songs.iter().map(|s| s.url.as_str()))
+                .into_iter()
+                .map(|pos| commands::RemoveFromPlaylist::position(name, pos))
+                .collect();
+            let count = deletes.len();
+            if count > 0 {
+                client
+                    .command_list(deletes)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            count
+        }
+    };
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `moves` to `items` one after the other, like MPD does.
+    fn apply_moves<T>(mut items: Vec<T>, moves: &[(usize, usize)]) -> Vec<T> {
+        for &(from, to) in moves {
+            let item = items.remove(from);
+            items.insert(to, item);
+        }
+        items
+    }
+
+    fn sorted<'a>(titles: &[&'a str], urls: &[&'a str], descending: bool) -> Vec<&'a str> {
+        let keys: Vec<Vec<String>> = titles.iter().map(|t| vec![t.to_string()]).collect();
+        let moves = moves_for(&sort_order(&keys, urls, descending));
+        apply_moves(urls.to_vec(), &moves)
+    }
+
+    #[test]
+    fn compares_digit_runs_by_value() {
+        assert_eq!(natural_cmp("Track 2", "track 10"), Ordering::Less);
+        assert_eq!(natural_cmp("a007", "a7"), Ordering::Equal);
+        assert_eq!(natural_cmp("a7b", "a07c"), Ordering::Less);
+        assert_eq!(natural_cmp("abc", "ab"), Ordering::Greater);
+        let mut titles = ["10", "9", "Disc 1 - 2", "Disc 1 - 10", "b", "A"];
+        titles.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(titles, ["9", "10", "A", "b", "Disc 1 - 2", "Disc 1 - 10"]);
+    }
+
+    #[test]
+    fn moves_rearrange_into_order() {
+        let order = [3, 0, 4, 1, 2];
+        let moves = moves_for(&order);
+        assert_eq!(apply_moves((0..5).collect(), &moves), order);
+        assert!(moves_for(&[0, 1, 2]).is_empty());
+        let reversed: Vec<usize> = (0..6).rev().collect();
+        assert_eq!(
+            apply_moves((0..6).collect(), &moves_for(&reversed)),
+            reversed
+        );
+    }
+
+    #[test]
+    fn sorts_numeric_runs_by_value() {
+        let urls = ["t10.mp3", "t2.mp3", "t1.mp3"];
+        let titles = ["Track 10", "Track 2", "track 1"];
+        assert_eq!(
+            sorted(&titles, &urls, false),
+            ["t1.mp3", "t2.mp3", "t10.mp3"]
+        );
+    }
+
+    #[test]
+    fn sorts_descending_with_uri_tie_break() {
+        let urls = ["b.mp3", "c.mp3", "a.mp3", "d.mp3"];
+        let titles = ["Same", "Other", "same", "Zed"];
+        assert_eq!(
+            sorted(&titles, &urls, false),
+            ["c.mp3", "a.mp3", "b.mp3", "d.mp3"]
+        );
+        // Ties are reversed too, so descending is the exact mirror of ascending.
+        assert_eq!(
+            sorted(&titles, &urls, true),
+            ["d.mp3", "b.mp3", "a.mp3", "c.mp3"]
+        );
+    }
+
+    #[test]
+    fn dedupe_deletes_last_first() {
+        let urls = ["a", "b", "a", "c", "b", "a"];
+        let positions = duplicates(urls);
+        assert_eq!(positions, [5, 4, 2]);
+        let mut items = urls.to_vec();
+        for pos in positions {
+            items.remove(pos);
+        }
+        assert_eq!(items, ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Extended M3U (`#EXTM3U`), used for both `.m3u` and `.m3u8`.
+
+use std::{path::Path, time::Duration};
+
+use super::{is_url, Entry};
+
+/// Whether a line of a plain M3U file, without the `#EXTM3U` header, looks like a song.
+fn is_location(line: &str) -> bool {
+    is_url(line) || Path::new(line).extension().is_some()
+}
+
+/// Parse M3U, refusing files that are neither extended M3U nor a list of files and URLs.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut info: Option<Entry> = None;
+    let mut lines = text.lines().map(str::trim).enumerate().peekable();
+    let extended = lines
+        .peek()
+        .is_some_and(|(_, line)| line.starts_with("#EXTM3U"));
+    for (number, line) in lines {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let (length, display) = extinf.split_once(',').unwrap_or((extinf, ""));
+            // Attributes like `tvg-id="..."` may follow the length.
+            let length = length.split_whitespace().next().unwrap_or_default();
+            let (artist, title) = match display.split_once(" - ") {
+                Some((artist, title)) => (Some(artist.trim()), title.trim()),
+                None => (None, display.trim()),
+            };
+            info = Some(Entry {
+                title: Some(title.to_string()).filter(|t| !t.is_empty()),
+                artist: artist.map(String::from),
+                duration: length
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|&l| l >= 0.0)
+                    .map(Duration::from_secs_f64),
+                ..Entry::default()
+            });
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else if !extended && !is_location(line) {
+            return Err(format!("Not an M3U playlist, line {}", number + 1));
+        } else {
+            entries.push(Entry {
+                location: line.to_string(),
+                ..info.take().unwrap_or_default()
+            });
+        }
+    }
+    Ok(entries)
+}
+
+pub fn write(entries: &[Entry]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        let length = entry.duration.map_or(-1, |d| d.as_secs() as i64);
+        let display = match (&entry.artist, &entry.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => String::new(),
+        };
+        out.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            length, display, entry.location
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_extinf() {
+        let text = "#EXTM3U\n#EXTINF:123,Artist - Title\n/music/a.flac\n\nb.mp3\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location, "/music/a.flac");
+        assert_eq!(entries[0].artist.as_deref(), Some("Artist"));
+        assert_eq!(entries[0].title.as_deref(), Some("Title"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(123)));
+        assert_eq!(entries[1].location, "b.mp3");
+        assert!(entries[1].title.is_none());
+    }
+
+    #[test]
+    fn parses_extinf_attributes_and_unknown_length() {
+        let text = "#EXTM3U\n#EXTINF:-1 tvg-id=\"x\",Radio\nhttp://host/stream\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(entries[0].title.as_deref(), Some("Radio"));
+        assert!(entries[0].artist.is_none());
+        assert!(entries[0].duration.is_none());
+    }
+
+    #[test]
+    fn accepts_plain_lists_of_files() {
+        let entries = parse("a/b.ogg\r\nhttp://host/stream\r\n").unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        assert!(parse("root:x:0:0:root:/root:/bin/bash\n").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let entries = vec![
+            Entry {
+                location: "/music/a.flac".to_string(),
+                title: Some("Title".to_string()),
+                artist: Some("Artist".to_string()),
+                duration: Some(Duration::from_secs(61)),
+                ..Entry::default()
+            },
+            Entry {
+                location: "b.mp3".to_string(),
+                ..Entry::default()
+            },
+        ];
+        let text = write(&entries);
+        assert!(text.starts_with("#EXTM3U\n#EXTINF:61,Artist - Title\n"));
+        let parsed = parse(&text).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].title, entries[0].title);
+        assert_eq!(parsed[0].artist, entries[0].artist);
+        assert_eq!(parsed[0].duration, entries[0].duration);
+        assert_eq!(parsed[1].location, "b.mp3");
+        assert!(parsed[1].duration.is_none());
+    }
+}
@@ -0,0 +1,491 @@
+//! Importing and exporting playlist files; whole-playlist edits live in [`edit`] and smart
+//! playlists in [`smart`].
+//!
+//! Exported entries point at the songs' files under the music directory when it is known, and
+//! at the bare MPD URIs otherwise. Imported entries are resolved back to database URIs by path,
+//! trying the music directory, the playlist's own directory and finally the longest trailing
+//! part of the path that names a song, then by tags for entries whose paths don't line up.
+//! Stream URLs are added as they are; anything else that doesn't resolve is reported back as
+//! unmatched.
+
+pub mod edit;
+mod m3u;
+mod pls;
+pub mod smart;
+mod xspf;
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use mpd_client::{commands, responses, Client};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::{
+    library::Library,
+    reflection::{
+        response::{ImportReport, Song, UnmatchedEntry},
+        tag::Tag,
+    },
+};
+
+/// Characters escaped in the path of a `file://` URI.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// URL schemes MPD plays streams from; other URLs can't be added by clients.
+const STREAM_SCHEMES: [&str; 10] = [
+    "http", "https", "mms", "mmsh", "mmst", "mmsu", "rtmp", "rtp", "rtsp", "udp",
+];
+
+/// Durations closer than this count as the same song when matching by tags.
+const DURATION_TOLERANCE: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Xspf,
+    Pls,
+}
+
+impl PlaylistFormat {
+    /// Guess the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "m3u" => Some(PlaylistFormat::M3u),
+            "m3u8" => Some(PlaylistFormat::M3u8),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            "pls" => Some(PlaylistFormat::Pls),
+            _ => None,
+        }
+    }
+}
+
+/// The queue or a stored playlist, as the source of an export or the target of an import.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub enum PlaylistSource {
+    Queue,
+    Stored(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Entry {
+    /// Path or URL as written in the file.
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// Whether `location` is a URL, e.g. of a stream, rather than a path.
+pub fn is_url(location: &str) -> bool {
+    location
+        .split_once("://")
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains(['/', '\\']))
+}
+
+/// Whether `location` is the URL of a stream MPD can play.
+fn is_stream(location: &str) -> bool {
+    location.split_once("://").is_some_and(|(scheme, _)| {
+        STREAM_SCHEMES
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Turn a location into a URI for XSPF.
+pub fn to_uri(location: &str) -> String {
+    if is_url(location) {
+        location.to_string()
+    } else if Path::new(location).is_absolute() {
+        let path = location.replace('\\', "/");
+        let path = if path.starts_with('/') {
+            path
+        } else {
+            format!("/{}", path)
+        };
+        format!("file://{}", utf8_percent_encode(&path, PATH))
+    } else {
+        utf8_percent_encode(location, PATH).to_string()
+    }
+}
+
+/// Turn an XSPF location or `file://` URI back into a path, leaving other URLs alone.
+fn from_uri(uri: &str) -> String {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path.strip_prefix("localhost").unwrap_or(path),
+        None if is_url(uri) => return uri.to_string(),
+        None => uri,
+    };
+    let path = percent_decode_str(path).decode_utf8_lossy().into_owned();
+    // `file:///C:/Music` on Windows.
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    }
+}
+
+/// Read a playlist file, accepting Latin-1 for files that aren't valid UTF-8.
+///
+/// Locations come back as paths or URLs, `file://` URIs being turned into paths whatever the
+/// format.
+pub fn read_entries(path: &Path, format: PlaylistFormat) -> Result<Vec<Entry>, String> {
+    let raw = fs::read(path).map_err(|e| e.to_string())?;
+    if raw.contains(&0) {
+        return Err(format!("Not a playlist: {}", path.display()));
+    }
+    let text = match String::from_utf8(raw) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    };
+    let text = text.trim_start_matches('\u{feff}');
+    let mut entries = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::parse(text)?,
+        PlaylistFormat::Pls => pls::parse(text)?,
+        PlaylistFormat::Xspf => xspf::parse(text)?,
+    };
+    for entry in &mut entries {
+        // Only XSPF percent-encodes plain paths.
+        if format == PlaylistFormat::Xspf || entry.location.starts_with("file://") {
+            entry.location = from_uri(&entry.location);
+        }
+    }
+    Ok(entries)
+}
+
+/// Write a playlist file, always as UTF-8.
+pub fn write_entries(path: &Path, format: PlaylistFormat, entries: &[Entry]) -> Result<(), String> {
+    let text = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::write(entries),
+        PlaylistFormat::Pls => pls::write(entries),
+        PlaylistFormat::Xspf => {
+            let entries: Vec<Entry> = entries
+                .iter()
+                .map(|e| Entry {
+                    location: to_uri(&e.location),
+                    ..e.clone()
+                })
+                .collect();
+            xspf::write(&entries)
+        }
+    };
+    fs::write(path, text).map_err(|e| e.to_string())
+}
+
+fn entry_from_song(song: &responses::Song, music_dir: Option<&Path>) -> Entry {
+    let location = match music_dir {
+        Some(dir) if !is_url(&song.url) => dir.join(&song.url).to_string_lossy().into_owned(),
+        _ => song.url.clone(),
+    };
+    Entry {
+        location,
+        title: song.title().map(String::from),
+        artist: song.artists().first().cloned(),
+        album: song.album().map(String::from),
+        duration: song.duration,
+    }
+}
+
+/// Export the queue or a stored playlist to `path`, returning the number of entries.
+pub async fn export(
+    client: &Client,
+    music_dir: Option<&Path>,
+    source: &PlaylistSource,
+    path: &Path,
+    format: PlaylistFormat,
+) -> Result<usize, String> {
+    let songs: Vec<responses::Song> = match source {
+        PlaylistSource::Queue => client
+            .command(commands::Queue)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|s| s.song)
+            .collect(),
+        PlaylistSource::Stored(name) => client
+            .command(commands::GetPlaylist(name))
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    let entries: Vec<Entry> = songs
+        .iter()
+        .map(|song| entry_from_song(song, music_dir))
+        .collect();
+    write_entries(path, format, &entries)?;
+    Ok(entries.len())
+}
+
+/// Normalize a path into forward-slash components, resolving `.` and `..`.
+fn components(path: &Path) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts
+}
+
+fn tag_eq(song: &Song, tags: &[Tag], value: &str) -> bool {
+    tags.iter()
+        .filter_map(|tag| song.tags.get(&tag.to_string()))
+        .flatten()
+        .any(|v| v.eq_ignore_ascii_case(value))
+}
+
+struct Resolver<'a> {
+    songs: &'a [Song],
+    uris: HashSet<&'a str>,
+    music_dir: Option<Vec<String>>,
+    base: PathBuf,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(songs: &'a [Song], music_dir: Option<&Path>, base: PathBuf) -> Self {
+        Self {
+            songs,
+            uris: songs.iter().map(|s| s.url.as_str()).collect(),
+            music_dir: music_dir.map(components),
+            base,
+        }
+    }
+
+    fn by_path(&self, location: &str) -> Option<String> {
+        let location = location.replace('\\', "/");
+        if self.uris.contains(location.as_str()) {
+            return Some(location);
+        }
+        let path = Path::new(&location);
+        let parts = if path.is_absolute() {
+            components(path)
+        } else {
+            components(&self.base.join(path))
+        };
+        if let Some(music_dir) = &self.music_dir {
+            if let Some(rest) = parts.strip_prefix(music_dir.as_slice()) {
+                let uri = rest.join("/");
+                if self.uris.contains(uri.as_str()) {
+                    return Some(uri);
+                }
+            }
+        }
+        // The file may come from another machine with the library mounted elsewhere.
+        (0..parts.len())
+            .map(|skip| parts[skip..].join("/"))
+            .find(|uri| self.uris.contains(uri.as_str()))
+    }
+
+    fn by_tags(&self, entry: &Entry) -> Option<String> {
+        let title = entry.title.as_deref()?;
+        let candidates = self.songs.iter().filter(|song| {
+            tag_eq(song, &[Tag::Title], title)
+                && entry
+                    .artist
+                    .as_deref()
+                    .is_none_or(|a| tag_eq(song, &[Tag::Artist, Tag::AlbumArtist], a))
+                && entry.duration.is_none_or(|d| {
+                    song.duration.as_ref().is_none_or(|t| {
+                        Duration::new(t.secs, t.nanos).abs_diff(d) <= DURATION_TOLERANCE
+                    })
+                })
+        });
+        let candidates: Vec<&Song> = candidates.collect();
+        let album_match = entry.album.as_deref().and_then(|album| {
+            candidates
+                .iter()
+                .find(|song| tag_eq(song, &[Tag::Album], album))
+        });
+        album_match
+            .or(candidates.first())
+            .map(|song| song.url.clone())
+    }
+
+    /// The URI to add for `entry`, `None` if it isn't in the database or a stream.
+    fn resolve(&self, entry: &Entry) -> Option<String> {
+        if is_stream(&entry.location) {
+            return Some(entry.location.clone());
+        }
+        if is_url(&entry.location) {
+            return self.by_tags(entry);
+        }
+        self.by_path(&entry.location)
+            .or_else(|| self.by_tags(entry))
+    }
+}
+
+/// Import the playlist file at `path`, appending the entries that resolve to the queue or a
+/// stored playlist.
+pub async fn import(
+    client: &Client,
+    library: &Library,
+    music_dir: Option<&Path>,
+    path: &Path,
+    format: PlaylistFormat,
+    target: &PlaylistSource,
+) -> Result<ImportReport, String> {
+    let entries = read_entries(path, format)?;
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let (uris, unmatched) = library.with_songs(|songs| {
+        let resolver = Resolver::new(songs, music_dir, base);
+        let mut uris = Vec::new();
+        let mut unmatched = Vec::new();
+        for (index, entry) in entries.into_iter().enumerate() {
+            match resolver.resolve(&entry) {
+                Some(uri) => uris.push(uri),
+                None => unmatched.push(UnmatchedEntry {
+                    index,
+                    location: entry.location,
+                    title: entry.title,
+                    artist: entry.artist,
+                }),
+            }
+        }
+        (uris, unmatched)
+    });
+    if !uris.is_empty() {
+        match target {
+            PlaylistSource::Queue => {
+                let cmds: Vec<_> = uris.iter().map(|uri| commands::Add::uri(uri)).collect();
+                client.command_list(cmds).await.map(drop)
+            }
+            PlaylistSource::Stored(name) => {
+                let cmds: Vec<_> = uris
+                    .iter()
+                    .map(|uri| commands::AddToPlaylist::new(name, uri))
+                    .collect();
+                client.command_list(cmds).await.map(drop)
+            }
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(ImportReport {
+        added: uris.len(),
+        unmatched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_urls() {
+        assert!(is_url("http://host/stream"));
+        assert!(is_url("file:///music/a.flac"));
+        assert!(!is_url("/music/a.flac"));
+        assert!(!is_url("dir/://odd.mp3"));
+    }
+
+    #[test]
+    fn recognizes_streams() {
+        assert!(is_stream("http://host/stream"));
+        assert!(is_stream("HTTPS://host/stream"));
+        assert!(!is_stream("file:///music/a.flac"));
+        assert!(!is_stream("smb://nas/a.flac"));
+    }
+
+    #[test]
+    fn converts_paths_to_uris_and_back() {
+        assert_eq!(to_uri("/music/a b#1.flac"), "file:///music/a%20b%231.flac");
+        assert_eq!(to_uri("http://host/a b"), "http://host/a b");
+        assert_eq!(to_uri("dir/a b.mp3"), "dir/a%20b.mp3");
+        assert_eq!(
+            from_uri("file:///music/a%20b%231.flac"),
+            "/music/a b#1.flac"
+        );
+        assert_eq!(from_uri("file://localhost/music/a.flac"), "/music/a.flac");
+        assert_eq!(from_uri("file:///C:/Music/a.flac"), "C:/Music/a.flac");
+        assert_eq!(from_uri("http://host/a%20b"), "http://host/a%20b");
+    }
+
+    fn song(url: &str, tags: &[(Tag, &str)]) -> Song {
+        Song {
+            url: url.to_string(),
+            duration: None,
+            tags: tags
+                .iter()
+                .map(|(tag, value)| (tag.to_string(), vec![value.to_string()]))
+                .collect(),
+            format: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn resolves_file_uris_and_streams_only() {
+        let dir = std::env::temp_dir().join(format!("rmpd-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("list.m3u");
+        fs::write(
+            &path,
+            "#EXTM3U\n\
+             file:///music/a%20b.flac\n\
+             file:///elsewhere/music/c.flac\n\
+             http://radio.example/stream\n\
+             #EXTINF:-1,Artist - Song\n\
+             smb://nas/music/d.flac\n\
+             ftp://host/e.flac\n\
+             missing.flac\n",
+        )
+        .unwrap();
+        let entries = read_entries(&path, PlaylistFormat::M3u).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let songs = [
+            song("a b.flac", &[]),
+            song("c.flac", &[]),
+            song("d.flac", &[(Tag::Artist, "Artist"), (Tag::Title, "Song")]),
+        ];
+        let resolver = Resolver::new(&songs, Some(Path::new("/music")), dir);
+        let resolved: Vec<Option<String>> = entries.iter().map(|e| resolver.resolve(e)).collect();
+        assert_eq!(
+            resolved,
+            [
+                Some("a b.flac".to_string()),
+                Some("c.flac".to_string()),
+                Some("http://radio.example/stream".to_string()),
+                // Not a stream, but found by its tags.
+                Some("d.flac".to_string()),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn normalizes_components() {
+        assert_eq!(
+            components(Path::new("/music/./a/../b/c.mp3")),
+            ["music", "b", "c.mp3"]
+        );
+    }
+
+    #[test]
+    fn guesses_format_from_extension() {
+        let format = |p: &str| PlaylistFormat::from_path(Path::new(p));
+        assert_eq!(format("a.M3U8"), Some(PlaylistFormat::M3u8));
+        assert_eq!(format("a.xspf"), Some(PlaylistFormat::Xspf));
+        assert_eq!(format("a.txt"), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! PLS, the INI-style playlist format.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use super::Entry;
+
+/// Parse PLS, refusing files that don't start with the `[playlist]` section.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if !lines
+        .next()
+        .is_some_and(|line| line.eq_ignore_ascii_case("[playlist]"))
+    {
+        return Err("Not a PLS playlist, missing [playlist]".to_string());
+    }
+    let mut entries: BTreeMap<u32, Entry> = BTreeMap::new();
+    for line in lines {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let Ok(number) = key[split..].parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        let value = value.trim().to_string();
+        match &key[..split] {
+            "file" => entry.location = value,
+            "title" => entry.title = Some(value).filter(|t| !t.is_empty()),
+            "length" => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|&l| l >= 0)
+                    .map(|l| Duration::from_secs(l as u64))
+            }
+            _ => {}
+        }
+    }
+    Ok(entries
+        .into_values()
+        .filter(|e| !e.location.is_empty())
+        .collect())
+}
+
+pub fn write(entries: &[Entry]) -> String {
+    let mut out = String::from("[playlist]\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry.location));
+        let title = match (&entry.artist, &entry.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (_, title) => title.clone(),
+        };
+        if let Some(title) = title {
+            out.push_str(&format!("Title{}={}\n", n, title));
+        }
+        let length = entry.duration.map_or(-1, |d| d.as_secs() as i64);
+        out.push_str(&format!("Length{}={}\n", n, length));
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries_in_number_order() {
+        let text = "[Playlist]\nFile2=b.mp3\nfile1=a.mp3\nTitle1=First\nLength1=30\nLength2=-1\n\
+                    NumberOfEntries=2\nVersion=2\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location, "a.mp3");
+        assert_eq!(entries[0].title.as_deref(), Some("First"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(30)));
+        assert_eq!(entries[1].location, "b.mp3");
+        assert!(entries[1].duration.is_none());
+    }
+
+    #[test]
+    fn skips_entries_without_file() {
+        let entries = parse("[playlist]\nTitle1=Nothing\nFile2=b.mp3\n").unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        assert!(parse("root:x:0:0:root:/root:/bin/bash\n").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let entries = vec![Entry {
+            location: "a.mp3".to_string(),
+            title: Some("Title".to_string()),
+            artist: Some("Artist".to_string()),
+            duration: Some(Duration::from_secs(5)),
+            ..Entry::default()
+        }];
+        let text = write(&entries);
+        assert!(text.contains("Title1=Artist - Title\n"));
+        assert!(text.ends_with("NumberOfEntries=1\nVersion=2\n"));
+        let parsed = parse(&text).unwrap();
+        assert_eq!(parsed[0].location, "a.mp3");
+        assert_eq!(parsed[0].duration, Some(Duration::from_secs(5)));
+    }
+}
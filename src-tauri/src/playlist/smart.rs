@@ -0,0 +1,339 @@
+//! Smart playlists: saved filters that are turned into songs on demand.
+//!
+//! Definitions are kept in `smart_playlists.json` in the app config directory. A smart playlist
+//! materialised into a stored MPD playlist remembers it as its target, and targets are rebuilt
+//! whenever the database changes.
+
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use mpd_client::{
+    commands::{self, Command},
+    protocol::{command::Command as RawCommand, response::Frame},
+    responses::{Song, TypedResponseError},
+    Client,
+};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+
+use crate::{ipc::Filter, reflection::tag::Tag};
+
+use super::PlaylistSource;
+
+pub const SMART_PLAYLISTS_FILE: &str = "smart_playlists.json";
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct SmartPlaylist {
+    pub name: String,
+    pub filter: Filter,
+    pub sort: Option<Tag>,
+    pub descending: bool,
+    pub limit: Option<usize>,
+    /// Pick `limit` songs at random instead of the first ones.
+    pub random: bool,
+    /// Stored playlist this one was last materialised into, kept up to date.
+    pub target: Option<String>,
+}
+
+pub struct SmartPlaylists {
+    /// `None` if there's no config directory, in which case definitions aren't persisted.
+    path: Option<PathBuf>,
+    playlists: Mutex<BTreeMap<String, SmartPlaylist>>,
+}
+
+impl SmartPlaylists {
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let path = dir.map(|dir| dir.join(SMART_PLAYLISTS_FILE));
+        let playlists: Vec<SmartPlaylist> = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|raw| {
+                serde_json::from_slice(&raw)
+                    .map_err(|e| log::error!("Failed to load smart playlists: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            playlists: Mutex::new(playlists.into_iter().map(|p| (p.name.clone(), p)).collect()),
+        }
+    }
+
+    fn persist(&self, playlists: &BTreeMap<String, SmartPlaylist>) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let list: Vec<&SmartPlaylist> = playlists.values().collect();
+        let raw = serde_json::to_vec_pretty(&list).map_err(|e| e.to_string())?;
+        fs::write(path, raw).map_err(|e| e.to_string())
+    }
+
+    pub fn list(&self) -> Vec<SmartPlaylist> {
+        self.playlists.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<SmartPlaylist> {
+        self.playlists.lock().unwrap().get(name).cloned()
+    }
+
+    /// Add a smart playlist, replacing the one with the same name.
+    pub fn save(&self, playlist: SmartPlaylist) -> Result<(), String> {
+        let mut playlists = self.playlists.lock().unwrap();
+        playlists.insert(playlist.name.clone(), playlist);
+        self.persist(&playlists)
+    }
+
+    /// Remove a smart playlist, returning whether it existed. Its target is left alone.
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        let mut playlists = self.playlists.lock().unwrap();
+        let existed = playlists.remove(name).is_some();
+        self.persist(&playlists)?;
+        Ok(existed)
+    }
+
+    fn set_target(&self, name: &str, target: Option<String>) -> Result<(), String> {
+        let mut playlists = self.playlists.lock().unwrap();
+        if let Some(playlist) = playlists.get_mut(name) {
+            playlist.target = target;
+        }
+        self.persist(&playlists)
+    }
+}
+
+/// How a smart playlist orders and cuts the songs its filter finds.
+#[derive(Clone, Copy)]
+struct Order {
+    sorted: bool,
+    descending: bool,
+    limit: Option<usize>,
+    random: bool,
+}
+
+impl Order {
+    fn of(playlist: &SmartPlaylist) -> Self {
+        Self {
+            sorted: playlist.sort.is_some(),
+            descending: playlist.descending,
+            limit: playlist.limit,
+            random: playlist.random,
+        }
+    }
+
+    /// Whether the server can apply the limit: it sorts (descending too) before windowing, but
+    /// random picks and reversed database order need every song.
+    fn server_window(self) -> bool {
+        !self.random && (self.sorted || !self.descending)
+    }
+
+    /// Put the songs found, already sorted by the server if sorted at all, into their final
+    /// order and length. `key` is the sort tag's value, to order random picks again.
+    fn arrange<T, K: Ord>(
+        self,
+        mut songs: Vec<T>,
+        key: impl Fn(&T) -> K,
+        rng: &mut impl Rng,
+    ) -> Vec<T> {
+        if self.descending && !self.sorted {
+            songs.reverse();
+        }
+        if self.random {
+            songs.shuffle(rng);
+        }
+        if let Some(limit) = self.limit {
+            songs.truncate(limit);
+        }
+        if self.random && self.sorted {
+            // Sampled songs come back shuffled; restore the requested order.
+            songs.sort_by_key(|s| key(s));
+            if self.descending {
+                songs.reverse();
+            }
+        }
+        songs
+    }
+}
+
+/// Songs of a smart playlist, in their final order.
+pub async fn songs(client: &Client, playlist: &SmartPlaylist) -> Result<Vec<Song>, String> {
+    let Some(filter) = playlist
+        .filter
+        .clone()
+        .render()
+        .map_err(|e| e.to_string())?
+    else {
+        return Err("Empty filter.".to_string());
+    };
+    let order = Order::of(playlist);
+    let mut cmd = commands::Find::new(filter);
+    let tag = match &playlist.sort {
+        Some(tag) => Some(tag.clone().render().map_err(|e| e.to_string())?),
+        None => None,
+    };
+    if let Some(sort) = &playlist.sort {
+        // `-tag` sorts descending, since MPD 0.21.
+        let sort = match playlist.descending {
+            true => format!("-{}", sort),
+            false => sort.to_string(),
+        };
+        cmd = cmd.sort(mpd_client::tag::Tag::try_from(sort.as_str()).map_err(|e| e.to_string())?);
+    }
+    if let (Some(limit), true) = (playlist.limit, order.server_window()) {
+        cmd = cmd.window(0..limit);
+    }
+    let songs = client.command(cmd).await.map_err(|e| e.to_string())?;
+    let key = |s: &Song| tag.as_ref().and_then(|t| s.tags.get(t)).cloned();
+    Ok(order.arrange(songs, key, &mut rand::thread_rng()))
+}
+
+/// One step of rewriting a stored playlist.
+enum Rewrite<'a> {
+    Clear(&'a str),
+    Add(&'a str, &'a str),
+}
+
+impl Command for Rewrite<'_> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        match self {
+            Rewrite::Clear(name) => commands::ClearPlaylist(name).command(),
+            Rewrite::Add(name, uri) => commands::AddToPlaylist::new(name, uri).command(),
+        }
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// Put the songs of `playlist` into the queue (appended) or a stored playlist (replaced).
+///
+/// Returns the number of songs added.
+pub async fn materialize(
+    client: &Client,
+    playlist: &SmartPlaylist,
+    target: &PlaylistSource,
+) -> Result<usize, String> {
+    let songs = songs(client, playlist).await?;
+    match target {
+        PlaylistSource::Queue => {
+            if !songs.is_empty() {
+                let cmds: Vec<_> = songs.iter().map(|s| commands::Add::uri(&s.url)).collect();
+                client.command_list(cmds).await.map_err(|e| e.to_string())?;
+            }
+        }
+        PlaylistSource::Stored(name) => {
+            let exists = client
+                .command(commands::GetPlaylists)
+                .await
+                .map_err(|e| e.to_string())?
+                .iter()
+                .any(|p| &p.name == name);
+            let mut cmds = Vec::with_capacity(songs.len() + 1);
+            if exists {
+                cmds.push(Rewrite::Clear(name));
+            }
+            cmds.extend(songs.iter().map(|s| Rewrite::Add(name, &s.url)));
+            if !cmds.is_empty() {
+                client.command_list(cmds).await.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(songs.len())
+}
+
+/// Materialise the saved smart playlist `name`, remembering a stored playlist target.
+pub async fn materialize_saved(
+    client: &Client,
+    store: &SmartPlaylists,
+    name: &str,
+    target: &PlaylistSource,
+) -> Result<usize, String> {
+    let playlist = store
+        .get(name)
+        .ok_or_else(|| format!("No smart playlist named '{}'", name))?;
+    let count = materialize(client, &playlist, target).await?;
+    if let PlaylistSource::Stored(stored) = target {
+        store.set_target(name, Some(stored.clone()))?;
+    }
+    Ok(count)
+}
+
+/// Rebuild the stored playlists smart playlists were materialised into.
+pub async fn refresh_smart_playlists(handle: AppHandle) {
+    let client = handle.state::<Client>();
+    let store = handle.state::<SmartPlaylists>();
+    for playlist in store.list() {
+        let Some(target) = playlist.target.clone() else {
+            continue;
+        };
+        let target = PlaylistSource::Stored(target);
+        if let Err(e) = materialize(&client, &playlist, &target).await {
+            log::error!(
+                "Failed to refresh smart playlist '{}': {}",
+                playlist.name,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn order(sorted: bool, descending: bool, limit: Option<usize>, random: bool) -> Order {
+        Order {
+            sorted,
+            descending,
+            limit,
+            random,
+        }
+    }
+
+    fn arrange(order: Order, songs: Vec<u32>) -> Vec<u32> {
+        order.arrange(songs, |&s| s, &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn sorted_lists_are_windowed_by_the_server() {
+        assert!(order(true, false, Some(2), false).server_window());
+        assert!(order(true, true, Some(2), false).server_window());
+        assert!(order(false, false, Some(2), false).server_window());
+        assert!(!order(false, true, Some(2), false).server_window());
+        assert!(!order(true, false, Some(2), true).server_window());
+    }
+
+    #[test]
+    fn server_sorted_songs_stay_in_order() {
+        // Top 2 by a descending sort: the server already returned them highest first.
+        assert_eq!(
+            arrange(order(true, true, Some(2), false), vec![9, 7]),
+            [9, 7]
+        );
+    }
+
+    #[test]
+    fn unsorted_descending_takes_the_last_songs() {
+        assert_eq!(
+            arrange(order(false, true, Some(2), false), vec![1, 2, 3, 4]),
+            [4, 3]
+        );
+    }
+
+    #[test]
+    fn random_picks_are_sorted_again() {
+        let songs: Vec<u32> = (0..50).collect();
+        let picked = arrange(order(true, false, Some(10), true), songs.clone());
+        assert_eq!(picked.len(), 10);
+        assert!(picked.windows(2).all(|w| w[0] < w[1]));
+        let picked = arrange(order(true, true, Some(10), true), songs);
+        assert!(picked.windows(2).all(|w| w[0] > w[1]));
+    }
+}
@@ -0,0 +1,135 @@
+//! XSPF, the XML Shareable Playlist Format.
+
+use std::time::Duration;
+
+use quick_xml::{escape::escape, events::Event, Reader};
+
+use super::Entry;
+
+/// Parse XSPF, refusing documents whose root isn't `<playlist>`.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+    let mut entries = Vec::new();
+    let mut track: Option<Entry> = None;
+    let mut element = Vec::new();
+    let mut root = true;
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => {
+                element = e.local_name().as_ref().to_vec();
+                if std::mem::take(&mut root) && element != b"playlist" {
+                    return Err("Not an XSPF playlist, the root isn't <playlist>".to_string());
+                }
+                if element == b"track" {
+                    track = Some(Entry::default());
+                }
+            }
+            Event::Text(t) => {
+                let Some(entry) = track.as_mut() else {
+                    continue;
+                };
+                let text = t.unescape().map_err(|e| e.to_string())?.into_owned();
+                match element.as_slice() {
+                    // Only the first location counts, the others are alternatives.
+                    b"location" if entry.location.is_empty() => entry.location = text,
+                    b"title" => entry.title = Some(text),
+                    b"creator" => entry.artist = Some(text),
+                    b"album" => entry.album = Some(text),
+                    b"duration" => entry.duration = text.parse().ok().map(Duration::from_millis),
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"track" {
+                    entries.extend(track.take().filter(|t| !t.location.is_empty()));
+                }
+                element.clear();
+            }
+            Event::Eof if root => return Err("Not an XSPF playlist, no <playlist>".to_string()),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+pub fn write(entries: &[Entry]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+        "  <trackList>\n"
+    ));
+    for entry in entries {
+        out.push_str("    <track>\n");
+        out.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(&entry.location)
+        ));
+        let fields = [
+            ("title", &entry.title),
+            ("creator", &entry.artist),
+            ("album", &entry.album),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                out.push_str(&format!("      <{0}>{1}</{0}>\n", name, escape(value)));
+            }
+        }
+        if let Some(duration) = entry.duration {
+            out.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                duration.as_millis()
+            ));
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tracks() {
+        let text = r#"<?xml version="1.0"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/"><trackList>
+              <track>
+                <location>file:///music/a.flac</location>
+                <location>http://mirror/a.flac</location>
+                <title>Rock &amp; Roll</title><creator>Artist</creator>
+                <album>Album</album><duration>61500</duration>
+              </track>
+              <track><title>No location</title></track>
+            </trackList></playlist>"#;
+        let entries = parse(text).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].location, "file:///music/a.flac");
+        assert_eq!(entries[0].title.as_deref(), Some("Rock & Roll"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Artist"));
+        assert_eq!(entries[0].album.as_deref(), Some("Album"));
+        assert_eq!(entries[0].duration, Some(Duration::from_millis(61500)));
+    }
+
+    #[test]
+    fn refuses_other_documents() {
+        assert!(parse("root:x:0:0:root:/root:/bin/bash\n").is_err());
+        assert!(parse("<html><body>track</body></html>").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let entries = vec![Entry {
+            location: "file:///music/a%20b.flac".to_string(),
+            title: Some("<Title>".to_string()),
+            duration: Some(Duration::from_millis(1234)),
+            ..Entry::default()
+        }];
+        let parsed = parse(&write(&entries)).unwrap();
+        assert_eq!(parsed[0].location, entries[0].location);
+        assert_eq!(parsed[0].title, entries[0].title);
+        assert_eq!(parsed[0].duration, entries[0].duration);
+    }
+}
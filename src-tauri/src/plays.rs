@@ -0,0 +1,186 @@
+//! Turns player changes into plays of whole songs.
+//!
+//! On every `player` event the current song and state are compared with the previous ones. Time
+//! is counted only while playing, so seeking ahead doesn't count as listening. When the song
+//! changes the previous one is finished: it counts as played once it was listened to for half its
+//! duration or four minutes, whichever comes first, and as skipped otherwise. A song that starts
+//! over with the same queue id, as with `single` and `repeat` on, is finished as well.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use mpd_client::{
+    responses::{PlayState, Song, SongInQueue, Status},
+    Client,
+};
+use tauri::{async_runtime, AppHandle, Manager};
+
+use crate::{
+    history::History,
+    ratings,
+    scrobble::{now_playing, Scrobbler},
+    settings::SettingsStore,
+};
+
+/// Listening time after which any song counts as played.
+const MAX_PLAY_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+
+/// How close to its start a song is when it starts over.
+const RESTART_WINDOW: Duration = Duration::from_secs(5);
+
+/// Listening time needed for a song of `duration` to count as played.
+pub fn play_threshold(duration: Option<Duration>) -> Duration {
+    duration.map_or(MAX_PLAY_THRESHOLD, |d| (d / 2).min(MAX_PLAY_THRESHOLD))
+}
+
+/// Whether playback went back to the start of the song, being at `elapsed` when it should have
+/// been at `expected`.
+fn restarted(expected: Duration, elapsed: Duration) -> bool {
+    elapsed < RESTART_WINDOW && expected >= elapsed + RESTART_WINDOW
+}
+
+/// A song that stopped being the current one.
+#[derive(Clone, Debug)]
+pub struct Play {
+    pub song: Song,
+    pub started: SystemTime,
+    /// Time actually spent playing it.
+    pub listened: Duration,
+    /// Whether it was listened to long enough to count as played.
+    pub completed: bool,
+}
+
+/// What changed since the previous observation.
+#[derive(Default)]
+pub struct PlayChange {
+    /// The song that stopped being the current one.
+    pub finished: Option<Play>,
+    /// The song that became the current one.
+    pub started: Option<Song>,
+}
+
+struct Current {
+    id: u64,
+    song: Song,
+    started: SystemTime,
+    listened: Duration,
+    /// Set while playing, to when time was last added to `listened`.
+    resumed: Option<Instant>,
+    /// Elapsed time at the last observation.
+    position: Duration,
+}
+
+impl Current {
+    /// Where playback should be by `now` if nothing happened since the last observation.
+    fn expected(&self, now: Instant) -> Duration {
+        self.position + self.resumed.map_or(Duration::ZERO, |resumed| now - resumed)
+    }
+
+    fn mark(&mut self, now: Instant) {
+        if let Some(resumed) = self.resumed.take() {
+            self.listened += now - resumed;
+        }
+    }
+
+    fn finish(mut self, now: Instant) -> Play {
+        self.mark(now);
+        let completed = self.listened >= play_threshold(self.song.duration);
+        Play {
+            song: self.song,
+            started: self.started,
+            listened: self.listened,
+            completed,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PlayTracker {
+    current: Mutex<Option<Current>>,
+}
+
+impl PlayTracker {
+    /// Update the tracked song from a `status` and `currentsong` fetched together.
+    pub fn observe(&self, status: &Status, song: Option<SongInQueue>) -> PlayChange {
+        let now = Instant::now();
+        let playing = status.state == PlayState::Playing;
+        let mut change = PlayChange::default();
+        let mut current = self.current.lock().unwrap();
+        let song = song.filter(|_| status.state != PlayState::Stopped);
+        let elapsed = status.elapsed.unwrap_or_default();
+        let same = matches!((&*current, &song), (Some(c), Some(s))
+            if c.id == s.id.0 && !restarted(c.expected(now), elapsed));
+        if !same {
+            change.finished = current.take().map(|previous| previous.finish(now));
+            if let Some(song) = song {
+                change.started = Some(song.song.clone());
+                *current = Some(Current {
+                    id: song.id.0,
+                    song: song.song,
+                    started: SystemTime::now(),
+                    // Whatever played before we saw the song, e.g. when connecting mid-song.
+                    listened: elapsed,
+                    resumed: None,
+                    position: elapsed,
+                });
+            }
+        }
+        if let Some(current) = current.as_mut() {
+            current.mark(now);
+            current.position = elapsed;
+            if playing {
+                current.resumed = Some(now);
+            }
+        }
+        change
+    }
+}
+
+/// Act on songs that started or finished since the last player state.
+pub async fn track_plays(handle: &AppHandle, status: &Status, song: Option<SongInQueue>) {
+    let client = handle.state::<Client>();
+    let change = handle.state::<PlayTracker>().observe(status, song);
+    let profile = handle.state::<SettingsStore>().get().active_server();
+    if let Some(play) = change.finished {
+        let entry = handle.state::<History>().record(&play, &profile.name);
+        if let Err(e) = handle.emit_all("history", entry) {
+            log::error!("Failed to emit signal 'history': {}", e);
+        }
+        if profile.scrobble {
+            handle.state::<Scrobbler>().scrobble(handle, &play);
+        }
+        if let Err(e) = ratings::record_play(&client, &play).await {
+            log::error!("Failed to record play of '{}': {}", play.song.url, e);
+        }
+    }
+    if let (Some(song), true) = (change.started, profile.scrobble) {
+        async_runtime::spawn(now_playing(handle.clone(), song));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn threshold_is_half_the_song_up_to_four_minutes() {
+        assert_eq!(play_threshold(Some(secs(100))), secs(50));
+        assert_eq!(play_threshold(Some(secs(3600))), MAX_PLAY_THRESHOLD);
+        assert_eq!(play_threshold(None), MAX_PLAY_THRESHOLD);
+    }
+
+    #[test]
+    fn going_back_to_the_start_is_a_restart() {
+        assert!(restarted(secs(180), secs(1)));
+        assert!(restarted(secs(7), Duration::ZERO));
+        // Small corrections and seeks within the song are not.
+        assert!(!restarted(secs(3), secs(1)));
+        assert!(!restarted(secs(180), secs(60)));
+    }
+}
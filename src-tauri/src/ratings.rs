@@ -0,0 +1,167 @@
+//! Ratings and play counts stored as song stickers, so other clients see them too.
+//!
+//! Sticker names and values:
+//!
+//! - `rating`: `1` to `10`, half stars on a five star scale; no sticker means unrated.
+//! - `playcount`: number of times the song was played.
+//! - `skipcount`: number of times it was skipped before counting as played.
+//! - `lastplayed`: when it was last played, in seconds since the Unix epoch.
+
+use std::{collections::HashMap, time::UNIX_EPOCH};
+
+use mpd_client::{commands, Client};
+
+use crate::{
+    extension::{DeleteSticker, ListStickers, SetSticker, StickerType},
+    ipc::Filter,
+    library::Library,
+    plays::Play,
+    reflection::{
+        request::NumericComparison,
+        response::{RatedSong, SongStats},
+        Reflect,
+    },
+    stickers,
+};
+
+pub const RATING: &str = "rating";
+pub const PLAY_COUNT: &str = "playcount";
+pub const SKIP_COUNT: &str = "skipcount";
+pub const LAST_PLAYED: &str = "lastplayed";
+
+pub const MAX_RATING: u8 = 10;
+
+async fn stickers(client: &Client, uri: &str) -> Result<HashMap<String, String>, String> {
+    client
+        .command(ListStickers::new(&StickerType::Song, uri))
+        .await
+        .map(|stickers| stickers.into_iter().collect())
+        .map_err(|e| e.to_string())
+}
+
+fn number<T: std::str::FromStr>(stickers: &HashMap<String, String>, name: &str) -> Option<T> {
+    stickers.get(name).and_then(|v| v.trim().parse().ok())
+}
+
+/// Rate a song from `1` to `MAX_RATING`, or clear its rating with `0`.
+pub async fn rate(client: &Client, uri: &str, rating: u8) -> Result<(), String> {
+    if rating > MAX_RATING {
+        return Err(format!("Rating must be between 0 and {}", MAX_RATING));
+    }
+    if rating == 0 {
+        if stickers(client, uri).await?.contains_key(RATING) {
+            client
+                .command(DeleteSticker::new(&StickerType::Song, uri, Some(RATING)))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+    client
+        .command(SetSticker::new(
+            &StickerType::Song,
+            uri,
+            RATING,
+            &rating.to_string(),
+        ))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn song_stats(client: &Client, uri: &str) -> Result<SongStats, String> {
+    let stickers = stickers(client, uri).await?;
+    Ok(SongStats {
+        rating: number(&stickers, RATING),
+        play_count: number(&stickers, PLAY_COUNT).unwrap_or(0),
+        skip_count: number(&stickers, SKIP_COUNT).unwrap_or(0),
+        last_played: number(&stickers, LAST_PLAYED),
+    })
+}
+
+/// Bump the play or skip count of a finished song.
+pub async fn record_play(client: &Client, play: &Play) -> Result<(), String> {
+    let uri = play.song.url.as_str();
+    let stickers = stickers(client, uri).await?;
+    let song = StickerType::Song;
+    if play.completed {
+        let count = (number::<u64>(&stickers, PLAY_COUNT).unwrap_or(0) + 1).to_string();
+        let last_played = (play.started + play.listened)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        client
+            .command_list((
+                SetSticker::new(&song, uri, PLAY_COUNT, &count),
+                SetSticker::new(&song, uri, LAST_PLAYED, &last_played),
+            ))
+            .await
+            .map_err(|e| e.to_string())?;
+    } else {
+        let count = (number::<u64>(&stickers, SKIP_COUNT).unwrap_or(0) + 1).to_string();
+        client
+            .command(SetSticker::new(&song, uri, SKIP_COUNT, &count))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Rated songs matching `filter` (or the whole library), best rated first.
+pub async fn top_rated(
+    client: &Client,
+    library: &Library,
+    filter: Option<Filter>,
+    limit: usize,
+) -> Result<Vec<RatedSong>, String> {
+    let ratings: HashMap<String, u8> = stickers::find_numbers(
+        client,
+        &StickerType::Song,
+        "",
+        RATING,
+        Some((NumericComparison::Gt, 0)),
+        None,
+        None,
+    )
+    .await?
+    .into_iter()
+    .filter_map(|(uri, rating)| Some((uri, u8::try_from(rating).ok()?)))
+    .collect();
+    let filter = match filter {
+        Some(filter) => filter.render().map_err(|e| e.to_string())?,
+        None => None,
+    };
+    let mut rated: Vec<RatedSong> = match filter {
+        Some(filter) => client
+            .command(commands::Find::new(filter))
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter_map(|song| {
+                let rating = *ratings.get(&song.url)?;
+                Some(RatedSong {
+                    song: song.reflect(),
+                    rating,
+                })
+            })
+            .collect(),
+        None => library.with_songs(|songs| {
+            songs
+                .iter()
+                .filter_map(|song| {
+                    Some(RatedSong {
+                        rating: *ratings.get(&song.url)?,
+                        song: song.clone(),
+                    })
+                })
+                .collect()
+        }),
+    };
+    rated.sort_by(|a, b| {
+        b.rating
+            .cmp(&a.rating)
+            .then_with(|| a.song.url.cmp(&b.song.url))
+    });
+    rated.truncate(limit);
+    Ok(rated)
+}
@@ -4,7 +4,9 @@ use mpd_client::commands;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
-use super::{response::TVal, Reflect};
+use crate::extension;
+
+use super::{response::TVal, tag::Tag, Reflect};
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub enum SingleMode {
@@ -50,3 +52,89 @@ impl From<SeekMode> for mpd_client::commands::SeekMode {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub enum StickerType {
+    Song,
+    Playlist,
+    /// Values of a tag, e.g. albums by name (MPD 0.24+).
+    Tag(Tag),
+}
+
+impl From<StickerType> for extension::StickerType {
+    fn from(value: StickerType) -> Self {
+        match value {
+            StickerType::Song => extension::StickerType::Song,
+            StickerType::Playlist => extension::StickerType::Playlist,
+            StickerType::Tag(tag) => extension::StickerType::Tag(tag.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug)]
+pub enum StickerOperator {
+    Eq,
+    Gt,
+    Lt,
+    IntEq,
+    IntGt,
+    IntLt,
+    Contains,
+    StartsWith,
+}
+
+impl From<StickerOperator> for extension::StickerOperator {
+    fn from(value: StickerOperator) -> Self {
+        match value {
+            StickerOperator::Eq => extension::StickerOperator::Eq,
+            StickerOperator::Gt => extension::StickerOperator::Gt,
+            StickerOperator::Lt => extension::StickerOperator::Lt,
+            StickerOperator::IntEq => extension::StickerOperator::IntEq,
+            StickerOperator::IntGt => extension::StickerOperator::IntGt,
+            StickerOperator::IntLt => extension::StickerOperator::IntLt,
+            StickerOperator::Contains => extension::StickerOperator::Contains,
+            StickerOperator::StartsWith => extension::StickerOperator::StartsWith,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug)]
+pub enum StickerSort {
+    Uri,
+    Value,
+    ValueInt,
+}
+
+impl From<StickerSort> for extension::StickerSort {
+    fn from(value: StickerSort) -> Self {
+        match value {
+            StickerSort::Uri => extension::StickerSort::Uri,
+            StickerSort::Value => extension::StickerSort::Value,
+            StickerSort::ValueInt => extension::StickerSort::ValueInt,
+        }
+    }
+}
+
+/// Comparison of numeric sticker values, done locally so it works with any MPD version.
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericComparison {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl NumericComparison {
+    pub fn matches(self, value: i64, operand: i64) -> bool {
+        match self {
+            NumericComparison::Eq => value == operand,
+            NumericComparison::Ne => value != operand,
+            NumericComparison::Gt => value > operand,
+            NumericComparison::Ge => value >= operand,
+            NumericComparison::Lt => value < operand,
+            NumericComparison::Le => value <= operand,
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use std::{collections::HashMap, time::Duration, usize};
+use std::{collections::HashMap, time::Duration};
 
-use bytes::BytesMut;
 use mpd_client::responses;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::extension;
+
 use super::{request::SingleMode, Reflect};
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
@@ -30,22 +31,22 @@ impl From<TVal> for Duration {
 }
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
-pub struct AlbumArt {
-    pub size: usize,
-    pub mime: Option<String>,
-    #[specta(type = Vec<u8>)]
-    pub data: BytesMut,
+pub enum BatchResult {
+    Done,
+    Id(u64),
 }
 
-impl Reflect for responses::AlbumArt {
-    type Output = AlbumArt;
-    fn reflect(self) -> Self::Output {
-        Self::Output {
-            size: self.size,
-            mime: self.mime,
-            data: self.data,
-        }
-    }
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct BatchError {
+    pub index: u64,
+    pub code: u64,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct BatchResponse {
+    pub results: Vec<BatchResult>,
+    pub error: Option<BatchError>,
 }
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
@@ -84,6 +85,45 @@ impl<const N: usize> Reflect for responses::List<N> {
     }
 }
 
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Mount {
+    pub mount: String,
+    pub storage: Option<String>,
+}
+
+impl Reflect for extension::Mount {
+    type Output = Mount;
+    fn reflect(self) -> Self::Output {
+        Mount {
+            mount: self.mount,
+            storage: self.storage,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Neighbor {
+    pub neighbor: String,
+    pub name: Option<String>,
+}
+
+impl Reflect for extension::Neighbor {
+    type Output = Neighbor;
+    fn reflect(self) -> Self::Output {
+        Neighbor {
+            neighbor: self.neighbor,
+            name: self.name,
+        }
+    }
+}
+
+/// A song of a stored playlist together with its position in it.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct PlaylistMatch {
+    pub position: usize,
+    pub song: Song,
+}
+
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
 pub struct Playlist {
     pub name: String,
@@ -256,39 +296,76 @@ impl Reflect for responses::Status {
 }
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
-pub struct StickerFind {
-    pub value: HashMap<String, String>,
+pub struct Album {
+    /// Stable identifier of the album, as listed in [`Artist::albums`].
+    pub id: String,
+    pub title: String,
+    pub title_sort: String,
+    /// Album artist, `Various Artists` for compilations.
+    pub artist: String,
+    pub artist_sort: String,
+    pub compilation: bool,
+    pub date: Option<String>,
+    pub discs: u32,
+    /// Tracks ordered by disc and track number.
+    pub tracks: Vec<Song>,
+    pub duration: TVal,
+    /// Song URI to request the cover with through `mpdart://`.
+    pub cover_key: String,
+    pub genres: Vec<String>,
 }
 
-impl Reflect for responses::StickerFind {
-    type Output = StickerFind;
-    fn reflect(self) -> Self::Output {
-        StickerFind { value: self.value }
-    }
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct Artist {
+    pub name: String,
+    pub sort_name: String,
+    /// Ids of the albums the artist appears on.
+    pub albums: Vec<String>,
+    pub song_count: usize,
 }
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
-pub struct StickerGet {
-    pub value: String,
+pub struct ImportReport {
+    pub added: usize,
+    pub unmatched: Vec<UnmatchedEntry>,
 }
 
-impl Reflect for responses::StickerGet {
-    type Output = StickerGet;
-    fn reflect(self) -> Self::Output {
-        StickerGet { value: self.value }
-    }
+/// A playlist file entry that matched no song in the database.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct UnmatchedEntry {
+    /// Position of the entry in the file.
+    pub index: usize,
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
 }
 
+/// A song with its `rating` sticker.
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
-pub struct StickerList {
-    pub value: HashMap<String, String>,
+pub struct RatedSong {
+    pub song: Song,
+    pub rating: u8,
 }
 
-impl Reflect for responses::StickerList {
-    type Output = StickerList;
-    fn reflect(self) -> Self::Output {
-        StickerList { value: self.value }
-    }
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct SearchHit {
+    pub song: Song,
+    pub score: f32,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct SongStats {
+    pub rating: Option<u8>,
+    pub play_count: u64,
+    pub skip_count: u64,
+    /// Seconds since the Unix epoch.
+    pub last_played: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct UpdateJob {
+    pub job: u64,
+    pub elapsed: TVal,
 }
 
 #[derive(Serialize, Deserialize, Type, Clone, Debug)]
@@ -0,0 +1,62 @@
+//! Path checks for files the backend hands to the webview.
+//!
+//! The webview has no filesystem access of its own; covers (and anything else read from the
+//! music directory) are looked up from song URIs, which must not be able to point outside it.
+//! Files the user imports or exports are named relative to directories the app owns.
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Join a relative, MPD-style `uri` onto `root`, refusing absolute paths and `..` components.
+pub fn join_within(root: &Path, uri: &str) -> Option<PathBuf> {
+    let relative = Path::new(uri);
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        .then(|| root.join(relative))
+}
+
+/// Whether `path` lies inside `root` once symlinks are resolved.
+pub fn is_within(path: &Path, root: &Path) -> bool {
+    match (path.canonicalize(), root.canonicalize()) {
+        (Ok(path), Ok(root)) => path.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Resolve `name` to a file directly inside `dir`, a directory the app owns, creating it if
+/// needed. Only plain file names are accepted, and existing files must not lead out of `dir`.
+pub fn file_within(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let mut components = Path::new(name).components();
+    let (Some(Component::Normal(file)), None) = (components.next(), components.next()) else {
+        return Err(format!("Not a plain file name: {}", name));
+    };
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let path = dir.join(file);
+    if path.symlink_metadata().is_ok() && !is_within(&path, dir) {
+        return Err(format!("{} leads outside {}", name, dir.display()));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_within_takes_plain_names_only() {
+        let dir = std::env::temp_dir().join(format!("rmpd-scope-{}", std::process::id()));
+        assert_eq!(file_within(&dir, "a.m3u").unwrap(), dir.join("a.m3u"));
+        for name in ["../a.m3u", "/etc/passwd", "sub/a.m3u", "", ".", ".."] {
+            assert!(file_within(&dir, name).is_err(), "{}", name);
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc/passwd", dir.join("link.m3u")).unwrap();
+            assert!(file_within(&dir, "link.m3u").is_err());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
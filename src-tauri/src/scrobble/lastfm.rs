@@ -0,0 +1,159 @@
+//! Last.fm scrobbling API 2.0, also spoken by self-hosted servers.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+use tauri::api::http::{Body, ClientBuilder, FormBody, FormPart, HttpRequestBuilder, ResponseType};
+
+use crate::{credentials::Credentials, settings::LastFmSettings};
+
+use super::{Listen, SubmitError, REQUEST_TIMEOUT};
+
+/// Error codes meaning the call may succeed later: the session key is invalid or was revoked,
+/// the service is offline or temporarily unavailable, or the rate limit was exceeded.
+const RETRY_CODES: [i64; 4] = [9, 11, 16, 29];
+
+/// Sign `params` as the API requires: the hex MD5 of all `name` `value` pairs sorted by name,
+/// followed by the secret.
+fn sign(params: &BTreeMap<&str, String>, secret: &str) -> String {
+    let mut raw = String::new();
+    for (name, value) in params {
+        raw.push_str(name);
+        raw.push_str(value);
+    }
+    raw.push_str(secret);
+    format!("{:x}", md5::compute(raw))
+}
+
+async fn call(
+    settings: &LastFmSettings,
+    secret: &str,
+    mut params: BTreeMap<&str, String>,
+) -> Result<Value, SubmitError> {
+    params.insert("api_key", settings.api_key.clone());
+    let signature = sign(&params, secret);
+    let mut form: HashMap<String, FormPart> = params
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), FormPart::Text(value)))
+        .collect();
+    form.insert("api_sig".to_string(), FormPart::Text(signature));
+    form.insert("format".to_string(), FormPart::Text("json".to_string()));
+
+    let retry = |e: tauri::api::Error| SubmitError::Retry(e.to_string());
+    let client = ClientBuilder::new().build().map_err(retry)?;
+    let request = HttpRequestBuilder::new("POST", &settings.api_url)
+        .map_err(retry)?
+        .body(Body::Form(FormBody::new(form)))
+        .timeout(REQUEST_TIMEOUT)
+        .response_type(ResponseType::Json);
+    let response = client.send(request).await.map_err(retry)?;
+    let status = response.status().as_u16();
+    let data = response.read().await.map_err(retry)?.data;
+    if let Some(code) = data.get("error").and_then(Value::as_i64) {
+        let message = data
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        let message = format!("Last.fm error {}: {}", code, message);
+        return Err(if RETRY_CODES.contains(&code) {
+            SubmitError::Retry(message)
+        } else {
+            SubmitError::Rejected(message)
+        });
+    }
+    if status >= 400 {
+        return Err(SubmitError::Retry(format!(
+            "Last.fm returned HTTP {}",
+            status
+        )));
+    }
+    Ok(data)
+}
+
+/// Scrobble `listen`, or announce it as now playing.
+pub async fn submit(
+    settings: &LastFmSettings,
+    credentials: &Credentials,
+    listen: &Listen,
+    now_playing: bool,
+) -> Result<(), SubmitError> {
+    let Some(session) = &credentials.lastfm_session else {
+        return Err(SubmitError::Retry("Not logged in to Last.fm".to_string()));
+    };
+    let mut params = BTreeMap::new();
+    let method = if now_playing {
+        "track.updateNowPlaying"
+    } else {
+        params.insert("timestamp", listen.timestamp.to_string());
+        "track.scrobble"
+    };
+    params.insert("method", method.to_string());
+    params.insert("sk", session.key.clone());
+    params.insert("artist", listen.artist.clone());
+    params.insert("track", listen.track.clone());
+    let optional = [
+        ("album", listen.album.clone()),
+        ("albumArtist", listen.album_artist.clone()),
+        ("duration", listen.duration.map(|d| d.to_string())),
+        ("trackNumber", listen.track_number.map(|n| n.to_string())),
+        ("mbid", listen.recording_mbid.clone()),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            params.insert(name, value);
+        }
+    }
+    let data = call(settings, &credentials.lastfm_secret, params).await?;
+    let ignored = data
+        .pointer("/scrobbles/@attr/ignored")
+        .and_then(|v| v.as_i64().or_else(|| v.as_str()?.parse().ok()))
+        .unwrap_or(0);
+    if ignored > 0 {
+        let reason = data
+            .pointer("/scrobbles/scrobble/ignoredMessage/#text")
+            .and_then(Value::as_str)
+            .unwrap_or("ignored");
+        return Err(SubmitError::Rejected(reason.to_string()));
+    }
+    Ok(())
+}
+
+/// Exchange a username and password for a session key with `auth.getMobileSession`.
+pub async fn login(
+    settings: &LastFmSettings,
+    secret: &str,
+    username: &str,
+    password: &str,
+) -> Result<String, String> {
+    let params = BTreeMap::from([
+        ("method", "auth.getMobileSession".to_string()),
+        ("username", username.to_string()),
+        ("password", password.to_string()),
+    ]);
+    let data = call(settings, secret, params)
+        .await
+        .map_err(|e| e.message().to_string())?;
+    data.pointer("/session/key")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| "Last.fm returned no session key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_sorted_params_with_the_secret() {
+        let params = BTreeMap::from([
+            ("method", "auth.getMobileSession".to_string()),
+            ("api_key", "key".to_string()),
+        ]);
+        // MD5 of "api_keykeymethodauth.getMobileSessionsecret".
+        assert_eq!(sign(&params, "secret"), "018322def6bdaf0b7eba8f03ac376100");
+        assert_eq!(
+            sign(&BTreeMap::new(), "message digest"),
+            "f96b697d7cb7938d525a2f31aaf161d0"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! ListenBrainz `submit-listens` API.
+
+use serde_json::{json, Map, Value};
+use tauri::api::http::{Body, ClientBuilder, HttpRequestBuilder, ResponseType};
+
+use crate::settings::ListenBrainzSettings;
+
+use super::{Listen, SubmitError, REQUEST_TIMEOUT};
+
+fn payload(listen: &Listen, now_playing: bool) -> Value {
+    let mut info = Map::new();
+    info.insert("media_player".to_string(), "rmpd".into());
+    info.insert("submission_client".to_string(), "rmpd".into());
+    info.insert(
+        "submission_client_version".to_string(),
+        env!("CARGO_PKG_VERSION").into(),
+    );
+    if let Some(duration) = listen.duration {
+        info.insert("duration_ms".to_string(), (duration * 1000).into());
+    }
+    if let Some(number) = listen.track_number {
+        info.insert("tracknumber".to_string(), number.into());
+    }
+    if let Some(mbid) = &listen.recording_mbid {
+        info.insert("recording_mbid".to_string(), mbid.clone().into());
+    }
+    let mut metadata = json!({
+        "artist_name": listen.artist,
+        "track_name": listen.track,
+        "additional_info": info,
+    });
+    if let Some(album) = &listen.album {
+        metadata["release_name"] = album.clone().into();
+    }
+    let mut entry = json!({ "track_metadata": metadata });
+    if !now_playing {
+        entry["listened_at"] = listen.timestamp.into();
+    }
+    json!({
+        "listen_type": if now_playing { "playing_now" } else { "single" },
+        "payload": [entry],
+    })
+}
+
+/// Submit `listen`, or announce it as now playing.
+pub async fn submit(
+    settings: &ListenBrainzSettings,
+    token: &str,
+    listen: &Listen,
+    now_playing: bool,
+) -> Result<(), SubmitError> {
+    let url = format!(
+        "{}/1/submit-listens",
+        settings.api_url.trim_end_matches('/')
+    );
+    let retry = |e: tauri::api::Error| SubmitError::Retry(e.to_string());
+    let client = ClientBuilder::new().build().map_err(retry)?;
+    let request = HttpRequestBuilder::new("POST", url)
+        .map_err(retry)?
+        .header("Authorization", format!("Token {}", token))
+        .map_err(retry)?
+        .body(Body::Json(payload(listen, now_playing)))
+        .timeout(REQUEST_TIMEOUT)
+        .response_type(ResponseType::Text);
+    let response = client.send(request).await.map_err(retry)?;
+    let status = response.status().as_u16();
+    match status {
+        200..=299 => Ok(()),
+        // A bad token can be replaced, after which the listen can still go through.
+        401 | 429 | 500.. => Err(SubmitError::Retry(format!(
+            "ListenBrainz returned HTTP {}",
+            status
+        ))),
+        _ => {
+            let body = response
+                .read()
+                .await
+                .ok()
+                .and_then(|r| r.data.as_str().map(String::from))
+                .unwrap_or_default();
+            Err(SubmitError::Rejected(format!(
+                "ListenBrainz returned HTTP {}: {}",
+                status, body
+            )))
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Scrobbling to Last.fm compatible APIs and ListenBrainz.
+//!
+//! A finished song is scrobbled when it counted as played (half its duration or four minutes, see
+//! [`crate::plays`]) and is longer than 30 seconds. Scrobbles go through a queue persisted in
+//! `scrobbles.json` in the app data directory, so listens made while offline or while a service
+//! is down are submitted once it is reachable again. Now-playing notifications are sent right
+//! away and dropped on failure. Secrets are kept apart from the settings, see [`crate::credentials`].
+
+mod lastfm;
+mod listenbrainz;
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mpd_client::responses::Song;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    credentials::{CredentialStore, Credentials},
+    plays::Play,
+    settings::{ScrobbleSettings, SettingsStore},
+};
+
+pub use lastfm::login as lastfm_login;
+
+pub const SCROBBLES_FILE: &str = "scrobbles.json";
+/// Songs this short are never scrobbled.
+const MIN_DURATION: Duration = Duration::from_secs(30);
+/// How long to wait before retrying after a failed submission.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrobbleService {
+    LastFm,
+    ListenBrainz,
+}
+
+impl ScrobbleService {
+    const ALL: [ScrobbleService; 2] = [ScrobbleService::LastFm, ScrobbleService::ListenBrainz];
+
+    fn enabled(self, settings: &ScrobbleSettings, credentials: &Credentials) -> bool {
+        match self {
+            ScrobbleService::LastFm => {
+                settings.lastfm.enabled
+                    && !settings.lastfm.api_key.is_empty()
+                    && credentials.lastfm_session.is_some()
+            }
+            ScrobbleService::ListenBrainz => {
+                settings.listenbrainz.enabled && !credentials.listenbrainz_token.is_empty()
+            }
+        }
+    }
+}
+
+/// What is sent about a song.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Listen {
+    pub artist: String,
+    pub track: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub duration: Option<u64>,
+    pub track_number: Option<u64>,
+    pub recording_mbid: Option<String>,
+    /// When the song started, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Listen {
+    /// Listens need an artist and a title; other songs can't be scrobbled.
+    fn new(song: &Song, started: SystemTime) -> Option<Self> {
+        let track = song.title()?.to_string();
+        let artist = song.artists().join(", ");
+        if artist.is_empty() {
+            return None;
+        }
+        let mbid = mpd_client::tag::Tag::MusicBrainzRecordingId;
+        Some(Self {
+            artist,
+            track,
+            album: song.album().map(String::from),
+            album_artist: song.album_artists().first().cloned(),
+            duration: song.duration.map(|d| d.as_secs()),
+            track_number: Some(song.number().1).filter(|&n| n > 0),
+            recording_mbid: song.tags.get(&mbid).and_then(|v| v.first()).cloned(),
+            timestamp: started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+    }
+}
+
+/// Why a submission failed.
+pub enum SubmitError {
+    /// Worth trying again later, e.g. the service is unreachable.
+    Retry(String),
+    /// The service refused this listen; it is dropped.
+    Rejected(String),
+}
+
+impl SubmitError {
+    fn message(&self) -> &str {
+        match self {
+            SubmitError::Retry(message) | SubmitError::Rejected(message) => message,
+        }
+    }
+}
+
+async fn submit(
+    service: ScrobbleService,
+    (settings, credentials): (&ScrobbleSettings, &Credentials),
+    listen: &Listen,
+    now_playing: bool,
+) -> Result<(), SubmitError> {
+    match service {
+        ScrobbleService::LastFm => {
+            lastfm::submit(&settings.lastfm, credentials, listen, now_playing).await
+        }
+        ScrobbleService::ListenBrainz => {
+            let token = &credentials.listenbrainz_token;
+            listenbrainz::submit(&settings.listenbrainz, token, listen, now_playing).await
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Pending {
+    service: ScrobbleService,
+    listen: Listen,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct ScrobbleStatus {
+    /// Scrobbles waiting to be submitted.
+    pub pending: usize,
+    pub last_error: Option<String>,
+    /// User logged in to Last.fm.
+    pub lastfm_user: Option<String>,
+    pub lastfm_secret_set: bool,
+    pub listenbrainz_token_set: bool,
+}
+
+pub struct Scrobbler {
+    /// `None` if there's no data directory, in which case the queue isn't persisted.
+    file: Option<PathBuf>,
+    queue: Mutex<VecDeque<Pending>>,
+    last_error: Mutex<Option<String>>,
+    wake: Notify,
+}
+
+impl Scrobbler {
+    pub fn open(dir: Option<PathBuf>) -> Self {
+        let file = dir.map(|dir| dir.join(SCROBBLES_FILE));
+        let queue = file
+            .as_ref()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|raw| {
+                serde_json::from_slice(&raw)
+                    .map_err(|e| log::error!("Failed to load the scrobble queue: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            file,
+            queue: Mutex::new(queue),
+            last_error: Mutex::new(None),
+            wake: Notify::new(),
+        }
+    }
+
+    fn persist(&self, queue: &VecDeque<Pending>) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let saved = fs::create_dir_all(file.parent().unwrap_or(file))
+            .and_then(|_| fs::write(file, serde_json::to_vec(queue)?));
+        if let Err(e) = saved {
+            log::error!("Failed to save the scrobble queue: {}", e);
+        }
+    }
+
+    pub fn status(&self, credentials: &Credentials) -> ScrobbleStatus {
+        ScrobbleStatus {
+            pending: self.queue.lock().unwrap().len(),
+            last_error: self.last_error.lock().unwrap().clone(),
+            lastfm_user: credentials
+                .lastfm_session
+                .as_ref()
+                .map(|s| s.username.clone()),
+            lastfm_secret_set: !credentials.lastfm_secret.is_empty(),
+            listenbrainz_token_set: !credentials.listenbrainz_token.is_empty(),
+        }
+    }
+
+    /// Queue a finished song for every enabled service if it counts as a scrobble.
+    pub fn scrobble(&self, handle: &AppHandle, play: &Play) {
+        if !play.completed || play.song.duration.is_some_and(|d| d <= MIN_DURATION) {
+            return;
+        }
+        let Some(listen) = Listen::new(&play.song, play.started) else {
+            return;
+        };
+        let settings = handle.state::<SettingsStore>().get().scrobbling;
+        let credentials = handle.state::<CredentialStore>().get();
+        let mut queue = self.queue.lock().unwrap();
+        for service in ScrobbleService::ALL {
+            if service.enabled(&settings, &credentials) {
+                queue.push_back(Pending {
+                    service,
+                    listen: listen.clone(),
+                });
+            }
+        }
+        self.persist(&queue);
+        drop(queue);
+        self.flush();
+    }
+
+    /// Have the background task submit the queue now.
+    pub fn flush(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Submit queued scrobbles in order, stopping at the first one worth retrying.
+    async fn submit_pending(
+        &self,
+        settings: &ScrobbleSettings,
+        credentials: &Credentials,
+    ) -> Option<String> {
+        let mut error = None;
+        // Services that failed this round; their scrobbles wait while the others go through.
+        let mut down = Vec::new();
+        let mut index = 0;
+        loop {
+            let Some(pending) = self.queue.lock().unwrap().get(index).cloned() else {
+                break;
+            };
+            // Services that were disabled keep their scrobbles until they are enabled again.
+            if !pending.service.enabled(settings, credentials) || down.contains(&pending.service) {
+                index += 1;
+                continue;
+            }
+            let config = (settings, credentials);
+            match submit(pending.service, config, &pending.listen, false).await {
+                Ok(()) => {}
+                Err(SubmitError::Rejected(message)) => {
+                    log::error!(
+                        "{:?} rejected a scrobble of '{}': {}",
+                        pending.service,
+                        pending.listen.track,
+                        message
+                    );
+                    error = Some(message);
+                }
+                Err(e) => {
+                    error = Some(e.message().to_string());
+                    down.push(pending.service);
+                    index += 1;
+                    continue;
+                }
+            }
+            let mut queue = self.queue.lock().unwrap();
+            queue.remove(index);
+            self.persist(&queue);
+        }
+        error
+    }
+}
+
+fn emit_status(handle: &AppHandle) {
+    let credentials = handle.state::<CredentialStore>().get();
+    let status = handle.state::<Scrobbler>().status(&credentials);
+    if let Err(e) = handle.emit_all("scrobble", status) {
+        log::error!("Failed to emit signal 'scrobble': {}", e);
+    }
+}
+
+/// Tell the enabled services about the song that started playing.
+pub async fn now_playing(handle: AppHandle, song: Song) {
+    let Some(listen) = Listen::new(&song, SystemTime::now()) else {
+        return;
+    };
+    let settings = handle.state::<SettingsStore>().get().scrobbling;
+    let credentials = handle.state::<CredentialStore>().get();
+    for service in ScrobbleService::ALL {
+        if service.enabled(&settings, &credentials) {
+            if let Err(e) = submit(service, (&settings, &credentials), &listen, true).await {
+                log::warn!(
+                    "Failed to send now playing to {:?}: {}",
+                    service,
+                    e.message()
+                );
+            }
+        }
+    }
+}
+
+/// Submit the queue whenever scrobbles are added, retrying every few minutes while it isn't
+/// empty. Emits `scrobble` with the queue status after every attempt.
+pub async fn scrobble_task(handle: AppHandle) {
+    let scrobbler = handle.state::<Scrobbler>();
+    loop {
+        let settings = handle.state::<SettingsStore>().get().scrobbling;
+        let credentials = handle.state::<CredentialStore>().get();
+        let error = scrobbler.submit_pending(&settings, &credentials).await;
+        *scrobbler.last_error.lock().unwrap() = error;
+        emit_status(&handle);
+        let _ = tokio::time::timeout(RETRY_INTERVAL, scrobbler.wake.notified()).await;
+    }
+}
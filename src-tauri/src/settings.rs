@@ -0,0 +1,421 @@
+//! Persistent application settings.
+//!
+//! Settings live in `settings.json` in the app config directory. The file carries a `version` so
+//! older layouts can be upgraded on load; fields missing from the file take their defaults.
+//! Changes go through [`SettingsStore::update`], which saves them and wakes
+//! [`watch_settings`] to reconfigure the backend and emit `settings-changed`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::{
+    art::{cache::DEFAULT_CACHE_LIMIT, cover::CoverResolver, ArtCache},
+    clock::{PlaybackClock, DEFAULT_ELAPSED_INTERVAL},
+    credentials::{CredentialStore, Credentials},
+};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+/// Layout version written by this build.
+pub const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ServerProfile {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    /// Whether a password is stored for this server in the [`CredentialStore`]. Filled in by the
+    /// backend, which is the only one to see the password, and ignored on updates.
+    pub password_set: bool,
+    /// Scrobble songs played on this server to the enabled services.
+    pub scrobble: bool,
+}
+
+impl Default for ServerProfile {
+    fn default() -> Self {
+        Self {
+            name: "Local".to_string(),
+            host: "localhost".to_string(),
+            port: 6600,
+            password_set: false,
+            scrobble: true,
+        }
+    }
+}
+
+impl ServerProfile {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    /// Notify when the current song changes.
+    pub song_change: bool,
+    /// Notify when a database update finishes.
+    pub update_finished: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            song_change: true,
+            update_finished: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ShortcutSettings {
+    /// Register the bindings globally instead of only while the window is focused.
+    pub global: bool,
+    /// Action name to accelerator, e.g. `"next": "CmdOrCtrl+Right"`.
+    pub bindings: BTreeMap<String, String>,
+}
+
+impl Default for ShortcutSettings {
+    fn default() -> Self {
+        let bindings = [
+            ("play_pause", "Space"),
+            ("next", "CmdOrCtrl+Right"),
+            ("previous", "CmdOrCtrl+Left"),
+            ("volume_up", "CmdOrCtrl+Up"),
+            ("volume_down", "CmdOrCtrl+Down"),
+        ];
+        Self {
+            global: false,
+            bindings: bindings
+                .into_iter()
+                .map(|(action, key)| (action.to_string(), key.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// The API secret and session live in [`crate::credentials::CredentialStore`].
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct LastFmSettings {
+    pub enabled: bool,
+    /// Endpoint of a Last.fm compatible API, e.g. a self-hosted one.
+    pub api_url: String,
+    pub api_key: String,
+}
+
+impl Default for LastFmSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: "https://ws.audioscrobbler.com/2.0/".to_string(),
+            api_key: String::new(),
+        }
+    }
+}
+
+/// The user token lives in [`crate::credentials::CredentialStore`].
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListenBrainzSettings {
+    pub enabled: bool,
+    /// Root of a ListenBrainz compatible API, without the `/1/` path.
+    pub api_url: String,
+}
+
+impl Default for ListenBrainzSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: "https://api.listenbrainz.org".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScrobbleSettings {
+    pub lastfm: LastFmSettings,
+    pub listenbrainz: ListenBrainzSettings,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub servers: Vec<ServerProfile>,
+    /// Index into `servers` of the profile connected to at startup.
+    pub active_server: usize,
+    /// Local path of MPD's music directory, asked from MPD when unset.
+    pub music_directory: Option<String>,
+    /// Cover filename patterns tried in order, matched case-insensitively.
+    ///
+    /// `*` matches any run of characters, `?` a single one and `{a,b}` either alternative.
+    pub cover_patterns: Vec<String>,
+    /// Size limit of the album art cache in bytes.
+    pub art_cache_limit: u64,
+    pub notifications: NotificationSettings,
+    pub shortcuts: ShortcutSettings,
+    pub scrobbling: ScrobbleSettings,
+    /// Milliseconds between `elapsed` events while playing.
+    pub elapsed_interval: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            servers: vec![ServerProfile::default()],
+            active_server: 0,
+            music_directory: None,
+            cover_patterns: [
+                "cover.{png,jpg,jpeg}",
+                "folder.jpg",
+                "front.*",
+                "AlbumArt*.jpg",
+            ]
+            .map(String::from)
+            .to_vec(),
+            art_cache_limit: DEFAULT_CACHE_LIMIT,
+            notifications: NotificationSettings::default(),
+            shortcuts: ShortcutSettings::default(),
+            scrobbling: ScrobbleSettings::default(),
+            elapsed_interval: DEFAULT_ELAPSED_INTERVAL,
+        }
+    }
+}
+
+impl Settings {
+    pub fn active_server(&self) -> ServerProfile {
+        self.servers
+            .get(self.active_server)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Tell which server profiles have a password stored in `credentials`.
+    pub fn with_password_set(mut self, credentials: &Credentials) -> Self {
+        for server in &mut self.servers {
+            server.password_set = credentials.mpd_passwords.contains_key(&server.address());
+        }
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.servers.is_empty() {
+            return Err("at least one server profile is required".to_string());
+        }
+        if self.active_server >= self.servers.len() {
+            return Err(format!(
+                "active server {} out of range, {} profiles",
+                self.active_server,
+                self.servers.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Steps upgrading a raw settings object, `MIGRATIONS[n]` going from version `n + 1` to `n + 2`.
+///
+/// Version 1 is the first layout. Renaming or restructuring a field means bumping
+/// [`SETTINGS_VERSION`] and adding a step here; added fields only need a default.
+const MIGRATIONS: [fn(&mut Map<String, Value>); SETTINGS_VERSION as usize - 1] = [];
+
+/// Upgrade a raw settings object to [`SETTINGS_VERSION`], one version at a time.
+fn migrate(raw: &mut Map<String, Value>) -> Result<(), String> {
+    let version = raw.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version == 0 || version > SETTINGS_VERSION as u64 {
+        return Err(format!("unsupported settings version {}", version));
+    }
+    for step in &MIGRATIONS[version as usize - 1..] {
+        step(raw);
+    }
+    raw.insert("version".to_string(), SETTINGS_VERSION.into());
+    Ok(())
+}
+
+/// Take the passwords out of the server profiles, which used to keep them, by server address.
+fn take_passwords(raw: &mut Map<String, Value>) -> BTreeMap<String, String> {
+    let Some(Value::Array(servers)) = raw.get_mut("servers") else {
+        return BTreeMap::new();
+    };
+    servers
+        .iter_mut()
+        .filter_map(|server| {
+            let password = server.as_object_mut()?.remove("password")?;
+            let profile: ServerProfile = serde_json::from_value(server.clone()).ok()?;
+            Some((profile.address(), password.as_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// Load the settings, along with the passwords found in them.
+fn load(path: &Path) -> Result<(Settings, BTreeMap<String, String>), String> {
+    let raw = fs::read(path).map_err(|e| e.to_string())?;
+    let mut value: Value = serde_json::from_slice(&raw).map_err(|e| e.to_string())?;
+    let Value::Object(map) = &mut value else {
+        return Err("settings file is not an object".to_string());
+    };
+    migrate(map)?;
+    let passwords = take_passwords(map);
+    let settings: Settings = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    settings.validate()?;
+    Ok((settings, passwords))
+}
+
+fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    // Write a sibling first so a crash can't leave a truncated file behind.
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, raw).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+pub struct SettingsStore {
+    /// `None` if there's no config directory, in which case settings aren't persisted.
+    path: Option<PathBuf>,
+    current: watch::Sender<Settings>,
+}
+
+impl SettingsStore {
+    /// Load the settings in `dir`, falling back to defaults if the file is missing or unreadable.
+    ///
+    /// An unreadable file is kept as `settings.json.bak` instead of being overwritten. Passwords
+    /// found in the file are moved to `credentials`.
+    pub fn open(dir: Option<PathBuf>, credentials: &CredentialStore) -> Self {
+        let path = dir.map(|dir| dir.join(SETTINGS_FILE));
+        let (settings, passwords) = match &path {
+            Some(path) if path.exists() => match load(path) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    log::error!("Failed to load settings from {}: {}", path.display(), e);
+                    let _ = fs::rename(path, path.with_extension("json.bak"));
+                    (Settings::default(), BTreeMap::new())
+                }
+            },
+            _ => (Settings::default(), BTreeMap::new()),
+        };
+        let moved = passwords.is_empty()
+            || credentials
+                .update(|c| c.mpd_passwords.extend(passwords))
+                .map_err(|e| log::error!("Failed to move MPD passwords: {}", e))
+                .is_ok();
+        // Keep the passwords in the file until they are stored elsewhere.
+        if let Some(path) = path.as_ref().filter(|_| moved) {
+            if let Err(e) = save(path, &settings) {
+                log::error!("Failed to save settings to {}: {}", path.display(), e);
+            }
+        }
+        Self {
+            path,
+            current: watch::channel(settings).0,
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.borrow().clone()
+    }
+
+    /// Validate, save and publish new settings.
+    pub fn update(&self, mut settings: Settings) -> Result<Settings, String> {
+        settings.version = SETTINGS_VERSION;
+        settings.validate()?;
+        for server in &mut settings.servers {
+            server.password_set = false;
+        }
+        if let Some(path) = &self.path {
+            save(path, &settings)?;
+        }
+        self.current.send_if_modified(|current| {
+            let changed = *current != settings;
+            *current = settings.clone();
+            changed
+        });
+        Ok(settings)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.current.subscribe()
+    }
+}
+
+/// Push settings into the modules that depend on them.
+pub fn apply_settings(handle: &AppHandle, settings: &Settings) {
+    handle.state::<CoverResolver>().configure(
+        settings.music_directory.clone(),
+        settings.cover_patterns.clone(),
+    );
+    handle
+        .state::<ArtCache>()
+        .set_limit(settings.art_cache_limit);
+    handle
+        .state::<PlaybackClock>()
+        .set_interval(settings.elapsed_interval);
+}
+
+/// Apply every settings change and emit `settings-changed` with the new settings.
+///
+/// Server profile changes take effect on the next start.
+pub async fn watch_settings(handle: AppHandle) -> tauri::Result<()> {
+    let mut rx = handle.state::<SettingsStore>().subscribe();
+    while rx.changed().await.is_ok() {
+        let settings = rx.borrow_and_update().clone();
+        apply_settings(&handle, &settings);
+        let credentials = handle.state::<CredentialStore>().get();
+        handle.emit_all("settings-changed", settings.with_password_set(&credentials))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_passwords_out_of_server_profiles() {
+        let Value::Object(mut raw) = serde_json::json!({
+            "version": 1,
+            "servers": [
+                { "name": "Home", "host": "mpd.lan", "password": "secret" },
+                { "name": "Local", "password": null },
+            ],
+        }) else {
+            unreachable!()
+        };
+        let passwords = take_passwords(&mut raw);
+        assert_eq!(
+            passwords,
+            BTreeMap::from([("mpd.lan:6600".to_string(), "secret".to_string())])
+        );
+        assert!(!raw["servers"].to_string().contains("password"));
+
+        let settings: Settings = serde_json::from_value(Value::Object(raw)).unwrap();
+        let credentials = Credentials {
+            mpd_passwords: passwords,
+            ..Credentials::default()
+        };
+        let flags: Vec<_> = settings
+            .with_password_set(&credentials)
+            .servers
+            .iter()
+            .map(|s| s.password_set)
+            .collect();
+        assert_eq!(flags, [true, false]);
+    }
+}
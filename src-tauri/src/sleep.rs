@@ -0,0 +1,367 @@
+//! Sleep timer: stops or pauses playback after a while, the current song or the current album.
+//!
+//! The timer lives in the backend, so it keeps running while the UI reloads. While armed, the
+//! background task checks the player every second, emitting `sleep-timer` with the remaining
+//! time. During the last [`SleepTimerConfig::fade`] seconds the volume is lowered step by step
+//! with `setvol`, and put back to where it was once playback stopped or the timer is cancelled.
+//!
+//! A timer for a number of minutes runs out whatever the player does, while one for songs only
+//! counts down as they play and is disarmed without acting if playback stops before they end.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use mpd_client::{
+    commands,
+    responses::{PlayState, Song, Status},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::reflection::{response::TVal, Reflect};
+
+/// How often the remaining time is checked and emitted.
+const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq)]
+pub enum SleepTrigger {
+    /// After this many minutes, whatever is playing.
+    Minutes(u32),
+    /// When the current song ends.
+    EndOfSong,
+    /// When the last of the songs following the current one in the queue that are on the same
+    /// album ends. Not available in random mode, where the queue order isn't the play order.
+    EndOfAlbum,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepAction {
+    Stop,
+    Pause,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct SleepTimerConfig {
+    pub trigger: SleepTrigger,
+    pub action: SleepAction,
+    /// Seconds before the end over which the volume fades out; 0 for no fade.
+    pub fade: u32,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct SleepTimerStatus {
+    pub config: SleepTimerConfig,
+    pub remaining: TVal,
+    /// Whether the volume is being faded out.
+    pub fading: bool,
+}
+
+enum Target {
+    Deadline(Instant),
+    /// Queue ids and durations of the songs to play to the end, in order.
+    Songs(Vec<(u64, Option<Duration>)>),
+}
+
+/// Time left of `songs` while the one with queue id `current` is at `elapsed` of `duration`, or
+/// `None` if another song is playing.
+fn songs_remaining(
+    songs: &[(u64, Option<Duration>)],
+    current: u64,
+    duration: Option<Duration>,
+    elapsed: Duration,
+) -> Option<Duration> {
+    let index = songs.iter().position(|(song, _)| *song == current)?;
+    let current = duration
+        .or(songs[index].1)
+        .unwrap_or_default()
+        .saturating_sub(elapsed);
+    let rest = songs[index + 1..]
+        .iter()
+        .map(|(_, duration)| duration.unwrap_or_default())
+        .sum::<Duration>();
+    Some(current + rest)
+}
+
+impl Target {
+    /// Time left until the timer fires, or `None` if it is already due.
+    ///
+    /// A deadline runs out whatever the player does, while songs only count down as they play.
+    fn remaining(&self, status: &Status, now: Instant) -> Option<Duration> {
+        match self {
+            Target::Deadline(deadline) => Some(deadline.saturating_duration_since(now)),
+            Target::Songs(songs) => {
+                let (_, id) = status.current_song?;
+                let elapsed = status.elapsed.unwrap_or_default();
+                songs_remaining(songs, id.0, status.duration, elapsed)
+            }
+        }
+    }
+
+    /// Whether playback stopped before the songs ended, e.g. by hand, leaving nothing to do.
+    fn ended(&self, status: &Status) -> bool {
+        matches!(self, Target::Songs(_)) && status.state == PlayState::Stopped
+    }
+}
+
+struct Armed {
+    config: SleepTimerConfig,
+    target: Target,
+    remaining: Duration,
+    /// Volume before the fade started, to restore afterwards.
+    volume: Option<u8>,
+}
+
+impl Armed {
+    fn status(&self) -> SleepTimerStatus {
+        SleepTimerStatus {
+            config: self.config.clone(),
+            remaining: self.remaining.reflect(),
+            fading: self.volume.is_some(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SleepTimer {
+    armed: Mutex<Option<Armed>>,
+    wake: Notify,
+}
+
+impl SleepTimer {
+    pub fn status(&self) -> Option<SleepTimerStatus> {
+        self.armed.lock().unwrap().as_ref().map(Armed::status)
+    }
+
+    /// Have the background task check the player now.
+    pub fn poke(&self) {
+        self.wake.notify_one();
+    }
+}
+
+fn same_album(a: &Song, b: &Song) -> bool {
+    a.album().is_some() && a.album() == b.album() && a.album_artists() == b.album_artists()
+}
+
+async fn target(
+    client: &Client,
+    trigger: &SleepTrigger,
+    status: &Status,
+) -> Result<Target, String> {
+    let current = || "Nothing is playing.".to_string();
+    if !matches!(trigger, SleepTrigger::Minutes(_)) && status.state == PlayState::Stopped {
+        return Err(current());
+    }
+    match trigger {
+        SleepTrigger::Minutes(minutes) => Ok(Target::Deadline(
+            Instant::now() + Duration::from_secs(*minutes as u64 * 60),
+        )),
+        SleepTrigger::EndOfSong => {
+            let song = client
+                .command(commands::CurrentSong)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(current)?;
+            Ok(Target::Songs(vec![(song.id.0, song.song.duration)]))
+        }
+        SleepTrigger::EndOfAlbum => {
+            if status.random {
+                return Err("The end of the album can't be told in random mode.".to_string());
+            }
+            let (position, _) = status.current_song.ok_or_else(current)?;
+            let queue = client
+                .command(commands::Queue)
+                .await
+                .map_err(|e| e.to_string())?;
+            let rest = queue.get(position.0..).ok_or_else(current)?;
+            let first = &rest.first().ok_or_else(current)?.song;
+            let songs = rest
+                .iter()
+                .enumerate()
+                .take_while(|(i, s)| *i == 0 || same_album(first, &s.song))
+                .map(|(_, s)| (s.id.0, s.song.duration))
+                .collect();
+            Ok(Target::Songs(songs))
+        }
+    }
+}
+
+async fn restore_volume(client: &Client, volume: Option<u8>) {
+    if let Some(volume) = volume {
+        if let Err(e) = client.command(commands::SetVolume(volume)).await {
+            log::error!("Failed to restore the volume after the sleep timer: {}", e);
+        }
+    }
+}
+
+fn emit_status(handle: &AppHandle, status: Option<SleepTimerStatus>) {
+    if let Err(e) = handle.emit_all("sleep-timer", status) {
+        log::error!("Failed to emit signal 'sleep-timer': {}", e);
+    }
+}
+
+/// Arm the timer, replacing the one already running.
+pub async fn start(
+    handle: &AppHandle,
+    config: SleepTimerConfig,
+) -> Result<SleepTimerStatus, String> {
+    let client = handle.state::<Client>();
+    let player = client
+        .command(commands::Status)
+        .await
+        .map_err(|e| e.to_string())?;
+    let target = target(&client, &config.trigger, &player).await?;
+    let armed = Armed {
+        remaining: target
+            .remaining(&player, Instant::now())
+            .unwrap_or_default(),
+        config,
+        target,
+        volume: None,
+    };
+    let status = armed.status();
+    let previous = handle
+        .state::<SleepTimer>()
+        .armed
+        .lock()
+        .unwrap()
+        .replace(armed);
+    if let Some(previous) = previous {
+        restore_volume(&client, previous.volume).await;
+    }
+    handle.state::<SleepTimer>().poke();
+    emit_status(handle, Some(status.clone()));
+    Ok(status)
+}
+
+async fn disarm(handle: &AppHandle, client: &Client, armed: Armed) {
+    restore_volume(client, armed.volume).await;
+    emit_status(handle, None);
+}
+
+/// Disarm the timer, restoring the volume if it was fading.
+pub async fn cancel(handle: &AppHandle) {
+    let armed = handle.state::<SleepTimer>().armed.lock().unwrap().take();
+    if let Some(armed) = armed {
+        disarm(handle, &handle.state::<Client>(), armed).await;
+    }
+}
+
+/// Stop or pause once the timer is due, then restore the volume.
+async fn fire(handle: &AppHandle, client: &Client, armed: Armed) {
+    let result = match armed.config.action {
+        SleepAction::Stop => client.command(commands::Stop).await,
+        SleepAction::Pause => client.command(commands::SetPause(true)).await,
+    };
+    if let Err(e) = result {
+        log::error!("Sleep timer failed to {:?}: {}", armed.config.action, e);
+    }
+    disarm(handle, client, armed).await;
+}
+
+/// Volume for `remaining` time left of a `fade` from `volume` down to 0.
+fn faded(volume: u8, remaining: Duration, fade: Duration) -> u8 {
+    (volume as f64 * remaining.as_secs_f64() / fade.as_secs_f64()).round() as u8
+}
+
+/// Follow the armed timer, fading out and firing it when due.
+pub async fn sleep_task(handle: AppHandle) {
+    let timer = handle.state::<SleepTimer>();
+    loop {
+        if timer.armed.lock().unwrap().is_none() {
+            timer.wake.notified().await;
+            continue;
+        }
+        let client = handle.state::<Client>();
+        let player = match client.command(commands::Status).await {
+            Ok(player) => player,
+            Err(e) => {
+                log::error!("Sleep timer failed to fetch the player state: {}", e);
+                let _ = tokio::time::timeout(TICK, timer.wake.notified()).await;
+                continue;
+            }
+        };
+
+        let checked = {
+            let mut guard = timer.armed.lock().unwrap();
+            let Some(armed) = guard.as_mut() else {
+                continue;
+            };
+            if armed.target.ended(&player) {
+                Err((guard.take(), false))
+            } else {
+                match armed
+                    .target
+                    .remaining(&player, Instant::now())
+                    .filter(|r| !r.is_zero())
+                {
+                    Some(remaining) => {
+                        armed.remaining = remaining;
+                        let fade = Duration::from_secs(armed.config.fade as u64);
+                        let volume =
+                            (remaining < fade && player.state == PlayState::Playing).then(|| {
+                                let original = *armed.volume.get_or_insert(player.volume);
+                                faded(original, remaining, fade)
+                            });
+                        Ok((armed.status(), volume))
+                    }
+                    None => Err((guard.take(), true)),
+                }
+            }
+        };
+        let (status, volume) = match checked {
+            Ok(checked) => checked,
+            Err((armed, due)) => {
+                match armed {
+                    Some(armed) if due => fire(&handle, &client, armed).await,
+                    // Playback stopped before the songs ended, so there is nothing left to do.
+                    Some(armed) => disarm(&handle, &client, armed).await,
+                    None => {}
+                }
+                continue;
+            }
+        };
+        let remaining: Duration = status.remaining.clone().into();
+
+        if let Some(volume) = volume.filter(|&v| v != player.volume) {
+            if let Err(e) = client.command(commands::SetVolume(volume)).await {
+                log::warn!("Sleep timer failed to lower the volume: {}", e);
+            }
+        }
+        emit_status(&handle, Some(status));
+        let _ = tokio::time::timeout(TICK.min(remaining), timer.wake.notified()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn counts_down_the_remaining_songs() {
+        let songs = [(1, Some(secs(100))), (2, Some(secs(200))), (3, None)];
+        assert_eq!(songs_remaining(&songs, 1, None, secs(40)), Some(secs(260)));
+        // The player's duration wins over the one known when arming.
+        assert_eq!(
+            songs_remaining(&songs, 2, Some(secs(210)), secs(10)),
+            Some(secs(200))
+        );
+        assert_eq!(songs_remaining(&songs, 3, None, secs(5)), Some(secs(0)));
+        assert_eq!(songs_remaining(&songs, 4, None, secs(0)), None);
+    }
+
+    #[test]
+    fn fades_linearly() {
+        assert_eq!(faded(80, secs(10), secs(10)), 80);
+        assert_eq!(faded(80, secs(5), secs(10)), 40);
+        assert_eq!(faded(80, secs(0), secs(10)), 0);
+    }
+}
@@ -0,0 +1,67 @@
+//! Numeric views of sticker values.
+//!
+//! MPD stores sticker values as strings and, before 0.24, only compares them as strings, so
+//! `"9" > "10"`. These helpers parse values as integers and compare and sort them locally;
+//! values that aren't integers are left out.
+
+use std::ops::Range;
+
+use mpd_client::Client;
+
+use crate::{
+    extension::{FindStickers, ListStickers, StickerType},
+    reflection::request::NumericComparison,
+};
+
+/// The sticker `name` of `uri` as an integer, `None` if it is missing or not a number.
+pub async fn get_number(
+    client: &Client,
+    kind: &StickerType,
+    uri: &str,
+    name: &str,
+) -> Result<Option<i64>, String> {
+    // `sticker get` fails for missing stickers, so look through the list instead.
+    let stickers = client
+        .command(ListStickers::new(kind, uri))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(stickers
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .and_then(|(_, value)| value.trim().parse().ok()))
+}
+
+/// The numeric stickers `name` under `uri` as `(uri, value)` pairs.
+///
+/// Only values satisfying `filter` are kept, sorted by value when `descending` is given, and
+/// `window` is applied last.
+pub async fn find_numbers(
+    client: &Client,
+    kind: &StickerType,
+    uri: &str,
+    name: &str,
+    filter: Option<(NumericComparison, i64)>,
+    descending: Option<bool>,
+    window: Option<Range<usize>>,
+) -> Result<Vec<(String, i64)>, String> {
+    let mut found: Vec<(String, i64)> = client
+        .command(FindStickers::new(kind, uri, name))
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|(uri, value)| Some((uri, value.trim().parse().ok()?)))
+        .filter(|(_, value)| filter.is_none_or(|(cmp, operand)| cmp.matches(*value, operand)))
+        .collect();
+    if let Some(descending) = descending {
+        found.sort_by(|(a_uri, a), (b_uri, b)| {
+            let ordering = if descending { b.cmp(a) } else { a.cmp(b) };
+            ordering.then_with(|| a_uri.cmp(b_uri))
+        });
+    }
+    if let Some(window) = window {
+        let end = window.end.min(found.len());
+        let start = window.start.min(end);
+        found = found.drain(start..end).collect();
+    }
+    Ok(found)
+}
@@ -0,0 +1,159 @@
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use mpd_client::{commands, Client};
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::reflection::{response::UpdateJob, Reflect};
+
+/// Number of finished jobs remembered for `wait_update`.
+const FINISHED_HISTORY: usize = 32;
+
+pub enum UpdateEvent {
+    Started(UpdateJob),
+    Finished(UpdateJob),
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    running: BTreeMap<u64, Instant>,
+    finished: BTreeMap<u64, Duration>,
+}
+
+impl TrackerInner {
+    fn start(&mut self, job: u64, events: &mut Vec<UpdateEvent>) {
+        if self.running.contains_key(&job) || self.finished.contains_key(&job) {
+            return;
+        }
+        self.running.insert(job, Instant::now());
+        events.push(UpdateEvent::Started(UpdateJob {
+            job,
+            elapsed: Duration::ZERO.reflect(),
+        }));
+    }
+
+    fn finish(&mut self, job: u64, events: &mut Vec<UpdateEvent>) {
+        if let Some(start) = self.running.remove(&job) {
+            let elapsed = start.elapsed();
+            self.finished.insert(job, elapsed);
+            while self.finished.len() > FINISHED_HISTORY {
+                self.finished.pop_first();
+            }
+            events.push(UpdateEvent::Finished(UpdateJob {
+                job,
+                elapsed: elapsed.reflect(),
+            }));
+        }
+    }
+}
+
+/// Tracks database update jobs issued by `update`/`rescan` or by other clients.
+///
+/// MPD hands out increasing job ids and only reports the job currently running in `status`, so a
+/// tracked job is finished once `status` reports no job or a later one.
+pub struct UpdateTracker {
+    inner: Mutex<TrackerInner>,
+    notify: watch::Sender<()>,
+}
+
+impl Default for UpdateTracker {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::default(),
+            notify: watch::channel(()).0,
+        }
+    }
+}
+
+impl UpdateTracker {
+    /// Register a job we just issued, given the `update_job` of a `status` fetched afterwards.
+    pub fn track(&self, job: u64, current: Option<u64>) -> Vec<UpdateEvent> {
+        let mut events = Vec::new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.start(job, &mut events);
+            if current.is_none_or(|c| c > job) {
+                inner.finish(job, &mut events);
+            }
+        }
+        self.notify.send_replace(());
+        events
+    }
+
+    /// Reconcile tracked jobs with the `update_job` reported by `status`.
+    pub fn sync(&self, current: Option<u64>) -> Vec<UpdateEvent> {
+        let mut events = Vec::new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(job) = current {
+                inner.start(job, &mut events);
+            }
+            let done: Vec<u64> = inner
+                .running
+                .keys()
+                .copied()
+                .filter(|&j| current.is_none_or(|c| j < c))
+                .collect();
+            for job in done {
+                inner.finish(job, &mut events);
+            }
+        }
+        self.notify.send_replace(());
+        events
+    }
+
+    /// Wait until `job` (or the latest running job if `None`) finishes.
+    ///
+    /// Returns `None` if there's nothing to wait for, i.e. the job is unknown to the tracker.
+    pub async fn wait(&self, job: Option<u64>) -> Option<UpdateJob> {
+        let mut rx = self.notify.subscribe();
+        let target = {
+            let inner = self.inner.lock().unwrap();
+            job.or_else(|| inner.running.keys().next_back().copied())?
+        };
+        loop {
+            {
+                let inner = self.inner.lock().unwrap();
+                if let Some(elapsed) = inner.finished.get(&target) {
+                    return Some(UpdateJob {
+                        job: target,
+                        elapsed: elapsed.reflect(),
+                    });
+                }
+                if !inner.running.contains_key(&target) {
+                    return None;
+                }
+            }
+            rx.changed().await.ok()?;
+        }
+    }
+}
+
+pub fn emit_update_events(handle: &AppHandle, events: Vec<UpdateEvent>) -> tauri::Result<()> {
+    for event in events {
+        match event {
+            UpdateEvent::Started(job) => handle.emit_all("update-started", job)?,
+            UpdateEvent::Finished(job) => handle.emit_all("update-finished", job)?,
+        }
+    }
+    Ok(())
+}
+
+/// Fetch `status` and emit events for update jobs that started or finished since the last sync.
+pub async fn sync_update_jobs(handle: &AppHandle) -> tauri::Result<()> {
+    let client = handle.state::<Client>();
+    match client.command(commands::Status).await {
+        Ok(status) => {
+            let events = handle.state::<UpdateTracker>().sync(status.update_job);
+            emit_update_events(handle, events)
+        }
+        Err(e) => {
+            log::error!("Failed to fetch status for update tracking: {}", e);
+            Ok(())
+        }
+    }
+}
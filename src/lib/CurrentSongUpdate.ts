@@ -1,11 +1,13 @@
 import { writable } from "svelte/store";
-import { getCoverPath, type SongInQueue } from "./bindings";
+import { convertFileSrc } from "@tauri-apps/api/tauri";
+import { type SongInQueue } from "./bindings";
 
-const coverPath = writable("/home/poyehchen/placeholder.png");
+const coverPath = writable("");
 const currentItem = writable(0);
 const currentTitle = writable("Unknown Title");
 const currentArtist = writable("Unknown Artist");
 const currentAlbum = writable("Unknown Album");
+let currentUrl = "";
 
 function currentSongUpdate(currentSong: SongInQueue) {
   if (currentSong === undefined) {
@@ -14,12 +16,20 @@ function currentSongUpdate(currentSong: SongInQueue) {
   currentItem.set(currentSong.position);
   const song = currentSong.song;
   const tags = song.tags;
-  getCoverPath(song.url).then((path: string) => coverPath.set(path));
+  currentUrl = song.url;
+  coverPath.set(convertFileSrc(song.url, "mpdart"));
   currentTitle.update((orig) => tags?.["Title"]?.[0] || orig);
   currentArtist.update((orig) => tags?.["Artist"]?.[0] || orig);
   currentAlbum.update((orig) => tags?.["Album"]?.[0] || orig);
 }
 
+// The cover was resolved after the placeholder was served; load it again.
+function artUpdate(url: string) {
+  if (url === currentUrl) {
+    coverPath.set(`${convertFileSrc(url, "mpdart")}?v=${Date.now()}`);
+  }
+}
+
 export {
   coverPath,
   currentItem,
@@ -27,4 +37,5 @@ export {
   currentArtist,
   currentAlbum,
   currentSongUpdate,
+  artUpdate,
 };
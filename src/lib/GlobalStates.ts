@@ -2,13 +2,15 @@ import { writable, type Writable } from "svelte/store";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import {
   currentsong,
+  elapsed,
   status,
   playlistinfo,
+  type PlaybackPosition,
   type SongInQueue,
   type Status,
 } from "./bindings";
-import { currentSongUpdate } from "./CurrentSongUpdate";
-import { statusUpdate } from "./StatusUpdate";
+import { artUpdate, currentSongUpdate } from "./CurrentSongUpdate";
+import { elapsedUpdate, statusUpdate } from "./StatusUpdate";
 import { stateSubscription, updateStatePromise } from "./Utils";
 
 const currentQueue: Writable<SongInQueue[]> = writable([]);
@@ -27,6 +29,9 @@ function setup(): UnlistenFn[] {
   updateStatePromise(currentQueue, playlistinfo());
   updateStatePromise(currentSong, currentsong());
   updateStatePromise(currentStatus, status());
+  elapsed()
+    .then(elapsedUpdate)
+    .catch((err) => console.error(err));
   // update state by event.
   listen("queue", () => {
     updateStatePromise(currentQueue, playlistinfo());
@@ -37,6 +42,12 @@ function setup(): UnlistenFn[] {
   listen("status", () => {
     updateStatePromise(currentStatus, status());
   }).then((x) => listenEvents.push(x));
+  listen<PlaybackPosition>("elapsed", (event) => {
+    elapsedUpdate(event.payload);
+  }).then((x) => listenEvents.push(x));
+  listen<string>("art", (event) => {
+    artUpdate(event.payload);
+  }).then((x) => listenEvents.push(x));
   return listenEvents;
 }
 
@@ -1,5 +1,10 @@
 import { writable, type Writable } from "svelte/store";
-import type { Status, SingleMode, PlayState } from "./bindings";
+import type {
+  Status,
+  SingleMode,
+  PlayState,
+  PlaybackPosition,
+} from "./bindings";
 import { tvalToMs, updateState } from "./Utils";
 
 const currentTime = writable(0);
@@ -9,7 +14,6 @@ const isPlaying = writable(false);
 const isRepeat = writable(false);
 const singleMode: Writable<SingleMode> = writable("Disabled");
 const playState: Writable<PlayState> = writable("Paused");
-let intervalID: NodeJS.Timer = undefined;
 
 function statusUpdate(status: Status) {
   if (status === undefined) {
@@ -19,18 +23,15 @@ function statusUpdate(status: Status) {
   updateState(isRepeat, status?.repeat);
   updateState(singleMode, status?.single);
   updateState(totalTime, tvalToMs(status?.duration));
-  updateState(currentTime, tvalToMs(status?.elapsed));
   updateState(playState, status?.state);
-  if (status?.state === "Playing") {
-    if (intervalID === undefined) {
-      intervalID = setInterval(() => currentTime.update((n) => n + 1000), 1000);
-    }
-  } else {
-    if (intervalID !== undefined) {
-      clearInterval(intervalID);
-      intervalID = undefined;
-    }
+}
+
+// Progress comes from the backend clock rather than being counted here.
+function elapsedUpdate(position: PlaybackPosition) {
+  if (position === undefined) {
+    return;
   }
+  updateState(currentTime, tvalToMs(position.elapsed));
 }
 
 export {
@@ -42,4 +43,5 @@ export {
   singleMode,
   playState,
   statusUpdate,
+  elapsedUpdate,
 };
@@ -10,24 +10,22 @@ declare global {
 // Function avoids 'window not defined' in SSR
 const invoke = () => window.__TAURI_INVOKE__;
 
-export function getCoverPath(url: string) {
-    return invoke()<string>("get_cover_path", { url })
-}
-
 export function add(uri: string, prop: AddVariant) {
     return invoke()<number>("add", { uri,prop })
 }
 
-export function playlistadd(playlist: string, uri: string, position: number | null) {
-    return invoke()<null>("playlistadd", { playlist,uri,position })
-}
-
-export function albumart(uri: string, offset: number) {
-    return invoke()<AlbumArt | null>("albumart", { uri,offset })
+/**
+ * Send `operations` as one command list.
+ * 
+ * MPD stops at the first failing operation, in which case the results of the operations before
+ * it are returned together with the index and message from the ACK.
+ */
+export function batch(operations: BatchOperation[]) {
+    return invoke()<BatchResponse>("batch", { operations })
 }
 
-export function readpicture(uri: string, offset: number) {
-    return invoke()<AlbumArt | null>("readpicture", { uri,offset })
+export function playlistadd(playlist: string, uri: string, position: number | null) {
+    return invoke()<null>("playlistadd", { playlist,uri,position })
 }
 
 export function playlistclear(playlist: string) {
@@ -90,6 +88,14 @@ export function channels() {
     return invoke()<string[]>("channels")
 }
 
+export function listmounts() {
+    return invoke()<Mount[]>("listmounts")
+}
+
+export function listneighbors() {
+    return invoke()<Neighbor[]>("listneighbors")
+}
+
 export function load(playlist: string, range: [number, number] | null) {
     return invoke()<null>("load", { playlist,range })
 }
@@ -98,6 +104,10 @@ export function moveSong(variant: MoveVariant, target: MoveTarget) {
     return invoke()<null>("move_song", { variant,target })
 }
 
+export function mount(path: string, uri: string) {
+    return invoke()<null>("mount", { path,uri })
+}
+
 export function next() {
     return invoke()<null>("next")
 }
@@ -118,10 +128,32 @@ export function playlistinfo() {
     return invoke()<SongInQueue[]>("playlistinfo")
 }
 
+export function prio(priority: number, variant: PrioVariant) {
+    return invoke()<null>("prio", { priority,variant })
+}
+
 export function playlistdelete(playlist: string, variant: PlaylistDeleteVariant) {
     return invoke()<null>("playlistdelete", { playlist,variant })
 }
 
+/**
+ * Songs in the queue matching `filter` exactly.
+ */
+export function playlistfind(filter: Filter) {
+    return invoke()<SongInQueue[]>("playlistfind", { filter })
+}
+
+export function playlistmove(playlist: string, from: number, to: number) {
+    return invoke()<null>("playlistmove", { playlist,from,to })
+}
+
+/**
+ * Songs in the queue matching `filter`, ignoring case.
+ */
+export function playlistsearch(filter: Filter) {
+    return invoke()<SongInQueue[]>("playlistsearch", { filter })
+}
+
 export function rename(from: string, to: string) {
     return invoke()<null>("rename", { from,to })
 }
@@ -134,6 +166,13 @@ export function save(playlist: string) {
     return invoke()<null>("save", { playlist })
 }
 
+/**
+ * Songs of a stored playlist matching `filter`, ignoring case. Requires MPD 0.24.
+ */
+export function searchplaylist(playlist: string, filter: Filter) {
+    return invoke()<PlaylistMatch[]>("searchplaylist", { playlist,filter })
+}
+
 export function seekcur(seekMode: SeekMode) {
     return invoke()<null>("seekcur", { seekMode })
 }
@@ -174,24 +213,50 @@ export function status() {
     return invoke()<Status>("status")
 }
 
-export function stickerDelete(uri: string, name: string) {
-    return invoke()<null>("sticker_delete", { uri,name })
+/**
+ * Delete the sticker `name` of `uri`, or all of its stickers without a name.
+ */
+export function stickerDelete(stickerType: StickerType, uri: string, name: string | null) {
+    return invoke()<null>("sticker_delete", { stickerType,uri,name })
+}
+
+/**
+ * Find the stickers `name` under `uri` as `(uri, value)` pairs.
+ * 
+ * Integer comparisons, `sort` and `window` need MPD 0.24 or later.
+ */
+export function stickerFind(stickerType: StickerType, uri: string, name: string, filter: [StickerOperator, string] | null, sort: [StickerSort, boolean] | null, window: [number, number] | null) {
+    return invoke()<([string, string])[]>("sticker_find", { stickerType,uri,name,filter,sort,window })
 }
 
-export function stickerFind(uri: string, name: string, value: string, variant: StickerFindVariant) {
-    return invoke()<StickerFind>("sticker_find", { uri,name,value,variant })
+/**
+ * Find the integer stickers `name` under `uri`, comparing and sorting by value locally so it
+ * works with any MPD version. Values that aren't integers are skipped.
+ */
+export function stickerFindNumber(stickerType: StickerType, uri: string, name: string, filter: [NumericComparison, number] | null, descending: boolean | null, window: [number, number] | null) {
+    return invoke()<([string, number])[]>("sticker_find_number", { stickerType,uri,name,filter,descending,window })
 }
 
-export function stickerGet(uri: string, name: string) {
-    return invoke()<StickerGet>("sticker_get", { uri,name })
+export function stickerGet(stickerType: StickerType, uri: string, name: string) {
+    return invoke()<string>("sticker_get", { stickerType,uri,name })
 }
 
-export function stickerList(uri: string) {
-    return invoke()<StickerList>("sticker_list", { uri })
+/**
+ * The sticker `name` of `uri` as an integer, `None` if it is missing or not a number.
+ */
+export function stickerGetNumber(stickerType: StickerType, uri: string, name: string) {
+    return invoke()<number | null>("sticker_get_number", { stickerType,uri,name })
 }
 
-export function stickerSet(uri: string, name: string, value: string) {
-    return invoke()<null>("sticker_set", { uri,name,value })
+/**
+ * All stickers of `uri` as `(name, value)` pairs.
+ */
+export function stickerList(stickerType: StickerType, uri: string) {
+    return invoke()<([string, string])[]>("sticker_list", { stickerType,uri })
+}
+
+export function stickerSet(stickerType: StickerType, uri: string, name: string, value: string) {
+    return invoke()<null>("sticker_set", { stickerType,uri,name,value })
 }
 
 export function stop() {
@@ -206,11 +271,309 @@ export function unsubscribe(channel: string) {
     return invoke()<null>("unsubscribe", { channel })
 }
 
+export function unmount(path: string) {
+    return invoke()<null>("unmount", { path })
+}
+
 export function update(uri: string | null) {
     return invoke()<number>("update", { uri })
 }
 
-export type StickerFindVariant = "Eq" | "Gt" | "Lt"
+/**
+ * Albums of the indexed library, or only those `artist` appears on.
+ */
+export function albums(artist: string | null) {
+    return invoke()<Album[]>("albums", { artist })
+}
+
+export function artists() {
+    return invoke()<Artist[]>("artists")
+}
+
+/**
+ * Fuzzy search over titles, artists, albums and paths of the indexed library.
+ * 
+ * Every word of `query` has to match, allowing for prefixes and typos. Results are ranked best
+ * first, at most `limit` of them (50 by default).
+ */
+export function quickSearch(query: string, limit: number | null) {
+    return invoke()<SearchHit[]>("quick_search", { query,limit })
+}
+
+/**
+ * Export the queue or a stored playlist to `file` in the app's playlist directory, returning
+ * the number of entries written.
+ * 
+ * Without `format`, it is guessed from the file extension.
+ */
+export function exportPlaylist(source: PlaylistSource, file: string, format: PlaylistFormat | null) {
+    return invoke()<number>("export_playlist", { source,file,format })
+}
+
+/**
+ * Append the songs of `file` in the app's playlist directory to the queue or a stored
+ * playlist.
+ * 
+ * Entries are matched to songs in the indexed library by path, then by title, artist and
+ * duration; those that match nothing are reported back.
+ */
+export function importPlaylist(file: string, target: PlaylistSource, format: PlaylistFormat | null) {
+    return invoke()<ImportReport>("import_playlist", { file,target,format })
+}
+
+/**
+ * Names of the playlist files in the app's playlist directory, which can be imported.
+ */
+export function playlistFiles() {
+    return invoke()<string[]>("playlist_files")
+}
+
+/**
+ * Sort, dedupe, reverse or shuffle a stored playlist in place.
+ * 
+ * Returns the number of entries moved or removed.
+ */
+export function playlistedit(playlist: string, edit: PlaylistEdit) {
+    return invoke()<number>("playlistedit", { playlist,edit })
+}
+
+export function smartPlaylists() {
+    return invoke()<SmartPlaylist[]>("smart_playlists")
+}
+
+/**
+ * Save a smart playlist, replacing any with the same name.
+ */
+export function saveSmartPlaylist(playlist: SmartPlaylist) {
+    return invoke()<null>("save_smart_playlist", { playlist })
+}
+
+/**
+ * Delete a smart playlist. A stored playlist it was materialised into is kept, but no longer
+ * refreshed.
+ */
+export function deleteSmartPlaylist(name: string) {
+    return invoke()<boolean>("delete_smart_playlist", { name })
+}
+
+/**
+ * The songs a smart playlist currently yields, saved or not.
+ */
+export function previewSmartPlaylist(playlist: SmartPlaylist) {
+    return invoke()<Song[]>("preview_smart_playlist", { playlist })
+}
+
+/**
+ * Append the songs of a saved smart playlist to the queue, or replace a stored playlist with
+ * them. A stored playlist is then refreshed whenever the database changes.
+ */
+export function materializeSmartPlaylist(name: string, target: PlaylistSource) {
+    return invoke()<number>("materialize_smart_playlist", { name,target })
+}
+
+/**
+ * Rate a song from 1 to 10, or clear its rating with 0.
+ */
+export function rateSong(uri: string, rating: number) {
+    return invoke()<null>("rate_song", { uri,rating })
+}
+
+/**
+ * Rating, play and skip counts and last play time of a song.
+ */
+export function songStats(uri: string) {
+    return invoke()<SongStats>("song_stats", { uri })
+}
+
+/**
+ * Rated songs matching `filter`, or in the whole library, best rated first.
+ */
+export function topRated(filter: Filter | null, limit: number | null) {
+    return invoke()<RatedSong[]>("top_rated", { filter,limit })
+}
+
+/**
+ * Listening history matching `query`, newest first, `limit` entries (50 by default) after
+ * skipping `offset`.
+ */
+export function history(query: HistoryQuery | null, offset: number | null, limit: number | null) {
+    return invoke()<HistoryPage>("history", { query,offset,limit })
+}
+
+/**
+ * Export the listening history matching `query` to `file` in the app's export directory,
+ * returning the number of entries.
+ * 
+ * Without `format`, it is guessed from the file extension.
+ */
+export function exportHistory(file: string, format: HistoryFormat | null, query: HistoryQuery | null) {
+    return invoke()<number>("export_history", { file,format,query })
+}
+
+/**
+ * Listening statistics for the week, month or year containing `at` (seconds since the Unix
+ * epoch, now by default), with `limit` entries (10 by default) in every top list.
+ */
+export function listeningReport(period: ReportPeriod, at: number | null, limit: number | null) {
+    return invoke()<ListeningReport>("listening_report", { period,at,limit })
+}
+
+/**
+ * Position in the current song according to the backend clock, as also emitted by `elapsed`.
+ */
+export function elapsed() {
+    return invoke()<PlaybackPosition>("elapsed")
+}
+
+/**
+ * Lyrics of the song at `uri`, from a `.lrc` file next to it or its tags.
+ */
+export function lyrics(uri: string) {
+    return invoke()<Lyrics | null>("lyrics", { uri })
+}
+
+/**
+ * Lyrics of the current song.
+ * 
+ * Also has the active line emitted again as `lyrics-line`, so a reloaded UI can pick it up.
+ */
+export function currentLyrics() {
+    return invoke()<Lyrics | null>("current_lyrics")
+}
+
+export function scrobbleStatus() {
+    return invoke()<ScrobbleStatus>("scrobble_status")
+}
+
+/**
+ * Submit queued scrobbles now instead of waiting for the next retry.
+ */
+export function scrobbleFlush() {
+    return invoke()<null>("scrobble_flush")
+}
+
+/**
+ * Log in to the configured Last.fm compatible API, keeping the session key in the backend.
+ * 
+ * The password is only sent to the API and never stored.
+ */
+export function lastfmLogin(username: string, password: string) {
+    return invoke()<ScrobbleStatus>("lastfm_login", { username,password })
+}
+
+/**
+ * Forget the Last.fm session.
+ */
+export function lastfmLogout() {
+    return invoke()<ScrobbleStatus>("lastfm_logout")
+}
+
+/**
+ * Set the shared secret of the Last.fm API account. It can't be read back.
+ */
+export function setLastfmSecret(secret: string) {
+    return invoke()<ScrobbleStatus>("set_lastfm_secret", { secret })
+}
+
+/**
+ * Set the ListenBrainz user token, or clear it with an empty one. It can't be read back.
+ */
+export function setListenbrainzToken(token: string) {
+    return invoke()<ScrobbleStatus>("set_listenbrainz_token", { token })
+}
+
+/**
+ * The armed sleep timer, if any.
+ */
+export function sleepTimerStatus() {
+    return invoke()<SleepTimerStatus | null>("sleep_timer_status")
+}
+
+/**
+ * Arm the sleep timer, replacing the one already armed.
+ */
+export function sleepTimerStart(config: SleepTimerConfig) {
+    return invoke()<SleepTimerStatus>("sleep_timer_start", { config })
+}
+
+export function sleepTimerCancel() {
+    return invoke()<null>("sleep_timer_cancel")
+}
+
+export function alarms() {
+    return invoke()<Alarm[]>("alarms")
+}
+
+/**
+ * Add an alarm, or replace the one with the same name.
+ */
+export function saveAlarm(alarm: Alarm) {
+    return invoke()<null>("save_alarm", { alarm })
+}
+
+/**
+ * Remove an alarm, returning whether it existed.
+ */
+export function deleteAlarm(name: string) {
+    return invoke()<boolean>("delete_alarm", { name })
+}
+
+/**
+ * The enabled alarm going off next.
+ */
+export function nextAlarm() {
+    return invoke()<NextAlarm | null>("next_alarm")
+}
+
+export function autodjStatus() {
+    return invoke()<AutoDjStatus>("autodj_status")
+}
+
+/**
+ * Start keeping the queue filled, with `config` if given or the last configuration otherwise.
+ */
+export function autodjStart(config: AutoDjConfig | null) {
+    return invoke()<AutoDjStatus>("autodj_start", { config })
+}
+
+export function autodjStop() {
+    return invoke()<AutoDjStatus>("autodj_stop")
+}
+
+/**
+ * Change the source or thresholds, taking effect immediately if the Auto-DJ is running.
+ */
+export function autodjConfigure(config: AutoDjConfig) {
+    return invoke()<AutoDjStatus>("autodj_configure", { config })
+}
+
+export function getSettings() {
+    return invoke()<Settings>("get_settings")
+}
+
+/**
+ * Replace the settings, returning them as saved.
+ * 
+ * Listeners of `settings-changed` are notified once the change is applied.
+ */
+export function updateSettings(settings: Settings) {
+    return invoke()<Settings>("update_settings", { settings })
+}
+
+/**
+ * Set the MPD password of the server profile at `server`, or clear it with `None`. It can't be
+ * read back.
+ * 
+ * Passwords belong to the server address and take effect on the next start.
+ */
+export function setServerPassword(server: number, password: string | null) {
+    return invoke()<Settings>("set_server_password", { server,password })
+}
+
+export function waitUpdate(job: number | null) {
+    return invoke()<UpdateJob | null>("wait_update", { job })
+}
+
 export type SingleMode = "Enabled" | "Disabled" | "Oneshot"
 export type Song = { url: string; duration: TVal | null; tags: { [key: string]: string[] }; format: string | null; last_modified: Timestamp | null }
 export type Filter = { elements: FilterElement[]; negate: boolean }
@@ -218,17 +581,13 @@ export type MoveVariant = { Id: number } | { Position: number } | { Range: [numb
 export type Count = { songs: number; playtime: TVal }
 export type SeekMode = { Forward: TVal } | { Backward: TVal } | { Absolute: TVal }
 export type SongInQueue = { position: number; id: number; range: SongRange | null; priority: number; song: Song }
-export type StickerList = { value: { [key: string]: string } }
 export type List = { [key: string]: string[] }
 export type ItemVariant = { Id: number } | { Position: number }
 export type Stats = { artists: number; albums: number; songs: number; uptime: TVal; playtime: TVal; db_playtime: TVal; db_last_update: number }
-export type StickerGet = { value: string }
 export type AddVariant = "Append" | { At: number } | { BeforeCurrent: number } | { AfterCurrent: number }
 export type Timestamp = string
 export type Playlist = { name: string; last_modified: Timestamp }
 export type MoveTarget = { Position: number } | { BeforeCurrent: number } | { AfterCurrent: number }
-export type AlbumArt = { size: number; mime: string | null; data: number[] }
-export type StickerFind = { value: { [key: string]: string } }
 export type FilterElement = { tag: Tag; variant: FilterVariant; negate: boolean }
 export type DeleteVariant = { Id: number } | { Position: number } | { Range: [number, number] }
 export type SongRange = { from: TVal; to: TVal | null }
@@ -239,3 +598,89 @@ export type Tag = "Album" | "AlbumArtist" | "AlbumArtistSort" | "AlbumSort" | "A
 export type TVal = { secs: number; nanos: number }
 export type Operator = "Equal" | "NotEqual" | "Contain" | "Match" | "NotMatch"
 export type PlayState = "Stopped" | "Playing" | "Paused"
+export type Mount = { mount: string; storage: string | null }
+export type Neighbor = { neighbor: string; name: string | null }
+export type UpdateJob = { job: number; elapsed: TVal }
+/**
+ * A single queue/playback operation that can be sent as part of a command list.
+ */
+export type BatchOperation = { Add: { uri: string; prop: AddVariant } } | { Delete: DeleteVariant } | { Move: { variant: MoveVariant; target: MoveTarget } } | { Prio: { priority: number; variant: PrioVariant } } | "Clear" | { Load: { playlist: string; range: [number, number] | null } } | { PlaylistAdd: { playlist: string; uri: string; position: number | null } } | { Play: ItemVariant | null } | { Pause: boolean } | "Stop" | "Next" | "Previous" | { SeekCur: SeekMode } | { SetVol: number } | { Consume: boolean } | { Random: boolean } | { Repeat: boolean } | { Single: SingleMode }
+export type BatchResult = "Done" | { Id: number }
+export type BatchResponse = { results: BatchResult[]; error: BatchError | null }
+export type BatchError = { index: number; code: number; message: string }
+export type PrioVariant = { Id: number } | { Range: [number, number] }
+export type NotificationSettings = { enabled: boolean; song_change: boolean; update_finished: boolean }
+export type Settings = { version: number; servers: ServerProfile[]; active_server: number; music_directory: string | null; cover_patterns: string[]; art_cache_limit: number; notifications: NotificationSettings; shortcuts: ShortcutSettings; scrobbling: ScrobbleSettings; elapsed_interval: number }
+export type ShortcutSettings = { global: boolean; bindings: { [key: string]: string } }
+export type ServerProfile = { name: string; host: string; port: number; password_set: boolean; scrobble: boolean }
+export type SearchHit = { song: Song; score: number }
+export type Artist = { name: string; sort_name: string; albums: string[]; song_count: number }
+export type Album = { id: string; title: string; title_sort: string; artist: string; artist_sort: string; compilation: boolean; date: string | null; discs: number; tracks: Song[]; duration: TVal; cover_key: string; genres: string[] }
+/**
+ * The queue or a stored playlist, as the source of an export or the target of an import.
+ */
+export type PlaylistSource = "Queue" | { Stored: string }
+export type PlaylistFormat = "M3u" | "M3u8" | "Xspf" | "Pls"
+export type ImportReport = { added: number; unmatched: UnmatchedEntry[] }
+/**
+ * A playlist file entry that matched no song in the database.
+ */
+export type UnmatchedEntry = { index: number; location: string; title: string | null; artist: string | null }
+export type PlaylistEdit = { Sort: { tags: Tag[]; descending: boolean } } | "Dedupe" | "Reverse" | "Shuffle"
+/**
+ * A song of a stored playlist together with its position in it.
+ */
+export type PlaylistMatch = { position: number; song: Song }
+export type SmartPlaylist = { name: string; filter: Filter; sort: Tag | null; descending: boolean; limit: number | null; random: boolean; target: string | null }
+export type AutoDjConfig = { source: AutoDjSource; min_upcoming: number; history: number }
+export type AutoDjStatus = { running: boolean; config: AutoDjConfig; added: number; error: string | null }
+export type AutoDjSource = { SmartPlaylist: string } | { Filter: Filter } | "RandomAlbum"
+export type SongStats = { rating: number | null; play_count: number; skip_count: number; last_played: number | null }
+/**
+ * A song with its `rating` sticker.
+ */
+export type RatedSong = { song: Song; rating: number }
+export type StickerOperator = "Eq" | "Gt" | "Lt" | "IntEq" | "IntGt" | "IntLt" | "Contains" | "StartsWith"
+export type StickerType = "Song" | "Playlist" | { Tag: Tag }
+/**
+ * Comparison of numeric sticker values, done locally so it works with any MPD version.
+ */
+export type NumericComparison = "Eq" | "Ne" | "Gt" | "Ge" | "Lt" | "Le"
+export type StickerSort = "Uri" | "Value" | "ValueInt"
+export type HistoryFormat = "Csv" | "Json"
+export type HistoryPage = { entries: HistoryEntry[]; total: number }
+export type HistoryQuery = { from: number | null; to: number | null; artist: string | null; album: string | null; completed_only: boolean }
+export type HistoryEntry = { id: number; uri: string; tags: { [key: string]: string[] }; started: number; duration: TVal | null; listened: TVal; completed: boolean; server: string }
+export type Streak = { days: number; start: string }
+export type ForgottenFavourite = { uri: string; title: string | null; artist: string | null; plays: number; last_played: number }
+export type RankedItem = { name: string; artist: string | null; uri: string | null; plays: number; listening_time: TVal }
+export type ListeningReport = { period: ReportPeriod; from: number; to: number; plays: number; skips: number; listening_time: TVal; top_artists: RankedItem[]; top_albums: RankedItem[]; top_tracks: RankedItem[]; top_genres: RankedItem[]; heatmap: number[][]; longest_streak: Streak | null; current_streak: Streak | null; forgotten_favourites: ForgottenFavourite[] }
+export type ReportPeriod = "Week" | "Month" | "Year" | "AllTime"
+export type ScrobbleSettings = { lastfm: LastFmSettings; listenbrainz: ListenBrainzSettings }
+export type ScrobbleStatus = { pending: number; last_error: string | null; lastfm_user: string | null; lastfm_secret_set: boolean; listenbrainz_token_set: boolean }
+/**
+ * The API secret and session live in [`crate::credentials::CredentialStore`].
+ */
+export type LastFmSettings = { enabled: boolean; api_url: string; api_key: string }
+/**
+ * The user token lives in [`crate::credentials::CredentialStore`].
+ */
+export type ListenBrainzSettings = { enabled: boolean; api_url: string }
+export type LyricsSource = "File" | "Tag"
+export type Lyrics = { uri: string; source: LyricsSource; synced: boolean; lines: LyricLine[] }
+export type LyricLine = { time: TVal | null; text: string }
+export type SleepTrigger = { Minutes: number } | "EndOfSong" | "EndOfAlbum"
+export type SleepAction = "Stop" | "Pause"
+export type SleepTimerStatus = { config: SleepTimerConfig; remaining: TVal; fading: boolean }
+export type SleepTimerConfig = { trigger: SleepTrigger; action: SleepAction; fade: number }
+/**
+ * The alarm going off next, as reported to the UI.
+ */
+export type NextAlarm = { name: string; at: number }
+export type Alarm = { name: string; enabled: boolean; hour: number; minute: number; weekdays: Weekday[]; source: AlarmSource; replace_queue: boolean; volume: number | null; fade_in: number }
+export type Weekday = "Monday" | "Tuesday" | "Wednesday" | "Thursday" | "Friday" | "Saturday" | "Sunday"
+export type AlarmSource = { Playlist: string } | { SmartPlaylist: string }
+/**
+ * Position in the current song, emitted as `elapsed`.
+ */
+export type PlaybackPosition = { elapsed: TVal; duration: TVal | null; playing: boolean }